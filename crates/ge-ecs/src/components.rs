@@ -0,0 +1,119 @@
+use crate::Entity;
+use ge_world::BlockType;
+use nalgebra::Vector3;
+
+/// Where an entity is and which way it is facing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub position: Vector3<f32>,
+    /// Rotation around the vertical axis in radians.
+    pub yaw: f32,
+    /// Rotation up and down in radians.
+    pub pitch: f32,
+}
+
+impl Transform {
+    #[must_use]
+    pub fn new(position: impl Into<Vector3<f32>>, yaw: f32, pitch: f32) -> Self {
+        return Self {
+            position: position.into(),
+            yaw,
+            pitch,
+        };
+    }
+
+    /// Get the direction the entity is facing.
+    #[must_use]
+    pub fn forward(&self) -> Vector3<f32> {
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+
+        return -Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, -sin_pitch).normalize();
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        return Self::new(Vector3::zeros(), 0.0, 0.0);
+    }
+}
+
+/// How fast an entity moves, in blocks per second.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Velocity(pub Vector3<f32>);
+
+/// An axis aligned box around the position of an entity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collider {
+    pub half_extents: Vector3<f32>,
+}
+
+impl Collider {
+    #[must_use]
+    pub fn new(half_extents: impl Into<Vector3<f32>>) -> Self {
+        return Self {
+            half_extents: half_extents.into(),
+        };
+    }
+
+    /// Get the lowest and highest corner of the box when the entity is at `position`.
+    #[must_use]
+    pub fn bounds(&self, position: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        return (position - self.half_extents, position + self.half_extents);
+    }
+
+    /// Returns `true` if the boxes of two entities overlap.
+    #[must_use]
+    pub fn overlaps(
+        &self,
+        position: Vector3<f32>,
+        other: &Collider,
+        other_pos: Vector3<f32>,
+    ) -> bool {
+        let (min, max) = self.bounds(position);
+        let (other_min, other_max) = other.bounds(other_pos);
+        return (0..3).all(|i| return min[i] < other_max[i] && other_min[i] < max[i]);
+    }
+}
+
+/// Draws an entity as a block.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Renderable {
+    pub block: BlockType,
+    /// The size of the block, where 1 is the size of a block in the world.
+    pub scale: f32,
+}
+
+impl Renderable {
+    #[must_use]
+    pub fn new(block: BlockType, scale: f32) -> Self {
+        return Self { block, scale };
+    }
+}
+
+/// Marks the entity that is controlled by the player.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Player;
+
+/// Keeps an entity at an offset from another entity, facing the same way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Follow {
+    pub target: Entity,
+    pub offset: Vector3<f32>,
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlaps() {
+        let a = Collider::new([0.5, 0.5, 1.0]);
+        let b = Collider::new([0.5, 0.5, 0.5]);
+        let origin = Vector3::zeros();
+        assert!(a.overlaps(origin, &b, Vector3::new(0.9, 0.0, 1.4)));
+        assert!(!a.overlaps(origin, &b, Vector3::new(1.0, 0.0, 0.0)));
+        assert!(!a.overlaps(origin, &b, Vector3::new(0.0, 0.0, 1.5)));
+    }
+}
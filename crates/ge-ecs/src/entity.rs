@@ -0,0 +1,81 @@
+use std::fmt;
+
+/// A handle to an entity in a `World`.
+///
+/// The index of a despawned entity is reused, but with a new generation, so old handles to it
+/// are never mistaken for the new entity.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    /// The index of the entity, which is unique among the entities that are alive.
+    #[must_use]
+    pub fn index(self) -> u32 {
+        return self.index;
+    }
+
+    #[must_use]
+    pub fn generation(self) -> u32 {
+        return self.generation;
+    }
+}
+
+impl fmt::Debug for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "Entity({}v{})", self.index, self.generation);
+    }
+}
+
+/// Hands out entities and keeps track of which are alive.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Entities {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+}
+
+impl Entities {
+    pub fn spawn(&mut self) -> Entity {
+        if let Some(index) = self.free.pop() {
+            let i = index as usize;
+            self.alive[i] = true;
+            return Entity {
+                index,
+                generation: self.generations[i],
+            };
+        }
+
+        let index = u32::try_from(self.generations.len()).expect("too many entities");
+        self.generations.push(0);
+        self.alive.push(true);
+        return Entity {
+            index,
+            generation: 0,
+        };
+    }
+
+    /// Returns `false` if the entity was not alive.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let i = entity.index as usize;
+        self.alive[i] = false;
+        self.generations[i] = self.generations[i].wrapping_add(1);
+        self.free.push(entity.index);
+        return true;
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let i = entity.index as usize;
+        return self.alive.get(i).copied().unwrap_or(false)
+            && self.generations[i] == entity.generation;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.alive.len() - self.free.len();
+    }
+}
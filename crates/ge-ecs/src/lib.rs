@@ -0,0 +1,29 @@
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::module_name_repetitions)]
+#![allow(clippy::similar_names)]
+#![allow(clippy::default_trait_access)]
+#![deny(missing_debug_implementations)]
+#![deny(missing_copy_implementations)]
+//
+#![feature(lint_reasons)]
+
+pub mod components;
+mod entity;
+mod schedule;
+mod storage;
+pub mod systems;
+mod world;
+
+pub use entity::Entity;
+pub use schedule::Schedule;
+pub use storage::SparseSet;
+pub use world::World;
+
+/// Data that can be attached to an entity.
+///
+/// This is implemented for every type that can be sent between threads.
+pub trait Component: Send + Sync + 'static {}
+
+impl<T: Send + Sync + 'static> Component for T {}
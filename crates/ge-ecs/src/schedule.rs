@@ -0,0 +1,154 @@
+use crate::World;
+use std::{fmt, time::Duration};
+
+/// A system is called once every tick with the world and the length of a tick in seconds.
+type System = Box<dyn FnMut(&mut World, f32) + Send>;
+
+/// Runs systems in a fixed order at a fixed tick rate.
+///
+/// Time that is passed to `update` is saved up and spent in whole ticks, so the systems see the
+/// same time step no matter how fast frames are drawn.
+pub struct Schedule {
+    systems: Vec<(&'static str, System)>,
+    tick: Duration,
+    max_ticks: u32,
+    accumulator: Duration,
+    ticks: u64,
+}
+
+impl fmt::Debug for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.systems().collect::<Vec<_>>();
+        return f
+            .debug_struct("Schedule")
+            .field("systems", &names)
+            .field("tick", &self.tick)
+            .field("max_ticks", &self.max_ticks)
+            .field("accumulator", &self.accumulator)
+            .field("ticks", &self.ticks)
+            .finish();
+    }
+}
+
+impl Schedule {
+    /// Create a schedule that runs `rate` ticks per second.
+    ///
+    /// # Panics
+    /// If `rate` is zero.
+    #[must_use]
+    pub fn new(rate: u32) -> Self {
+        assert!(rate > 0, "the tick rate must be positive");
+        return Self {
+            systems: Vec::new(),
+            tick: Duration::from_secs(1) / rate,
+            max_ticks: 5,
+            accumulator: Duration::ZERO,
+            ticks: 0,
+        };
+    }
+
+    /// Set the most ticks that are run in a single update.
+    ///
+    /// When updates fall further behind than this, for example after the window was dragged, the
+    /// remaining time is dropped instead of trying to catch up.
+    #[must_use]
+    pub fn with_max_ticks(mut self, max_ticks: u32) -> Self {
+        self.max_ticks = max_ticks;
+        return self;
+    }
+
+    /// Add a system that runs after every system that was added before it.
+    #[must_use]
+    pub fn with_system(
+        mut self,
+        name: &'static str,
+        system: impl FnMut(&mut World, f32) + Send + 'static,
+    ) -> Self {
+        self.systems.push((name, Box::new(system)));
+        return self;
+    }
+
+    /// The names of the systems, in the order they run.
+    pub fn systems(&self) -> impl Iterator<Item = &'static str> + '_ {
+        return self.systems.iter().map(|&(name, _)| return name);
+    }
+
+    /// The length of a tick.
+    #[must_use]
+    pub fn tick(&self) -> Duration {
+        return self.tick;
+    }
+
+    /// The number of ticks that were run since the schedule was created.
+    #[must_use]
+    pub fn ticks(&self) -> u64 {
+        return self.ticks;
+    }
+
+    /// How far the time is between the last tick and the next one, from 0 to 1.
+    #[must_use]
+    pub fn alpha(&self) -> f32 {
+        return self.accumulator.as_secs_f32() / self.tick.as_secs_f32();
+    }
+
+    /// Run every system once.
+    pub fn run(&mut self, world: &mut World) {
+        let dt = self.tick.as_secs_f32();
+        for (_, system) in &mut self.systems {
+            system(world, dt);
+        }
+        self.ticks += 1;
+    }
+
+    /// Run as many ticks as fit in the time that passed, and return how many ran.
+    pub fn update(&mut self, world: &mut World, elapsed: Duration) -> u32 {
+        self.accumulator += elapsed;
+        let mut ran = 0;
+        while self.accumulator >= self.tick {
+            if ran == self.max_ticks {
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+            self.run(world);
+            self.accumulator -= self.tick;
+            ran += 1;
+        }
+        return ran;
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn fixed_ticks_in_order() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let (first, second) = (Arc::clone(&log), Arc::clone(&log));
+        let mut schedule = Schedule::new(10)
+            .with_max_ticks(3)
+            .with_system("first", move |_, dt| {
+                first.lock().unwrap().push(("first", dt))
+            })
+            .with_system("second", move |_, dt| {
+                second.lock().unwrap().push(("second", dt))
+            });
+        let mut world = World::new();
+
+        assert_eq!(
+            vec!["first", "second"],
+            schedule.systems().collect::<Vec<_>>()
+        );
+        assert_eq!(0, schedule.update(&mut world, Duration::from_millis(50)));
+        assert_eq!(1, schedule.update(&mut world, Duration::from_millis(75)));
+        assert_eq!(vec![("first", 0.1), ("second", 0.1)], *log.lock().unwrap());
+        assert!((schedule.alpha() - 0.25).abs() < 1e-3);
+
+        // falling far behind only runs the maximum number of ticks
+        assert_eq!(3, schedule.update(&mut world, Duration::from_secs(10)));
+        assert_eq!(4, schedule.ticks());
+        assert_eq!(0.0, schedule.alpha());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{Component, Entity};
+use std::any::Any;
+
+/// Stores one type of component in a sparse set.
+///
+/// Components are packed together in a dense array, so iterating over them is fast, and a sparse
+/// array indexed by entity finds the component of a single entity in constant time.
+#[derive(Debug, Clone)]
+pub struct SparseSet<T> {
+    sparse: Vec<Option<u32>>,
+    dense: Vec<T>,
+    entities: Vec<Entity>,
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        return Self {
+            sparse: Vec::new(),
+            dense: Vec::new(),
+            entities: Vec::new(),
+        };
+    }
+}
+
+impl<T> SparseSet<T> {
+    fn slot(&self, entity: Entity) -> Option<usize> {
+        let slot = (*self.sparse.get(entity.index() as usize)?)? as usize;
+        // the index may belong to an older generation of the entity
+        return (self.entities[slot] == entity).then_some(slot);
+    }
+
+    /// Attach a component to an entity, returning the component it replaced.
+    ///
+    /// # Panics
+    /// If there are more than `u32::MAX` components.
+    pub fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        if let Some(slot) = self.slot(entity) {
+            return Some(std::mem::replace(&mut self.dense[slot], value));
+        }
+
+        let index = entity.index() as usize;
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, None);
+        }
+        // a component of an older generation of the entity is dropped
+        if let Some(slot) = self.sparse[index] {
+            self.remove_slot(slot as usize);
+        }
+        let slot = u32::try_from(self.dense.len()).expect("too many components");
+        self.sparse[index] = Some(slot);
+        self.dense.push(value);
+        self.entities.push(entity);
+        return None;
+    }
+
+    /// Remove the component of an entity.
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slot(entity)?;
+        return Some(self.remove_slot(slot));
+    }
+
+    fn remove_slot(&mut self, slot: usize) -> T {
+        let entity = self.entities.swap_remove(slot);
+        self.sparse[entity.index() as usize] = None;
+        if let Some(&moved) = self.entities.get(slot) {
+            #[allow(
+                clippy::cast_possible_truncation,
+                reason = "slots are checked when inserting"
+            )]
+            let slot = slot as u32;
+            self.sparse[moved.index() as usize] = Some(slot);
+        }
+        return self.dense.swap_remove(slot);
+    }
+
+    #[must_use]
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        return self.slot(entity).map(|slot| return &self.dense[slot]);
+    }
+
+    #[must_use]
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        return self.slot(entity).map(|slot| return &mut self.dense[slot]);
+    }
+
+    #[must_use]
+    pub fn contains(&self, entity: Entity) -> bool {
+        return self.slot(entity).is_some();
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        return self.dense.len();
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        return self.dense.is_empty();
+    }
+
+    /// Iterate over every entity with this component, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> + '_ {
+        return self.entities.iter().copied().zip(&self.dense);
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> + '_ {
+        return self.entities.iter().copied().zip(&mut self.dense);
+    }
+}
+
+/// A component storage with the type erased, so storages of all types can be kept together.
+pub(crate) trait Storage: Any + Send + Sync {
+    fn remove_entity(&mut self, entity: Entity);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Component> Storage for SparseSet<T> {
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        return self;
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        return self;
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::Entities;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut entities = Entities::default();
+        let [a, b, c] = [(); 3].map(|_| return entities.spawn());
+
+        let mut set = SparseSet::default();
+        assert_eq!(None, set.insert(a, 1));
+        assert_eq!(None, set.insert(b, 2));
+        assert_eq!(None, set.insert(c, 3));
+        assert_eq!(Some(2), set.insert(b, 20));
+
+        // removing from the middle keeps the other components reachable
+        assert_eq!(Some(1), set.remove(a));
+        assert_eq!(None, set.get(a));
+        assert_eq!(Some(&20), set.get(b));
+        assert_eq!(Some(&3), set.get(c));
+        assert_eq!(2, set.len());
+
+        let mut all = set.iter().map(|(e, &v)| return (e, v)).collect::<Vec<_>>();
+        all.sort();
+        assert_eq!(vec![(b, 20), (c, 3)], all);
+    }
+
+    #[test]
+    fn generations() {
+        let mut entities = Entities::default();
+        let old = entities.spawn();
+        let mut set = SparseSet::default();
+        set.insert(old, "old");
+
+        // the index is reused by the next entity, which must not see the old component
+        entities.despawn(old);
+        let new = entities.spawn();
+        assert_eq!(old.index(), new.index());
+        assert!(!entities.is_alive(old));
+        assert_eq!(None, set.get(new));
+
+        assert_eq!(None, set.insert(new, "new"));
+        assert_eq!(None, set.get(old));
+        assert_eq!(Some(&"new"), set.get(new));
+        assert_eq!(1, set.len());
+    }
+}
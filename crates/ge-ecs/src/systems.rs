@@ -0,0 +1,75 @@
+//! Systems that are shared by every game, to be added to a `Schedule`.
+
+use crate::{
+    components::{Follow, Transform, Velocity},
+    World,
+};
+
+/// Move every entity by its velocity.
+pub fn movement(world: &mut World, dt: f32) {
+    world.join_mut::<Transform, Velocity>(|_, transform, velocity| {
+        transform.position += velocity.0 * dt;
+    });
+}
+
+/// Move every entity that follows another to its target.
+///
+/// Entities whose target has no transform, for example because it was despawned, stay where
+/// they are.
+pub fn follow(world: &mut World, _dt: f32) {
+    let targets = world
+        .join::<Follow, Transform>()
+        .filter_map(|(e, follow, _)| {
+            let target = world.get::<Transform>(follow.target)?;
+            return Some((e, *target, follow.offset));
+        })
+        .collect::<Vec<_>>();
+
+    for (e, target, offset) in targets {
+        if let Some(transform) = world.get_mut::<Transform>(e) {
+            *transform = Transform {
+                position: target.position + offset,
+                ..target
+            };
+        }
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Schedule;
+    use nalgebra::Vector3;
+    use std::time::Duration;
+
+    #[test]
+    fn move_and_follow() {
+        let mut world = World::new();
+        let player = world.spawn();
+        world.insert(player, Transform::new([0.0, 0.0, 10.0], 1.0, 0.0));
+        world.insert(player, Velocity(Vector3::new(2.0, 0.0, 0.0)));
+        let camera = world.spawn();
+        world.insert(camera, Transform::default());
+        world.insert(
+            camera,
+            Follow {
+                target: player,
+                offset: Vector3::new(0.0, 0.0, 1.5),
+            },
+        );
+
+        let mut schedule = Schedule::new(4)
+            .with_system("movement", movement)
+            .with_system("follow", follow);
+        assert_eq!(2, schedule.update(&mut world, Duration::from_millis(500)));
+
+        let expected = Transform::new([1.0, 0.0, 11.5], 1.0, 0.0);
+        assert_eq!(Some(&expected), world.get::<Transform>(camera));
+
+        // a camera without a target stays put
+        world.despawn(player);
+        schedule.run(&mut world);
+        assert_eq!(Some(&expected), world.get::<Transform>(camera));
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{
+    entity::Entities,
+    storage::{SparseSet, Storage},
+    Component, Entity,
+};
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    fmt,
+};
+
+/// Every entity, their components and the resources shared by all systems.
+#[derive(Default)]
+pub struct World {
+    entities: Entities,
+    storages: HashMap<TypeId, Box<dyn Storage>>,
+    resources: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl fmt::Debug for World {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f
+            .debug_struct("World")
+            .field("entities", &self.entities.len())
+            .field("storages", &self.storages.len())
+            .field("resources", &self.resources.len())
+            .finish();
+    }
+}
+
+impl World {
+    #[must_use]
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Create an entity without any components.
+    pub fn spawn(&mut self) -> Entity {
+        return self.entities.spawn();
+    }
+
+    /// Remove an entity and all of its components.
+    ///
+    /// Returns `false` if the entity was already despawned.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.entities.despawn(entity) {
+            return false;
+        }
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(entity);
+        }
+        return true;
+    }
+
+    #[must_use]
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        return self.entities.is_alive(entity);
+    }
+
+    /// The number of entities that are alive.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        return self.entities.len();
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    /// Attach a component to an entity, returning the component of the same type it replaced.
+    ///
+    /// # Panics
+    /// If the entity was despawned.
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> Option<T> {
+        assert!(self.is_alive(entity), "{entity:?} was despawned");
+        return self.storage_mut::<T>().insert(entity, component);
+    }
+
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        return self.storage_mut::<T>().remove(entity);
+    }
+
+    #[must_use]
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        return self.storage::<T>()?.get(entity);
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        return self.storage_mut::<T>().get_mut(entity);
+    }
+
+    /// Get the storage of a component type, or `None` if it was never used.
+    #[must_use]
+    pub fn storage<T: Component>(&self) -> Option<&SparseSet<T>> {
+        return self
+            .storages
+            .get(&TypeId::of::<T>())
+            .map(|s| return Self::downcast(s.as_ref()));
+    }
+
+    /// Get the storage of a component type, creating it if it was never used.
+    pub fn storage_mut<T: Component>(&mut self) -> &mut SparseSet<T> {
+        let storage = self
+            .storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| return Box::<SparseSet<T>>::default());
+        return Self::downcast_mut(storage.as_mut());
+    }
+
+    fn downcast<T: Component>(storage: &dyn Storage) -> &SparseSet<T> {
+        return storage
+            .as_any()
+            .downcast_ref()
+            .expect("storages are keyed by their type");
+    }
+
+    fn downcast_mut<T: Component>(storage: &mut dyn Storage) -> &mut SparseSet<T> {
+        return storage
+            .as_any_mut()
+            .downcast_mut()
+            .expect("storages are keyed by their type");
+    }
+
+    /// Iterate over every entity with a component of type `T`.
+    pub fn query<T: Component>(&self) -> impl Iterator<Item = (Entity, &T)> + '_ {
+        return self.storage::<T>().into_iter().flat_map(SparseSet::iter);
+    }
+
+    pub fn query_mut<T: Component>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> + '_ {
+        return self.storage_mut::<T>().iter_mut();
+    }
+
+    /// Iterate over every entity with components of both types `A` and `B`.
+    pub fn join<A: Component, B: Component>(&self) -> impl Iterator<Item = (Entity, &A, &B)> + '_ {
+        let b = self.storage::<B>();
+        return self.query::<A>().filter_map(move |(e, a)| {
+            return b.and_then(|b| return b.get(e)).map(|b| return (e, a, b));
+        });
+    }
+
+    /// Call `f` for every entity with components of both types `A` and `B`, with mutable access
+    /// to both.
+    ///
+    /// # Panics
+    /// If `A` and `B` are the same type.
+    pub fn join_mut<A: Component, B: Component>(
+        &mut self,
+        mut f: impl FnMut(Entity, &mut A, &mut B),
+    ) {
+        assert_ne!(
+            TypeId::of::<A>(),
+            TypeId::of::<B>(),
+            "cannot borrow {} twice",
+            type_name::<A>()
+        );
+        self.storage_mut::<A>();
+        self.storage_mut::<B>();
+
+        let [Some(a), Some(b)] = self
+            .storages
+            .get_disjoint_mut([&TypeId::of::<A>(), &TypeId::of::<B>()])
+        else {
+            unreachable!("both storages were just created");
+        };
+        let (a, b) = (
+            Self::downcast_mut::<A>(a.as_mut()),
+            Self::downcast_mut::<B>(b.as_mut()),
+        );
+        for (e, a) in a.iter_mut() {
+            if let Some(b) = b.get_mut(e) {
+                f(e, a, b);
+            }
+        }
+    }
+
+    /// Add a resource that is shared by all systems, returning the one it replaced.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "resources are keyed by their type"
+    )]
+    pub fn insert_resource<T: Component>(&mut self, resource: T) -> Option<T> {
+        return self
+            .resources
+            .insert(TypeId::of::<T>(), Box::new(resource))
+            .map(|r| return *r.downcast().expect("resources are keyed by their type"));
+    }
+
+    #[must_use]
+    pub fn resource<T: Component>(&self) -> Option<&T> {
+        return self
+            .resources
+            .get(&TypeId::of::<T>())
+            .and_then(|r| return r.downcast_ref());
+    }
+
+    pub fn resource_mut<T: Component>(&mut self) -> Option<&mut T> {
+        return self
+            .resources
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|r| return r.downcast_mut());
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+
+    #[derive(Debug, PartialEq)]
+    struct Speed(i32);
+
+    #[test]
+    fn components() {
+        let mut world = World::new();
+        let a = world.spawn();
+        let b = world.spawn();
+        world.insert(a, Position(0));
+        world.insert(a, Speed(2));
+        world.insert(b, Position(10));
+
+        assert_eq!(2, world.query::<Position>().count());
+        assert_eq!(
+            vec![a],
+            world
+                .join::<Position, Speed>()
+                .map(|(e, _, _)| return e)
+                .collect::<Vec<_>>()
+        );
+
+        world.join_mut::<Position, Speed>(|_, p, s| {
+            p.0 += s.0;
+            s.0 = 0;
+        });
+        assert_eq!(Some(&Position(2)), world.get(a));
+        assert_eq!(Some(&Speed(0)), world.get(a));
+        assert_eq!(Some(&Position(10)), world.get(b));
+
+        assert!(world.despawn(a));
+        assert!(!world.despawn(a));
+        assert_eq!(None, world.get::<Position>(a));
+        assert_eq!(0, world.query::<Speed>().count());
+        assert_eq!(1, world.len());
+    }
+
+    #[test]
+    fn resources() {
+        let mut world = World::new();
+        assert_eq!(None, world.resource::<Speed>());
+        assert_eq!(None, world.insert_resource(Speed(1)));
+        world.resource_mut::<Speed>().unwrap().0 += 1;
+        assert_eq!(Some(Speed(2)), world.insert_resource(Speed(3)));
+        assert_eq!(Some(&Speed(3)), world.resource());
+    }
+}
@@ -0,0 +1,78 @@
+//! Run a dedicated server that generates the world and streams it to clients.
+//!
+//! ```text
+//! ge-server [--config config/engine.toml] [--bind 0.0.0.0:25565] [--seed <seed>]
+//! ```
+//!
+//! The server runs until it is killed.
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use ge_net::{Server, ServerConfig};
+use ge_util::{cli, Seed};
+use std::path::PathBuf;
+
+#[derive(Debug)]
+struct Args {
+    config: PathBuf,
+    bind: String,
+    seed: Option<Seed>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut parsed = Args {
+        config: PathBuf::from("config/engine.toml"),
+        bind: "0.0.0.0:25565".to_owned(),
+        seed: None,
+    };
+
+    cli::parse_args(std::env::args().skip(1), |key, value| {
+        match key {
+            "--config" => parsed.config = PathBuf::from(&value),
+            "--bind" => parsed.bind = value,
+            "--seed" => parsed.seed = Some(Seed::from_text(&value)),
+            _ => return Err(format!("unknown argument: {key}")),
+        }
+        return Ok(());
+    })?;
+    return Ok(parsed);
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!("usage: ge-server [--config path] [--bind addr] [--seed seed]");
+            std::process::exit(2);
+        }
+    };
+
+    tracing_subscriber::fmt()
+        .compact()
+        .with_target(false)
+        .with_max_level(tracing::Level::INFO)
+        .init();
+
+    let config = match cli::load_config(&args.config, args.seed) {
+        Ok((config, warning)) => {
+            if let Some(warning) = warning {
+                tracing::warn!("{warning}");
+            }
+            config
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let server = match Server::bind(&args.bind, ServerConfig::new(config)) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("could not listen on {}: {e}", args.bind);
+            std::process::exit(1);
+        }
+    };
+    server.spawn().expect("could not start the server").join();
+}
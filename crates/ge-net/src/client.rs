@@ -0,0 +1,108 @@
+use crate::{
+    protocol::{read_message, write_message, Welcome},
+    ClientMessage, NetError, ServerMessage, PROTOCOL_VERSION,
+};
+use std::{
+    net::{Shutdown, TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// A connection to a server.
+///
+/// Messages from the server are read on a separate thread, so `poll` never blocks.
+#[derive(Debug)]
+pub struct Client {
+    stream: TcpStream,
+    welcome: Welcome,
+    messages: Receiver<ServerMessage>,
+}
+
+impl Client {
+    /// Connect to a server and join it as `name`.
+    ///
+    /// # Errors
+    /// Errors if the server cannot be reached or rejects the client.
+    pub fn connect(addr: impl ToSocketAddrs, name: &str) -> Result<Self, NetError> {
+        let mut stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let hello = ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: name.to_owned(),
+        };
+        write_message(&mut stream, &hello.encode())?;
+
+        let welcome = match ServerMessage::decode(&read_message(&mut stream)?)? {
+            ServerMessage::Welcome(welcome) => welcome,
+            ServerMessage::Rejected { reason } => return Err(NetError::Rejected(reason)),
+            _ => return Err(NetError::Invalid("expected welcome")),
+        };
+        info!(
+            "joined {:?} as player {}",
+            stream.peer_addr(),
+            welcome.player
+        );
+
+        let (tx, messages) = mpsc::channel();
+        let mut reader = stream.try_clone()?;
+        thread::Builder::new()
+            .name("ge-client".to_owned())
+            .spawn(move || loop {
+                match read_message(&mut reader).and_then(|m| return ServerMessage::decode(&m)) {
+                    Ok(message) => {
+                        if tx.send(message).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => {
+                        debug!("disconnected from the server: {e}");
+                        return;
+                    }
+                }
+            })?;
+
+        return Ok(Self {
+            stream,
+            welcome,
+            messages,
+        });
+    }
+
+    /// What the server sent when the client joined.
+    #[must_use]
+    pub fn welcome(&self) -> Welcome {
+        return self.welcome;
+    }
+
+    /// # Errors
+    /// Errors if the connection was closed.
+    pub fn send(&mut self, message: &ClientMessage) -> Result<(), NetError> {
+        return write_message(&mut self.stream, &message.encode());
+    }
+
+    /// Take every message that arrived since the last poll, without blocking.
+    pub fn poll(&self) -> impl Iterator<Item = ServerMessage> + '_ {
+        return self.messages.try_iter();
+    }
+
+    /// Wait for the next message.
+    ///
+    /// # Errors
+    /// Errors if no message arrived within `timeout` or the connection was closed.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<ServerMessage, NetError> {
+        return self.messages.recv_timeout(timeout).map_err(|e| {
+            return match e {
+                RecvTimeoutError::Timeout => NetError::Timeout,
+                RecvTimeoutError::Disconnected => NetError::Disconnected,
+            };
+        });
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        // wakes the reader thread up so that it exits
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
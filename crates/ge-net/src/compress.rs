@@ -0,0 +1,163 @@
+use crate::NetError;
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE, SECTIONS_PER_CHUNK, SECTION_SIZE},
+    ChunkOffset, ChunkPos,
+};
+use ge_world::{BlockType, Chunk};
+
+#[allow(clippy::cast_sign_loss, reason = "constants are positive")]
+const BLOCKS_PER_CHUNK: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_HEIGHT) as usize;
+#[allow(clippy::cast_sign_loss, reason = "constants are positive")]
+const BLOCKS_PER_SECTION: usize = (CHUNK_SIZE * CHUNK_SIZE * SECTION_SIZE) as usize;
+
+/// Compress the blocks of a chunk with run-length encoding.
+///
+/// Blocks are visited in `z`, `y`, `x` order, so the layers of stone, dirt and air that make up
+/// most of the terrain become a few long runs. Each run is stored as its length in LEB128 followed
+/// by the id of the block type.
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "positions are always inside the chunk"
+)]
+#[must_use]
+pub fn compress(chunk: &Chunk) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut run = (BlockType::Air, 0);
+    let mut push = |ty: BlockType, count: usize| {
+        if run.0 == ty {
+            run.1 += count;
+            return;
+        }
+        write_run(&mut out, run);
+        run = (ty, count);
+    };
+
+    #[allow(clippy::cast_sign_loss, reason = "constant is positive")]
+    for i in 0..SECTIONS_PER_CHUNK as usize {
+        if chunk.section(i).is_none() {
+            push(BlockType::Air, BLOCKS_PER_SECTION);
+            continue;
+        }
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let base_z = i as i32 * SECTION_SIZE;
+        for z in base_z..base_z + SECTION_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let pos = ChunkPos::new(x, y, z).expect("position is inside the chunk");
+                    push(chunk.get(pos), 1);
+                }
+            }
+        }
+    }
+    write_run(&mut out, run);
+    return out;
+}
+
+fn write_run(out: &mut Vec<u8>, (ty, mut count): (BlockType, usize)) {
+    if count == 0 {
+        return;
+    }
+    while count >= 0x80 {
+        #[allow(
+            clippy::cast_possible_truncation,
+            reason = "only the low bits are kept"
+        )]
+        out.push((count as u8 & 0x7f) | 0x80);
+        count >>= 7;
+    }
+    #[allow(clippy::cast_possible_truncation, reason = "count is below 0x80")]
+    out.push(count as u8);
+    out.push(ty.id());
+}
+
+/// Decompress the blocks of a chunk that were compressed with `compress`.
+///
+/// # Errors
+/// Errors if the data is truncated, contains an unknown block type or does not cover exactly one
+/// chunk.
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "positions are checked to be inside the chunk"
+)]
+pub fn decompress(position: ChunkOffset, data: &[u8]) -> Result<Chunk, NetError> {
+    let mut chunk = Chunk::new(position);
+    let mut bytes = data.iter().copied();
+    let mut index = 0;
+    while index < BLOCKS_PER_CHUNK {
+        let mut count = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = bytes.next().ok_or(NetError::Invalid("truncated chunk"))?;
+            if shift > 21 {
+                return Err(NetError::Invalid("run is too long"));
+            }
+            count |= usize::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        let id = bytes.next().ok_or(NetError::Invalid("truncated chunk"))?;
+        let ty = BlockType::from_id(id).ok_or(NetError::Invalid("unknown block type"))?;
+        if count == 0 || index + count > BLOCKS_PER_CHUNK {
+            return Err(NetError::Invalid("runs do not cover the chunk"));
+        }
+
+        if ty != BlockType::Air {
+            for i in index..index + count {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+                let i = i as i32;
+                let pos = ChunkPos::new(
+                    i % CHUNK_SIZE,
+                    i / CHUNK_SIZE % CHUNK_SIZE,
+                    i / (CHUNK_SIZE * CHUNK_SIZE),
+                )
+                .expect("index is inside the chunk");
+                chunk.set(pos, ty);
+            }
+        }
+        index += count;
+    }
+
+    if bytes.next().is_some() {
+        return Err(NetError::Invalid("trailing data after chunk"));
+    }
+    return Ok(chunk);
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let offset = ChunkOffset::new(3, -4, 0).unwrap();
+        let mut chunk = Chunk::new(offset);
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.fill_column(x, y, 0..100, BlockType::Stone);
+                chunk.set(ChunkPos::new(x, y, 100).unwrap(), BlockType::Grass);
+            }
+        }
+        chunk.set(ChunkPos::new(5, 6, 200).unwrap(), BlockType::Wood);
+
+        let data = compress(&chunk);
+        assert!(data.len() < 32, "{} bytes", data.len());
+        assert_eq!(chunk, decompress(offset, &data).unwrap());
+
+        let empty = Chunk::new(offset);
+        assert_eq!(empty, decompress(offset, &compress(&empty)).unwrap());
+    }
+
+    #[test]
+    fn invalid() {
+        let offset = ChunkOffset::default();
+        let data = compress(&Chunk::new(offset));
+        assert!(decompress(offset, &data[..data.len() - 1]).is_err());
+        assert!(decompress(offset, &[data.as_slice(), &[1, 0]].concat()).is_err());
+        // a single run of 1 block of an unknown type
+        assert!(decompress(offset, &[1, 200]).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+#![warn(clippy::pedantic)]
+#![allow(clippy::module_name_repetitions)]
+#![allow(clippy::similar_names)]
+#![allow(clippy::default_trait_access)]
+#![deny(missing_debug_implementations)]
+#![deny(missing_copy_implementations)]
+//
+#![feature(lint_reasons)]
+
+#[macro_use]
+extern crate tracing;
+
+pub mod client;
+pub mod compress;
+pub mod protocol;
+pub mod server;
+
+pub use client::Client;
+pub use protocol::{ClientMessage, ServerMessage, Welcome, PROTOCOL_VERSION};
+pub use server::{Server, ServerConfig, ServerHandle};
+
+use ge_world::trns::StageError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum NetError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid world-gen pipeline: {0}")]
+    Stage(#[from] StageError),
+    #[error("invalid message: {0}")]
+    Invalid(&'static str),
+    #[error("protocol version {0} is not supported, expected {PROTOCOL_VERSION}")]
+    UnsupportedVersion(u16),
+    #[error("rejected by the server: {0}")]
+    Rejected(String),
+    #[error("timed out waiting for the server")]
+    Timeout,
+    #[error("disconnected from the server")]
+    Disconnected,
+}
@@ -0,0 +1,473 @@
+use crate::{compress, NetError};
+use ge_util::{ChunkOffset, Seed, WorldPos};
+use ge_world::{BlockType, Chunk};
+use std::io::{Read, Write};
+
+/// The version of the protocol, which must be the same on the client and the server.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// The largest message that is accepted, which is far more than a compressed chunk needs.
+pub const MAX_MESSAGE_LEN: usize = 1 << 20;
+
+/// A message sent from a client to the server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClientMessage {
+    /// The first message of every connection.
+    Hello { version: u16, name: String },
+    /// The player moved or turned.
+    Move {
+        position: [f32; 3],
+        yaw: f32,
+        pitch: f32,
+    },
+    /// The player wants to change a block.
+    Edit { pos: WorldPos, block: BlockType },
+}
+
+/// What the server tells a client after accepting it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Welcome {
+    /// The id of the client's player.
+    pub player: u32,
+    pub seed: Seed,
+    pub spawn: [f32; 3],
+    /// The radius in chunks around the player that chunks are sent for.
+    pub view_distance: u32,
+}
+
+/// A message sent from the server to a client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerMessage {
+    /// The answer to `Hello` if the client was accepted.
+    Welcome(Welcome),
+    /// The answer to `Hello` if the client was not accepted, after which the server disconnects.
+    Rejected {
+        reason: String,
+    },
+    /// The full contents of a chunk, which replace anything the client had for it.
+    Chunk(Chunk),
+    /// The chunk is out of range and will not receive any more updates.
+    Unload(ChunkOffset),
+    /// A block was changed by a player.
+    BlockChanged {
+        pos: WorldPos,
+        block: BlockType,
+    },
+    PlayerJoined {
+        player: u32,
+        name: String,
+    },
+    PlayerMoved {
+        player: u32,
+        position: [f32; 3],
+        yaw: f32,
+        pitch: f32,
+    },
+    PlayerLeft {
+        player: u32,
+    },
+}
+
+/// Write a message, prefixed by its length.
+///
+/// # Errors
+/// Errors if the message cannot be written.
+pub fn write_message(writer: &mut impl Write, payload: &[u8]) -> Result<(), NetError> {
+    let len = u32::try_from(payload.len()).map_err(|_| return NetError::Invalid("too long"))?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(payload)?;
+    writer.flush()?;
+    return Ok(());
+}
+
+/// Read a message that was written with `write_message`.
+///
+/// # Errors
+/// Errors if the message cannot be read or is longer than `MAX_MESSAGE_LEN`.
+pub fn read_message(reader: &mut impl Read) -> Result<Vec<u8>, NetError> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_MESSAGE_LEN {
+        return Err(NetError::Invalid("message is too long"));
+    }
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload)?;
+    return Ok(payload);
+}
+
+impl ClientMessage {
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        match self {
+            ClientMessage::Hello { version, name } => {
+                w.u8(0);
+                w.u16(*version);
+                w.string(name);
+            }
+            ClientMessage::Move {
+                position,
+                yaw,
+                pitch,
+            } => {
+                w.u8(1);
+                w.vec3(*position);
+                w.f32(*yaw);
+                w.f32(*pitch);
+            }
+            ClientMessage::Edit { pos, block } => {
+                w.u8(2);
+                w.world_pos(*pos);
+                w.u8(block.id());
+            }
+        }
+        return w.0;
+    }
+
+    /// # Errors
+    /// Errors if the message is not a valid client message.
+    pub fn decode(bytes: &[u8]) -> Result<Self, NetError> {
+        let mut r = Reader(bytes);
+        let message = match r.u8()? {
+            0 => ClientMessage::Hello {
+                version: r.u16()?,
+                name: r.string()?,
+            },
+            1 => ClientMessage::Move {
+                position: r.vec3()?,
+                yaw: r.f32()?,
+                pitch: r.f32()?,
+            },
+            2 => ClientMessage::Edit {
+                pos: r.world_pos()?,
+                block: r.block()?,
+            },
+            _ => return Err(NetError::Invalid("unknown client message")),
+        };
+        r.finish()?;
+        return Ok(message);
+    }
+}
+
+impl ServerMessage {
+    #[must_use]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::default();
+        match self {
+            ServerMessage::Welcome(welcome) => {
+                w.u8(0);
+                w.u32(welcome.player);
+                w.u64(welcome.seed.value());
+                w.vec3(welcome.spawn);
+                w.u32(welcome.view_distance);
+            }
+            ServerMessage::Rejected { reason } => {
+                w.u8(1);
+                w.string(reason);
+            }
+            ServerMessage::Chunk(chunk) => {
+                w.u8(2);
+                w.chunk_offset(chunk.position);
+                w.bytes(&compress::compress(chunk));
+            }
+            ServerMessage::Unload(offset) => {
+                w.u8(3);
+                w.chunk_offset(*offset);
+            }
+            ServerMessage::BlockChanged { pos, block } => {
+                w.u8(4);
+                w.world_pos(*pos);
+                w.u8(block.id());
+            }
+            ServerMessage::PlayerJoined { player, name } => {
+                w.u8(5);
+                w.u32(*player);
+                w.string(name);
+            }
+            ServerMessage::PlayerMoved {
+                player,
+                position,
+                yaw,
+                pitch,
+            } => {
+                w.u8(6);
+                w.u32(*player);
+                w.vec3(*position);
+                w.f32(*yaw);
+                w.f32(*pitch);
+            }
+            ServerMessage::PlayerLeft { player } => {
+                w.u8(7);
+                w.u32(*player);
+            }
+        }
+        return w.0;
+    }
+
+    /// # Errors
+    /// Errors if the message is not a valid server message.
+    pub fn decode(bytes: &[u8]) -> Result<Self, NetError> {
+        let mut r = Reader(bytes);
+        let message = match r.u8()? {
+            0 => ServerMessage::Welcome(Welcome {
+                player: r.u32()?,
+                seed: Seed::new(r.u64()?),
+                spawn: r.vec3()?,
+                view_distance: r.u32()?,
+            }),
+            1 => ServerMessage::Rejected {
+                reason: r.string()?,
+            },
+            2 => {
+                let offset = r.chunk_offset()?;
+                ServerMessage::Chunk(compress::decompress(offset, r.bytes()?)?)
+            }
+            3 => ServerMessage::Unload(r.chunk_offset()?),
+            4 => ServerMessage::BlockChanged {
+                pos: r.world_pos()?,
+                block: r.block()?,
+            },
+            5 => ServerMessage::PlayerJoined {
+                player: r.u32()?,
+                name: r.string()?,
+            },
+            6 => ServerMessage::PlayerMoved {
+                player: r.u32()?,
+                position: r.vec3()?,
+                yaw: r.f32()?,
+                pitch: r.f32()?,
+            },
+            7 => ServerMessage::PlayerLeft { player: r.u32()? },
+            _ => return Err(NetError::Invalid("unknown server message")),
+        };
+        r.finish()?;
+        return Ok(message);
+    }
+}
+
+/// Writes values in little endian.
+#[derive(Debug, Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u16(&mut self, v: u16) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i32(&mut self, v: i32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn vec3(&mut self, v: [f32; 3]) {
+        for v in v {
+            self.f32(v);
+        }
+    }
+
+    fn bytes(&mut self, v: &[u8]) {
+        #[allow(clippy::cast_possible_truncation, reason = "messages are far smaller")]
+        self.u32(v.len() as u32);
+        self.0.extend_from_slice(v);
+    }
+
+    fn string(&mut self, v: &str) {
+        self.bytes(v.as_bytes());
+    }
+
+    fn world_pos(&mut self, v: WorldPos) {
+        self.i32(v.x());
+        self.i32(v.y());
+        self.i32(v.z());
+    }
+
+    fn chunk_offset(&mut self, v: ChunkOffset) {
+        self.i32(v.x());
+        self.i32(v.y());
+    }
+}
+
+/// Reads values that were written by `Writer`.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], NetError> {
+        if self.0.len() < N {
+            return Err(NetError::Invalid("message is truncated"));
+        }
+        let (head, rest) = self.0.split_at(N);
+        self.0 = rest;
+        return Ok(head.try_into().expect("length is checked"));
+    }
+
+    fn u8(&mut self) -> Result<u8, NetError> {
+        return Ok(self.take::<1>()?[0]);
+    }
+
+    fn u16(&mut self) -> Result<u16, NetError> {
+        return Ok(u16::from_le_bytes(self.take()?));
+    }
+
+    fn u32(&mut self) -> Result<u32, NetError> {
+        return Ok(u32::from_le_bytes(self.take()?));
+    }
+
+    fn i32(&mut self) -> Result<i32, NetError> {
+        return Ok(i32::from_le_bytes(self.take()?));
+    }
+
+    fn u64(&mut self) -> Result<u64, NetError> {
+        return Ok(u64::from_le_bytes(self.take()?));
+    }
+
+    fn f32(&mut self) -> Result<f32, NetError> {
+        return Ok(f32::from_le_bytes(self.take()?));
+    }
+
+    fn vec3(&mut self) -> Result<[f32; 3], NetError> {
+        return Ok([self.f32()?, self.f32()?, self.f32()?]);
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], NetError> {
+        let len = self.u32()? as usize;
+        if self.0.len() < len {
+            return Err(NetError::Invalid("message is truncated"));
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        return Ok(head);
+    }
+
+    fn string(&mut self) -> Result<String, NetError> {
+        return String::from_utf8(self.bytes()?.to_vec())
+            .map_err(|_| return NetError::Invalid("string is not utf-8"));
+    }
+
+    fn block(&mut self) -> Result<BlockType, NetError> {
+        return BlockType::from_id(self.u8()?).ok_or(NetError::Invalid("unknown block type"));
+    }
+
+    fn world_pos(&mut self) -> Result<WorldPos, NetError> {
+        return WorldPos::new(self.i32()?, self.i32()?, self.i32()?)
+            .map_err(|_| return NetError::Invalid("block position"));
+    }
+
+    fn chunk_offset(&mut self) -> Result<ChunkOffset, NetError> {
+        return ChunkOffset::new(self.i32()?, self.i32()?, 0)
+            .map_err(|_| return NetError::Invalid("chunk offset"));
+    }
+
+    fn finish(&self) -> Result<(), NetError> {
+        if !self.0.is_empty() {
+            return Err(NetError::Invalid("trailing data after message"));
+        }
+        return Ok(());
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ge_util::ChunkPos;
+
+    #[test]
+    fn client_messages() {
+        let messages = [
+            ClientMessage::Hello {
+                version: PROTOCOL_VERSION,
+                name: "steve".to_owned(),
+            },
+            ClientMessage::Move {
+                position: [1.0, -2.5, 100.0],
+                yaw: 0.5,
+                pitch: -0.25,
+            },
+            ClientMessage::Edit {
+                pos: WorldPos::new(-17, 3, 120).unwrap(),
+                block: BlockType::Wood,
+            },
+        ];
+        for message in messages {
+            assert_eq!(message, ClientMessage::decode(&message.encode()).unwrap());
+        }
+    }
+
+    #[test]
+    fn server_messages() {
+        let offset = ChunkOffset::new(-1, 2, 0).unwrap();
+        let mut chunk = Chunk::new(offset);
+        chunk.fill_column(3, 4, 0..50, BlockType::Stone);
+        chunk.set(ChunkPos::new(0, 0, 0).unwrap(), BlockType::Dirt);
+
+        let messages = [
+            ServerMessage::Welcome(Welcome {
+                player: 7,
+                seed: Seed::from_text("net"),
+                spawn: [0.0, 15.0, 105.0],
+                view_distance: 4,
+            }),
+            ServerMessage::Rejected {
+                reason: "full".to_owned(),
+            },
+            ServerMessage::Chunk(chunk),
+            ServerMessage::Unload(offset),
+            ServerMessage::BlockChanged {
+                pos: WorldPos::new(1, 2, 3).unwrap(),
+                block: BlockType::Air,
+            },
+            ServerMessage::PlayerJoined {
+                player: 2,
+                name: "alex".to_owned(),
+            },
+            ServerMessage::PlayerMoved {
+                player: 2,
+                position: [4.0, 5.0, 6.0],
+                yaw: 1.0,
+                pitch: 0.0,
+            },
+            ServerMessage::PlayerLeft { player: 2 },
+        ];
+        for message in messages {
+            assert_eq!(message, ServerMessage::decode(&message.encode()).unwrap());
+        }
+    }
+
+    #[test]
+    fn framing() {
+        let mut buffer = Vec::new();
+        let payload = ClientMessage::Hello {
+            version: PROTOCOL_VERSION,
+            name: "a".to_owned(),
+        }
+        .encode();
+        write_message(&mut buffer, &payload).unwrap();
+        write_message(&mut buffer, &[]).unwrap();
+
+        let mut reader = buffer.as_slice();
+        assert_eq!(payload, read_message(&mut reader).unwrap());
+        assert_eq!(Vec::<u8>::new(), read_message(&mut reader).unwrap());
+        assert!(read_message(&mut reader).is_err());
+
+        assert!(ClientMessage::decode(&[9]).is_err());
+        assert!(ClientMessage::decode(&payload[..payload.len() - 1]).is_err());
+        let too_long = (MAX_MESSAGE_LEN as u32 + 1).to_le_bytes();
+        assert!(read_message(&mut too_long.as_slice()).is_err());
+    }
+}
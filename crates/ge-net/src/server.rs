@@ -0,0 +1,562 @@
+use crate::{
+    protocol::{read_message, write_message, Welcome},
+    ClientMessage, NetError, ServerMessage, PROTOCOL_VERSION,
+};
+use ge_util::{coords::CHUNK_SIZE, ChunkOffset, EngineConfig, WorldPos};
+use ge_world::{gen::AsyncWorldGenerator, BlockType, Chunk, World};
+use rayon::prelude::*;
+use std::{
+    collections::{HashMap, HashSet},
+    io::ErrorKind,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How long the server waits for a message before checking whether it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The number of messages that may wait to be written to a client before it is disconnected.
+const OUTBOX_LEN: usize = 1024;
+
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// The radius in chunks around each player that chunks are sent for.
+    pub view_distance: u32,
+    pub max_players: usize,
+    /// How long a new connection may take to send its hello before it is closed.
+    pub hello_timeout: Duration,
+    pub engine: EngineConfig,
+}
+
+impl ServerConfig {
+    /// Use the world generation, render distance and spawn of the engine config.
+    #[must_use]
+    pub fn new(engine: EngineConfig) -> Self {
+        return Self {
+            view_distance: u32::try_from(engine.world_gen.render_distance).unwrap_or(u32::MAX),
+            max_players: 16,
+            hello_timeout: Duration::from_secs(5),
+            engine,
+        };
+    }
+}
+
+/// Something that happened on one of the connection threads.
+#[derive(Debug)]
+enum Event {
+    Joined {
+        player: u32,
+        name: String,
+        stream: TcpStream,
+        outbox: SyncSender<Vec<u8>>,
+    },
+    Message(u32, ClientMessage),
+    Left(u32),
+    /// A chunk that was generated in the background.
+    Generated(Chunk),
+}
+
+/// A player that is connected to the server.
+#[derive(Debug)]
+struct Connection {
+    name: String,
+    stream: TcpStream,
+    /// The messages waiting for the writer thread of the connection.
+    outbox: SyncSender<Vec<u8>>,
+    position: [f32; 3],
+    yaw: f32,
+    pitch: f32,
+    /// The chunks that were sent to the client and not unloaded since.
+    chunks: HashSet<ChunkOffset>,
+}
+
+impl Connection {
+    /// Queue a message without waiting for the client, disconnecting it if it cannot keep up.
+    fn send(&mut self, message: &ServerMessage) {
+        // the reader thread notices the connection is gone and removes the player
+        match self.outbox.try_send(message.encode()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                warn!("'{}' is not keeping up, disconnecting it", self.name);
+                let _ = self.stream.shutdown(Shutdown::Both);
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                debug!(
+                    "could not send to '{}', the connection is closed",
+                    self.name
+                );
+            }
+        }
+    }
+}
+
+/// A dedicated server that owns the world and streams chunks to its clients.
+///
+/// The server is authoritative: clients only ever see the chunks it sent them, and block edits
+/// only take effect once the server applied them and sent them back.
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    config: ServerConfig,
+    gen: Arc<AsyncWorldGenerator>,
+    world: World,
+    /// The chunks that were edited, which are kept even when no player is close to them.
+    edited: HashSet<ChunkOffset>,
+    /// The chunks that are being generated in the background.
+    generating: HashSet<ChunkOffset>,
+    clients: HashMap<u32, Connection>,
+    events: Sender<Event>,
+    inbox: Receiver<Event>,
+}
+
+/// A server that runs on its own thread.
+#[derive(Debug)]
+pub struct ServerHandle {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl ServerHandle {
+    /// The address the server listens on.
+    #[must_use]
+    pub fn addr(&self) -> SocketAddr {
+        return self.addr;
+    }
+
+    /// Stop the server, disconnect every client and wait for it to finish.
+    pub fn shutdown(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.join();
+    }
+
+    /// Wait until the server stops.
+    pub fn join(self) {
+        if self.thread.join().is_err() {
+            error!("the server panicked");
+        }
+    }
+}
+
+impl Server {
+    /// Listen on `addr`, which may use port 0 to pick any free port.
+    ///
+    /// # Errors
+    /// Errors if the address cannot be bound or the world-gen pipeline cannot be built.
+    pub fn bind(addr: impl ToSocketAddrs, config: ServerConfig) -> Result<Self, NetError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let gen = AsyncWorldGenerator::from_config((0, 0), &config.engine)?;
+        let (events, inbox) = mpsc::channel();
+        return Ok(Self {
+            listener,
+            config,
+            gen: Arc::new(gen),
+            world: World { chunks: Vec::new() },
+            edited: HashSet::new(),
+            generating: HashSet::new(),
+            clients: HashMap::new(),
+            events,
+            inbox,
+        });
+    }
+
+    /// The address the server listens on.
+    ///
+    /// # Errors
+    /// Errors if the address of the listener cannot be read.
+    pub fn local_addr(&self) -> Result<SocketAddr, NetError> {
+        return Ok(self.listener.local_addr()?);
+    }
+
+    /// Run the server on a new thread.
+    ///
+    /// # Errors
+    /// Errors if the address of the listener cannot be read.
+    pub fn spawn(self) -> Result<ServerHandle, NetError> {
+        let addr = self.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let thread = thread::Builder::new()
+            .name("ge-server".to_owned())
+            .spawn(move || return self.run(&flag))?;
+        return Ok(ServerHandle { addr, stop, thread });
+    }
+
+    /// Accept clients and handle their messages until `stop` is set.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "the accept thread only panics if spawning threads fails"
+    )]
+    pub fn run(mut self, stop: &Arc<AtomicBool>) {
+        info!("server listening on {:?}", self.listener.local_addr());
+        let listener = self.listener.try_clone().expect("listener can be cloned");
+        let accept = {
+            let events = self.events.clone();
+            let timeout = self.config.hello_timeout;
+            let stop = Arc::clone(stop);
+            thread::spawn(move || return accept(&listener, &events, timeout, &stop))
+        };
+
+        self.handle_events(stop);
+
+        for client in self.clients.values() {
+            let _ = client.stream.shutdown(Shutdown::Both);
+        }
+        let _ = accept.join();
+        info!("server stopped");
+    }
+
+    fn handle_events(&mut self, stop: &AtomicBool) {
+        while !stop.load(Ordering::Relaxed) {
+            let event = self.inbox.recv_timeout(POLL_INTERVAL);
+            match event {
+                Ok(Event::Joined {
+                    player,
+                    name,
+                    stream,
+                    outbox,
+                }) => self.join(player, name, stream, outbox),
+                Ok(Event::Message(player, message)) => self.handle(player, &message),
+                Ok(Event::Left(player)) => self.leave(player),
+                Ok(Event::Generated(chunk)) => self.generated(chunk),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+
+    fn join(&mut self, player: u32, name: String, stream: TcpStream, outbox: SyncSender<Vec<u8>>) {
+        let mut connection = Connection {
+            name,
+            stream,
+            outbox,
+            position: self.config.engine.camera.initial_position,
+            yaw: 0.0,
+            pitch: 0.0,
+            chunks: HashSet::new(),
+        };
+        if self.clients.len() >= self.config.max_players {
+            connection.send(&ServerMessage::Rejected {
+                reason: "the server is full".to_owned(),
+            });
+            // dropping the connection closes it once the writer thread sent the rejection
+            return;
+        }
+
+        info!("player {player} '{}' joined", connection.name);
+        connection.send(&ServerMessage::Welcome(Welcome {
+            player,
+            seed: self.config.engine.world_gen.seed,
+            spawn: connection.position,
+            view_distance: self.config.view_distance,
+        }));
+        for (&other, client) in &self.clients {
+            connection.send(&ServerMessage::PlayerJoined {
+                player: other,
+                name: client.name.clone(),
+            });
+            connection.send(&ServerMessage::PlayerMoved {
+                player: other,
+                position: client.position,
+                yaw: client.yaw,
+                pitch: client.pitch,
+            });
+        }
+        self.broadcast(&ServerMessage::PlayerJoined {
+            player,
+            name: connection.name.clone(),
+        });
+
+        self.clients.insert(player, connection);
+        self.stream_chunks(player);
+    }
+
+    fn leave(&mut self, player: u32) {
+        let Some(client) = self.clients.remove(&player) else {
+            return;
+        };
+        info!("player {player} '{}' left", client.name);
+        self.broadcast(&ServerMessage::PlayerLeft { player });
+        self.unload_unused();
+    }
+
+    fn handle(&mut self, player: u32, message: &ClientMessage) {
+        match *message {
+            ClientMessage::Hello { .. } => {
+                warn!("player {player} sent a second hello");
+            }
+            ClientMessage::Move {
+                position,
+                yaw,
+                pitch,
+            } => {
+                let Some(client) = self.clients.get_mut(&player) else {
+                    return;
+                };
+                let moved_chunk = chunk_at(client.position) != chunk_at(position);
+                client.position = position;
+                client.yaw = yaw;
+                client.pitch = pitch;
+
+                let moved = ServerMessage::PlayerMoved {
+                    player,
+                    position,
+                    yaw,
+                    pitch,
+                };
+                for (_, other) in self.clients.iter_mut().filter(|(&p, _)| return p != player) {
+                    other.send(&moved);
+                }
+                if moved_chunk {
+                    self.stream_chunks(player);
+                    self.unload_unused();
+                }
+            }
+            ClientMessage::Edit { pos, block } => self.edit(player, pos, block),
+        }
+    }
+
+    /// Apply an edit if the player can see the block, and send it to everyone who can see it.
+    fn edit(&mut self, player: u32, pos: WorldPos, block: BlockType) {
+        let offset = pos.to_chunk_offset();
+        if !self
+            .clients
+            .get(&player)
+            .is_some_and(|c| return c.chunks.contains(&offset))
+        {
+            warn!("player {player} tried to edit {pos} in a chunk it does not have");
+            return;
+        }
+        if self
+            .world
+            .set(pos, block)
+            .is_none_or(|before| return before == block)
+        {
+            return;
+        }
+        self.edited.insert(offset);
+
+        let changed = ServerMessage::BlockChanged { pos, block };
+        for client in self.clients.values_mut() {
+            if client.chunks.contains(&offset) {
+                client.send(&changed);
+            }
+        }
+    }
+
+    /// Send the chunks that came into view of a player and unload the ones that left it.
+    ///
+    /// Chunks that are not loaded yet are generated in the background and sent once they are done.
+    fn stream_chunks(&mut self, player: u32) {
+        let Some(client) = self.clients.get(&player) else {
+            return;
+        };
+        let wanted = self.chunks_around(client.position);
+        let unload = client
+            .chunks
+            .difference(&wanted)
+            .copied()
+            .collect::<Vec<_>>();
+        let mut load = wanted
+            .difference(&client.chunks)
+            .copied()
+            .collect::<Vec<_>>();
+        // send the closest chunks first
+        let center = chunk_at(client.position);
+        load.sort_unstable_by_key(|o| {
+            return (o.x() - center.x()).abs() + (o.y() - center.y()).abs();
+        });
+
+        let client = self
+            .clients
+            .get_mut(&player)
+            .expect("client was found above");
+        for offset in unload {
+            client.chunks.remove(&offset);
+            client.send(&ServerMessage::Unload(offset));
+        }
+        let mut missing = Vec::new();
+        for offset in load {
+            if let Some(chunk) = self.world.chunk(offset) {
+                client.chunks.insert(offset);
+                client.send(&ServerMessage::Chunk(chunk.clone()));
+            } else if self.generating.insert(offset) {
+                missing.push(offset);
+            }
+        }
+        self.generate(missing);
+    }
+
+    /// Generate chunks on rayon's global thread pool, which send themselves back as events.
+    fn generate(&self, offsets: Vec<ChunkOffset>) {
+        if offsets.is_empty() {
+            return;
+        }
+        let gen = Arc::clone(&self.gen);
+        let events = self.events.clone();
+        rayon::spawn(move || {
+            offsets.into_par_iter().for_each_with(events, |events, o| {
+                // the receiver only goes away with the server, which no longer needs the chunk
+                let _ = events.send(Event::Generated(gen.generate_chunk(o)));
+            });
+        });
+    }
+
+    /// Send a generated chunk to every player that can see it, and keep it if anyone could.
+    fn generated(&mut self, chunk: Chunk) {
+        let offset = chunk.position;
+        self.generating.remove(&offset);
+        let r = self.view_radius();
+        let mut seen = false;
+        for client in self.clients.values_mut() {
+            if in_view(client.position, offset, r) && client.chunks.insert(offset) {
+                client.send(&ServerMessage::Chunk(chunk.clone()));
+                seen = true;
+            }
+        }
+        if seen {
+            self.world.chunks.push(chunk);
+        }
+    }
+
+    /// Drop the chunks that no player can see, unless they were edited.
+    fn unload_unused(&mut self) {
+        let clients = &self.clients;
+        let edited = &self.edited;
+        self.world.chunks.retain(|chunk| {
+            return edited.contains(&chunk.position)
+                || clients
+                    .values()
+                    .any(|c| return c.chunks.contains(&chunk.position));
+        });
+    }
+
+    fn chunks_around(&self, position: [f32; 3]) -> HashSet<ChunkOffset> {
+        let center = chunk_at(position);
+        let r = self.view_radius();
+        return (-r..=r)
+            .flat_map(|x| return (-r..=r).map(move |y| return (x, y)))
+            .filter_map(|(x, y)| return ChunkOffset::new(center.x() + x, center.y() + y, 0).ok())
+            .collect();
+    }
+
+    fn view_radius(&self) -> i32 {
+        return i32::try_from(self.config.view_distance).unwrap_or(i32::MAX);
+    }
+
+    fn broadcast(&mut self, message: &ServerMessage) {
+        for client in self.clients.values_mut() {
+            client.send(message);
+        }
+    }
+}
+
+/// Get the chunk that contains a position.
+fn chunk_at(position: [f32; 3]) -> ChunkOffset {
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        reason = "positions are far smaller"
+    )]
+    let [x, y] = [position[0], position[1]].map(|v| return (v / CHUNK_SIZE as f32).floor() as i32);
+    return ChunkOffset::new(x, y, 0).unwrap_or_default();
+}
+
+/// Whether a chunk is within `r` chunks of a position.
+fn in_view(position: [f32; 3], offset: ChunkOffset, r: i32) -> bool {
+    let center = chunk_at(position);
+    return (offset.x() - center.x()).abs() <= r && (offset.y() - center.y()).abs() <= r;
+}
+
+/// Accept new connections until `stop` is set.
+fn accept(listener: &TcpListener, events: &Sender<Event>, timeout: Duration, stop: &AtomicBool) {
+    let mut next_player = 1;
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                debug!("connection from {addr}");
+                let player = next_player;
+                next_player += 1;
+                let events = events.clone();
+                let spawned = thread::Builder::new()
+                    .name(format!("ge-server-{player}"))
+                    .spawn(move || {
+                        if let Err(e) = read_client(player, stream, &events, timeout) {
+                            debug!("player {player} disconnected: {e}");
+                        }
+                        let _ = events.send(Event::Left(player));
+                    });
+                if let Err(e) = spawned {
+                    error!("could not spawn a thread for {addr}: {e}");
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(e) => error!("could not accept a connection: {e}"),
+        }
+    }
+}
+
+/// Check the handshake of a new client and forward its messages until it disconnects.
+///
+/// The client has `timeout` to send its hello, after which it may stay idle as long as it likes.
+fn read_client(
+    player: u32,
+    mut stream: TcpStream,
+    events: &Sender<Event>,
+    timeout: Duration,
+) -> Result<(), NetError> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(timeout))?;
+
+    let name = match ClientMessage::decode(&read_message(&mut stream)?)? {
+        ClientMessage::Hello { version, name } if version == PROTOCOL_VERSION => name,
+        ClientMessage::Hello { version, .. } => {
+            let reason = NetError::UnsupportedVersion(version).to_string();
+            write_message(&mut stream, &ServerMessage::Rejected { reason }.encode())?;
+            return Err(NetError::UnsupportedVersion(version));
+        }
+        _ => return Err(NetError::Invalid("expected hello")),
+    };
+    stream.set_read_timeout(None)?;
+
+    let (outbox, messages) = mpsc::sync_channel(OUTBOX_LEN);
+    let writer = stream.try_clone()?;
+    thread::Builder::new()
+        .name(format!("ge-server-{player}-writer"))
+        .spawn(move || return write_client(writer, &messages))?;
+    let joined = Event::Joined {
+        player,
+        name,
+        stream: stream.try_clone()?,
+        outbox,
+    };
+    if events.send(joined).is_err() {
+        return Ok(());
+    }
+
+    loop {
+        let message = ClientMessage::decode(&read_message(&mut stream)?)?;
+        if events.send(Event::Message(player, message)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Write the messages of a client until its connection is dropped or a write fails, then close it.
+fn write_client(mut stream: TcpStream, messages: &Receiver<Vec<u8>>) {
+    for message in messages {
+        if let Err(e) = write_message(&mut stream, &message) {
+            debug!("could not write to {:?}: {e}", stream.peer_addr());
+            break;
+        }
+    }
+    let _ = stream.shutdown(Shutdown::Both);
+}
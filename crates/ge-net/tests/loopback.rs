@@ -0,0 +1,161 @@
+//! Run a server on the loopback interface and connect headless clients to it.
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+#![allow(clippy::pedantic)]
+
+use ge_net::{
+    protocol::{read_message, write_message},
+    Client, ClientMessage, Server, ServerConfig, ServerHandle, ServerMessage,
+};
+use ge_util::{ChunkOffset, ChunkPos, EngineConfig};
+use ge_world::{BlockType, Chunk};
+use std::{collections::HashMap, io::Read, net::TcpStream, time::Duration};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn server() -> ServerHandle {
+    let mut engine = EngineConfig::default();
+    engine.world_gen.erosion.enabled = false;
+    let config = ServerConfig {
+        view_distance: 1,
+        hello_timeout: Duration::from_millis(200),
+        ..ServerConfig::new(engine)
+    };
+    return Server::bind("127.0.0.1:0", config)
+        .unwrap()
+        .spawn()
+        .unwrap();
+}
+
+/// Receive messages until one matches `f`, keeping track of the chunks the client has.
+fn wait_for(
+    client: &Client,
+    chunks: &mut HashMap<ChunkOffset, Chunk>,
+    f: impl Fn(&ServerMessage) -> bool,
+) -> ServerMessage {
+    loop {
+        let message = client.recv_timeout(TIMEOUT).unwrap();
+        match &message {
+            ServerMessage::Chunk(chunk) => {
+                chunks.insert(chunk.position, chunk.clone());
+            }
+            ServerMessage::Unload(offset) => {
+                chunks.remove(offset);
+            }
+            _ => {}
+        }
+        if f(&message) {
+            return message;
+        }
+    }
+}
+
+fn wait_for_chunks(client: &Client, chunks: &mut HashMap<ChunkOffset, Chunk>) {
+    while chunks.len() < 9 {
+        wait_for(client, chunks, |m| {
+            return matches!(m, ServerMessage::Chunk(_));
+        });
+    }
+}
+
+#[test]
+fn two_clients() {
+    let server = server();
+    let a = Client::connect(server.addr(), "a").unwrap();
+    let mut b = Client::connect(server.addr(), "b").unwrap();
+    assert_ne!(a.welcome().player, b.welcome().player);
+    assert_eq!(1, a.welcome().view_distance);
+
+    // chunks are generated in the background, so they may arrive before or after the join
+    let (mut a_chunks, mut b_chunks) = (HashMap::new(), HashMap::new());
+    let b_player = b.welcome().player;
+    wait_for(
+        &a,
+        &mut a_chunks,
+        |m| return matches!(m, ServerMessage::PlayerJoined { player, .. } if *player == b_player),
+    );
+    wait_for_chunks(&a, &mut a_chunks);
+    wait_for_chunks(&b, &mut b_chunks);
+    assert_eq!(a_chunks, b_chunks);
+
+    // an edit by one client is applied by the server and sent to both
+    let pos = ChunkPos::new(3, 4, 0)
+        .unwrap()
+        .to_world_pos(ChunkOffset::default());
+    assert_ne!(
+        BlockType::Wood,
+        a_chunks[&ChunkOffset::default()].get(pos.to_chunk_pos())
+    );
+    b.send(&ClientMessage::Edit {
+        pos,
+        block: BlockType::Wood,
+    })
+    .unwrap();
+    let changed = ServerMessage::BlockChanged {
+        pos,
+        block: BlockType::Wood,
+    };
+    for (client, chunks) in [(&a, &mut a_chunks), (&b, &mut b_chunks)] {
+        assert_eq!(changed, wait_for(client, chunks, |m| return *m == changed));
+    }
+
+    // moving to another chunk streams the chunks around it and is seen by the other client
+    b.send(&ClientMessage::Move {
+        position: [100.0, 100.0, 105.0],
+        yaw: 1.0,
+        pitch: 0.5,
+    })
+    .unwrap();
+    let moved = wait_for(&a, &mut a_chunks, |m| {
+        return matches!(m, ServerMessage::PlayerMoved { .. });
+    });
+    assert_eq!(
+        ServerMessage::PlayerMoved {
+            player: b_player,
+            position: [100.0, 100.0, 105.0],
+            yaw: 1.0,
+            pitch: 0.5,
+        },
+        moved
+    );
+    let far = ChunkOffset::new(6, 6, 0).unwrap();
+    wait_for(
+        &b,
+        &mut b_chunks,
+        |m| return matches!(m, ServerMessage::Chunk(c) if c.position == far),
+    );
+    wait_for_chunks(&b, &mut b_chunks);
+    assert!(b_chunks
+        .keys()
+        .all(|o| return (o.x() - 6).abs() <= 1 && (o.y() - 6).abs() <= 1));
+
+    drop(b);
+    wait_for(&a, &mut a_chunks, |m| {
+        return *m == ServerMessage::PlayerLeft { player: b_player };
+    });
+    server.shutdown();
+}
+
+#[test]
+fn wrong_version() {
+    let server = server();
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    let hello = ClientMessage::Hello {
+        version: 0,
+        name: "old".to_owned(),
+    };
+    write_message(&mut stream, &hello.encode()).unwrap();
+    let reply = ServerMessage::decode(&read_message(&mut stream).unwrap()).unwrap();
+    assert!(matches!(reply, ServerMessage::Rejected { .. }), "{reply:?}");
+    server.shutdown();
+}
+
+#[test]
+fn hello_timeout() {
+    let server = server();
+    let mut stream = TcpStream::connect(server.addr()).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    // a connection that never says hello is closed instead of keeping its thread forever
+    assert_eq!(0, stream.read(&mut [0; 1]).unwrap());
+    server.shutdown();
+}
@@ -0,0 +1,34 @@
+use ge_render::export::MeshExporter;
+use ge_resource::ResourceManager;
+use ge_util::EngineConfig;
+use ge_world::gen::AsyncWorldGenerator;
+
+const OUT: &str = "export";
+const CHUNKS: (i32, i32) = (4, 4);
+
+/// Generates the chunks around the origin with the engine config and saves them as
+/// `export/world.obj` (with `export/world.mtl`) and `export/world.glb`.
+fn main() {
+    let resources = ResourceManager::default();
+    let config: EngineConfig = resources.load_config("engine.toml").unwrap_or_default();
+
+    let start = std::time::Instant::now();
+    let world = AsyncWorldGenerator::from_config(CHUNKS, &config)
+        .unwrap()
+        .generate_region((-CHUNKS.0, -CHUNKS.1), CHUNKS);
+    let mesh = MeshExporter::new(&resources)
+        .unwrap()
+        .with_texture_dir("../assets")
+        .mesh(&world);
+    println!(
+        "meshed {} chunks into {} vertices in {:?}",
+        world.chunks.len(),
+        mesh.positions.len(),
+        start.elapsed()
+    );
+
+    std::fs::create_dir_all(OUT).unwrap();
+    mesh.save_obj(format!("{OUT}/world.obj")).unwrap();
+    mesh.save_glb(format!("{OUT}/world.glb")).unwrap();
+    println!("Saved meshes to '{OUT}'");
+}
@@ -0,0 +1,82 @@
+use ge_util::{cli, Seed};
+
+/// Command line arguments that override values from the config.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Args {
+    /// `--seed <seed>` overrides the world seed, accepting an integer or any string.
+    pub seed: Option<Seed>,
+    /// `--world <name>` opens (or creates) the world stored in `worlds/<name>`.
+    pub world: Option<String>,
+    /// `--connect <addr>` joins the server at `addr` instead of generating the world locally.
+    pub connect: Option<String>,
+    /// `--name <name>` is the name of the player on the server.
+    pub name: Option<String>,
+}
+
+impl Args {
+    /// Parse the arguments, ignoring (and logging) any that are not recognised.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Self {
+        let mut parsed = Self::default();
+        let result = cli::parse_args(args, |key, value| {
+            match key {
+                "--seed" => parsed.seed = Some(Seed::from_text(&value)),
+                "--world" => parsed.world = Some(value),
+                "--connect" => parsed.connect = Some(value),
+                "--name" => parsed.name = Some(value),
+                _ => warn!("unknown argument: {}", key),
+            }
+            return Ok(());
+        });
+        if let Err(e) = result {
+            warn!("{}", e);
+        }
+        return parsed;
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        return Args::parse(args.iter().map(|a| return a.to_string()));
+    }
+
+    #[test]
+    fn values() {
+        let args = parse(&[
+            "--seed",
+            "42",
+            "--world=home",
+            "--connect",
+            "127.0.0.1:7777",
+        ]);
+        assert_eq!(Some(Seed::new(42)), args.seed);
+        assert_eq!(Some("home".to_owned()), args.world);
+        assert_eq!(Some("127.0.0.1:7777".to_owned()), args.connect);
+        assert_eq!(None, args.name);
+        assert_eq!(Args::default(), parse(&[]));
+    }
+
+    #[test]
+    fn text_seeds() {
+        assert_eq!(
+            Some(Seed::from_text("hello")),
+            parse(&["--seed=hello"]).seed
+        );
+        assert_eq!(parse(&["--seed", "7"]).seed, parse(&["--seed=7"]).seed);
+    }
+
+    #[test]
+    fn bad_arguments_are_skipped() {
+        // unknown arguments are skipped without eating the next one
+        let args = parse(&["--fast", "--world", "home", "--seed"]);
+        assert_eq!(Some("home".to_owned()), args.world);
+        assert_eq!(None, args.seed);
+
+        // later values replace earlier ones
+        let args = parse(&["--connect=a:1", "--connect", "b:2"]);
+        assert_eq!(Some("b:2".to_owned()), args.connect);
+    }
+}
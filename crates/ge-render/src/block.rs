@@ -1,5 +1,6 @@
 use crate::renderer::Vertex;
 use bytemuck::{Pod, Zeroable};
+use ge_world::mesh::Face as MeshFace;
 use nalgebra::{Vector2, Vector3};
 
 #[repr(C)]
@@ -19,6 +20,25 @@ impl BlockVertex {
             tex_index,
         };
     }
+
+    #[must_use]
+    pub fn position(&self) -> [f32; 3] {
+        return self.position;
+    }
+
+    #[must_use]
+    pub fn tex_coords(&self) -> [f32; 2] {
+        return self.tex_coords;
+    }
+
+    /// The index of the face texture.
+    ///
+    /// On the unit cube this is the face in the order used by `BlockMeta`, while meshes store the
+    /// layer of the face in the block textures.
+    #[must_use]
+    pub fn tex_index(&self) -> u32 {
+        return self.tex_index;
+    }
 }
 
 impl Vertex for BlockVertex {
@@ -100,8 +120,8 @@ impl Block {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum FaceDirection {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FaceDirection {
     Top,
     Bottom,
     Left,
@@ -121,6 +141,40 @@ macro_rules! vert {
 }
 
 impl FaceDirection {
+    pub const ALL: [FaceDirection; 6] = [
+        FaceDirection::Top,
+        FaceDirection::Bottom,
+        FaceDirection::Left,
+        FaceDirection::Right,
+        FaceDirection::Front,
+        FaceDirection::Back,
+    ];
+
+    /// The direction the face points in, in block coordinates.
+    #[must_use]
+    pub fn normal(self) -> [i32; 3] {
+        return match self {
+            FaceDirection::Top => [0, 0, 1],
+            FaceDirection::Bottom => [0, 0, -1],
+            FaceDirection::Left => [-1, 0, 0],
+            FaceDirection::Right => [1, 0, 0],
+            FaceDirection::Front => [0, 1, 0],
+            FaceDirection::Back => [0, -1, 0],
+        };
+    }
+
+    /// The axes that the u and v texture coordinates of the face run along.
+    #[must_use]
+    pub fn tex_axes(self) -> (usize, usize) {
+        return match self {
+            FaceDirection::Top | FaceDirection::Bottom => (0, 1),
+            FaceDirection::Left | FaceDirection::Right => (1, 2),
+            FaceDirection::Front | FaceDirection::Back => (0, 2),
+        };
+    }
+
+    /// The vertices of the face on a unit cube, wound counter-clockwise when seen from outside.
+    #[must_use]
     pub fn get_vertices(self) -> [BlockVertex; 4] {
         return match self {
             FaceDirection::Top => [
@@ -163,6 +217,19 @@ impl FaceDirection {
     }
 }
 
+impl From<MeshFace> for FaceDirection {
+    fn from(face: MeshFace) -> Self {
+        return match face {
+            MeshFace::Top => FaceDirection::Top,
+            MeshFace::Bottom => FaceDirection::Bottom,
+            MeshFace::Left => FaceDirection::Left,
+            MeshFace::Right => FaceDirection::Right,
+            MeshFace::Front => FaceDirection::Front,
+            MeshFace::Back => FaceDirection::Back,
+        };
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 #[allow(unused)]
 struct Face {
@@ -1,4 +1,7 @@
-use crate::camera::Camera;
+use ge_ecs::{
+    components::{Transform, Velocity},
+    Entity, World,
+};
 use ge_util::deg_to_rad;
 use nalgebra::Vector3;
 use std::f32::consts::FRAC_PI_2;
@@ -84,29 +87,22 @@ impl CameraController {
         self.rotate_vertical = dy as f32;
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, dt: f64) {
+    /// Turn the player by the mouse movement and set its velocity from the pressed keys.
+    ///
+    /// The player is moved by the movement system on the next tick.
+    pub fn update_player(&mut self, world: &mut World, player: Entity, dt: f64) {
         #[allow(
             clippy::cast_possible_truncation,
             reason = "needs standardisation: see #2"
         )]
         let dt = dt as f32;
-
-        // Move forward/backward and left/right
-        let (yaw_sin, yaw_cos) = camera.yaw.sin_cos();
-        let forward = Vector3::new(yaw_cos, yaw_sin, 0.0).normalize();
-        let right = Vector3::new(yaw_sin, -yaw_cos, 0.0).normalize();
-        // let forward = Vector3::new(yaw_sin, yaw_cos, 0.0).normalize();
-        // let right = Vector3::new(-yaw_cos, yaw_sin, 0.0).normalize();
-        camera.position += forward * (self.amount_forward - self.amount_backward) * self.speed * dt;
-        camera.position += right * (self.amount_right - self.amount_left) * self.speed * dt;
-
-        // Move up/down. Since we don't use roll, we can just
-        // modify the y coordinate directly.
-        camera.position.z += (self.amount_up - self.amount_down) * self.speed * dt;
+        let Some(transform) = world.get_mut::<Transform>(player) else {
+            return;
+        };
 
         // Rotate
-        camera.yaw += deg_to_rad(-self.rotate_horizontal) * self.sensitivity / self.aspect * dt;
-        camera.pitch += deg_to_rad(self.rotate_vertical) * self.sensitivity * dt;
+        transform.yaw += deg_to_rad(-self.rotate_horizontal) * self.sensitivity / self.aspect * dt;
+        transform.pitch += deg_to_rad(self.rotate_vertical) * self.sensitivity * dt;
 
         // If process_mouse isn't called every frame, these values
         // will not get set to zero, and the camera will rotate
@@ -115,12 +111,24 @@ impl CameraController {
         self.rotate_vertical = 0.0;
 
         // Keep the camera's angle from going too high/low.
-        if camera.pitch < -SAFE_FRAC_PI_2 {
-            camera.pitch = -SAFE_FRAC_PI_2;
-        } else if camera.pitch > SAFE_FRAC_PI_2 {
-            camera.pitch = SAFE_FRAC_PI_2;
+        if transform.pitch < -SAFE_FRAC_PI_2 {
+            transform.pitch = -SAFE_FRAC_PI_2;
+        } else if transform.pitch > SAFE_FRAC_PI_2 {
+            transform.pitch = SAFE_FRAC_PI_2;
         }
 
-        camera.yaw = camera.yaw.rem_euclid(2.0 * std::f32::consts::PI);
+        transform.yaw = transform.yaw.rem_euclid(2.0 * std::f32::consts::PI);
+
+        // Move forward/backward and left/right
+        let (yaw_sin, yaw_cos) = transform.yaw.sin_cos();
+        let forward = Vector3::new(yaw_cos, yaw_sin, 0.0).normalize();
+        let right = Vector3::new(yaw_sin, -yaw_cos, 0.0).normalize();
+        let mut velocity = forward * (self.amount_forward - self.amount_backward)
+            + right * (self.amount_right - self.amount_left);
+
+        // Move up/down. Since we don't use roll, we can just
+        // modify the z coordinate directly.
+        velocity.z = self.amount_up - self.amount_down;
+        world.insert(player, Velocity(velocity * self.speed));
     }
 }
@@ -3,6 +3,7 @@ pub mod projection;
 pub mod uniform;
 
 use crate::text::DrawText;
+use ge_ecs::components::Transform;
 use nalgebra::{Matrix4, Vector3};
 use nalgebra_glm::look_at_lh;
 
@@ -22,19 +23,29 @@ impl Camera {
         };
     }
 
+    /// Get the direction the camera is looking in.
     #[must_use]
-    pub fn calc_matrix(&self) -> Matrix4<f32> {
+    pub fn forward(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
 
-        let look_dir =
-            &Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, -sin_pitch).normalize();
-        let target = self.position - look_dir;
+        return -Vector3::new(cos_pitch * cos_yaw, cos_pitch * sin_yaw, -sin_pitch).normalize();
+    }
+
+    #[must_use]
+    pub fn calc_matrix(&self) -> Matrix4<f32> {
+        let target = self.position + self.forward();
 
         return look_at_lh(&self.position, &target, &Vector3::z_axis());
     }
 }
 
+impl From<Transform> for Camera {
+    fn from(value: Transform) -> Self {
+        return Self::new(value.position, value.yaw, value.pitch);
+    }
+}
+
 impl DrawText for Camera {
     #[inline]
     fn name(&self) -> &'static str {
@@ -1,3 +1,4 @@
+use ge_util::{clock::Sky, Frustum};
 use nalgebra::{Matrix4, Vector3};
 
 #[repr(C)]
@@ -5,6 +6,11 @@ use nalgebra::{Matrix4, Vector3};
 pub struct CameraUniform {
     view_position: [f32; 4],
     view_proj: [[f32; 4]; 4],
+    sun_direction: [f32; 4],
+    moon_direction: [f32; 4],
+    sky_color: [f32; 4],
+    /// The ambient light level, the sky light intensity and the time of day.
+    light: [f32; 4],
 }
 
 impl CameraUniform {
@@ -13,6 +19,10 @@ impl CameraUniform {
         return Self {
             view_position: [0.0; 4],
             view_proj: Matrix4::identity().into(),
+            sun_direction: [0.0, 0.0, 1.0, 0.0],
+            moon_direction: [0.0, 0.0, -1.0, 0.0],
+            sky_color: [0.0; 4],
+            light: [1.0, 1.0, 0.5, 0.0],
         };
     }
 
@@ -25,6 +35,24 @@ impl CameraUniform {
         self.view_position = position.to_homogeneous().into();
         self.view_proj = (projection_matrix * camera_matrix).into();
     }
+
+    /// The volume the camera can see with the current view-projection matrix.
+    #[must_use]
+    pub fn frustum(&self) -> Frustum {
+        return Frustum::from_matrix(&self.view_proj.into());
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "precision is not important"
+    )]
+    pub fn update_sky(&mut self, sky: &Sky, time_of_day: f64) {
+        self.sun_direction = sky.sun.to_homogeneous().into();
+        self.moon_direction = sky.moon.to_homogeneous().into();
+        let [r, g, b] = sky.color;
+        self.sky_color = [r, g, b, 1.0];
+        self.light = [sky.ambient, sky.sky_light, time_of_day as f32, 0.0];
+    }
 }
 
 impl Default for CameraUniform {
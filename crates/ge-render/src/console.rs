@@ -0,0 +1,130 @@
+use std::{
+    io::BufRead,
+    str::FromStr,
+    sync::mpsc::{self, Receiver},
+};
+
+/// A command typed into the terminal that the engine was started from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Command {
+    /// `time` logs the current world time.
+    Time,
+    /// `time set <time>` sets the time of day, either as a number from 0 (midnight) to 1 or as
+    /// one of `midnight`, `sunrise`, `day`, `noon`, `sunset` and `night`.
+    SetTime(f64),
+    /// `time freeze` and `time unfreeze` stop and resume the clock.
+    Freeze(bool),
+    /// `time rate <rate>` sets how many times faster than real time the clock runs.
+    Rate(f64),
+    /// `wireframe` toggles wireframe mode, and `wireframe on` and `wireframe off` set it.
+    Wireframe(Option<bool>),
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        return match words.as_slice() {
+            ["time"] => Ok(Command::Time),
+            ["time", "freeze"] => Ok(Command::Freeze(true)),
+            ["time", "unfreeze"] => Ok(Command::Freeze(false)),
+            ["time", "set", time] => {
+                let time = match *time {
+                    "midnight" => 0.0,
+                    "sunrise" => 0.25,
+                    "day" => 0.3,
+                    "noon" => 0.5,
+                    "sunset" => 0.75,
+                    "night" => 0.85,
+                    time => time
+                        .parse()
+                        .ok()
+                        .filter(|t: &f64| return t.is_finite())
+                        .ok_or_else(|| return format!("invalid time: {time}"))?,
+                };
+                Ok(Command::SetTime(time))
+            }
+            ["time", "rate", rate] => rate
+                .parse()
+                .ok()
+                .filter(|r: &f64| return r.is_finite() && *r >= 0.0)
+                .map(Command::Rate)
+                .ok_or_else(|| return format!("invalid rate: {rate}")),
+            ["wireframe"] => Ok(Command::Wireframe(None)),
+            ["wireframe", "on"] => Ok(Command::Wireframe(Some(true))),
+            ["wireframe", "off"] => Ok(Command::Wireframe(Some(false))),
+            _ => Err(format!("unknown command: {s}")),
+        };
+    }
+}
+
+/// Reads commands from standard input on a background thread.
+#[derive(Debug)]
+pub(crate) struct Console {
+    lines: Receiver<String>,
+}
+
+impl Console {
+    pub fn spawn() -> Self {
+        let (sender, lines) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("console".to_owned())
+            .spawn(move || {
+                for line in std::io::stdin().lock().lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to start the console thread");
+        return Self { lines };
+    }
+
+    /// Get the commands that were typed since the last call, skipping empty lines.
+    pub fn commands(&self) -> impl Iterator<Item = Result<Command, String>> + '_ {
+        return self
+            .lines
+            .try_iter()
+            .filter(|l| return !l.trim().is_empty())
+            .map(|l| return l.parse());
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        assert_eq!(Ok(Command::Time), "  time ".parse());
+        assert_eq!(Ok(Command::Freeze(true)), "time freeze".parse());
+        assert_eq!(Ok(Command::Freeze(false)), "time unfreeze".parse());
+        assert_eq!(Ok(Command::SetTime(0.5)), "time set noon".parse());
+        assert_eq!(Ok(Command::SetTime(0.1)), "time set 0.1".parse());
+        assert_eq!(Ok(Command::Rate(20.0)), "time rate 20".parse());
+        assert_eq!(Ok(Command::Wireframe(None)), "wireframe".parse());
+        assert_eq!(Ok(Command::Wireframe(Some(false))), "wireframe off".parse());
+    }
+
+    #[test]
+    fn invalid() {
+        for command in [
+            "time set",
+            "time set later",
+            "time set inf",
+            "time set NaN",
+            "time rate -1",
+            "time rate inf",
+            "time rate nan",
+            "wireframe maybe",
+            "jump",
+        ] {
+            assert!(command.parse::<Command>().is_err(), "{command}");
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::pipeline::{Blend, PipelineCache, PipelineKey, Shader};
 use ge_util::EngineConfig;
 use std::sync::{Arc, Mutex, MutexGuard};
 
@@ -8,19 +9,19 @@ pub(crate) struct Context(Arc<Mutex<InnerContext>>);
 pub(crate) struct InnerContext {
     pub config: EngineConfig,
     pub uniform_bind_group: wgpu::BindGroup,
-    pub uniform_bind_group_layout: wgpu::BindGroupLayout,
+    pub pipelines: PipelineCache,
 }
 
 impl Context {
     pub(crate) fn new(
         config: EngineConfig,
         uniform_bind_group: wgpu::BindGroup,
-        uniform_bind_group_layout: wgpu::BindGroupLayout,
+        pipelines: PipelineCache,
     ) -> Self {
         return Self(Arc::new(Mutex::new(InnerContext {
             config,
             uniform_bind_group,
-            uniform_bind_group_layout,
+            pipelines,
         })));
     }
 
@@ -29,6 +30,17 @@ impl Context {
     }
 }
 
+impl InnerContext {
+    /// Get the pipeline for a shader and blend mode, in the polygon mode of the config.
+    pub(crate) fn pipeline(&self, shader: Shader, blend: Blend) -> &wgpu::RenderPipeline {
+        return self.pipelines.get(PipelineKey {
+            shader,
+            blend,
+            polygon_mode: self.config.renderer.polygon_mode(),
+        });
+    }
+}
+
 impl Clone for Context {
     fn clone(&self) -> Self {
         return Self(Arc::clone(&self.0));
@@ -1,71 +1,288 @@
 use crate::{
-    block::{Block, BlockVertex},
-    context::Context,
-    renderer::{create_render_pipeline, Draw, Renderer, Vertex},
+    block::{Block, BlockVertex, FaceDirection},
+    context::InnerContext,
+    drawables::mesher::ChunkMesh,
+    pipeline::{Blend, Shader},
+    renderer::{Draw, Renderer},
 };
-use ge_resource::{
-    texture::{Texture, TextureArray},
-    ResourceManager,
+use ge_resource::texture::BlockTextures;
+use ge_util::{
+    coords::{CHUNK_SIZE, SECTION_SIZE},
+    Aabb, ChunkOffset, Frustum,
 };
-use ge_world::{BlockType, Chunk};
-use nalgebra::{Matrix4, Vector3};
-use std::{collections::HashSet, sync::Arc};
+use ge_world::{lod::LodLevel, mesh::Quad, BlockType};
+use nalgebra::{Matrix4, Vector2, Vector3};
+use std::{ops::Range, sync::Arc};
 use wgpu::util::DeviceExt;
 
+/// The mesh of a chunk, with one vertex and index buffer for every section that can be seen.
+///
+/// Every block type samples the same texture array, so the whole chunk is drawn with one bind
+/// group and one draw call per section. Translucent blocks are drawn separately with
+/// `draw_translucent`, after every opaque block in the world. Their quads are kept in back to
+/// front order within each section by `sort_quads`.
 #[derive(Debug)]
 pub(crate) struct DrawChunk {
-    instances: Vec<DrawInstancedBlocks>,
+    bind_group: Arc<wgpu::BindGroup>,
+    sections: Vec<DrawSection>,
 }
 
 impl DrawChunk {
-    pub fn new(
-        cx: Context,
-        chunk: &Chunk,
-        renderer: &Renderer,
-        resources: &mut ResourceManager,
-    ) -> Self {
-        let config = cx.lock().config;
-        let visible = chunk.visible_blocks(&config);
+    pub fn new(mesh: &ChunkMesh, renderer: &Renderer, textures: &BlockTextures) -> Self {
+        let sections = mesh
+            .sections
+            .iter()
+            .map(|(_, quads)| {
+                return DrawSection::new(mesh.offset, mesh.level, quads, renderer, textures);
+            })
+            .collect::<Vec<_>>();
+
+        return Self {
+            bind_group: Arc::clone(&textures.bind_group),
+            sections,
+        };
+    }
 
-        // block types present in the chunk
-        let present_blk_ty = visible
+    /// The number of sections that have a mesh.
+    pub fn section_count(&self) -> usize {
+        return self.sections.len();
+    }
+
+    /// Hide the sections that are outside of the frustum, returning the number that are visible.
+    pub fn cull(&mut self, frustum: &Frustum) -> usize {
+        let mut visible = 0;
+        for section in &mut self.sections {
+            section.visible = frustum.intersects(&section.aabb);
+            visible += usize::from(section.visible);
+        }
+        return visible;
+    }
+
+    /// The visible sections with translucent blocks, with their squared distance to `camera`.
+    pub fn translucent(&self, camera: Vector3<f32>) -> impl Iterator<Item = (usize, f32)> + '_ {
+        return self
+            .sections
             .iter()
-            .map(|blk| return blk.ty())
-            .filter(|&ty| return ty != BlockType::Air)
-            .collect::<HashSet<BlockType>>();
-
-        // create instance buffer for each block type
-        let mut instances: Vec<DrawInstancedBlocks> = Vec::with_capacity(present_blk_ty.len());
-        for ty in present_blk_ty {
-            let textures = resources.load_texture_array(ty, &renderer.device, &renderer.queue);
-            let blocks = visible
-                .iter()
-                .filter(|blk| return blk.ty() == ty)
-                .map(|blk| return **blk)
-                .collect::<Vec<_>>();
-            instances.push(DrawInstancedBlocks::new(
-                cx.clone(),
-                renderer,
-                &blocks,
-                textures,
-            ));
+            .enumerate()
+            .filter(|(_, s)| return s.visible && !s.translucent().is_empty())
+            .map(move |(i, s)| return (i, (s.aabb.center() - camera).norm_squared()));
+    }
+
+    /// Order the translucent quads of every visible section back to front as seen from `camera`.
+    ///
+    /// A section is only sorted again once the camera moves into another section, so the order
+    /// can be slightly off for quads close to the camera.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        reason = "world coordinates fit in an i32"
+    )]
+    pub fn sort_quads(&mut self, camera: Vector3<f32>, queue: &wgpu::Queue) {
+        let key = (
+            ChunkOffset::from(camera),
+            (camera.z / SECTION_SIZE as f32).floor() as i32,
+        );
+        for section in &mut self.sections {
+            if section.visible && section.sorted_for != Some(key) {
+                section.sort(camera, queue);
+                section.sorted_for = Some(key);
+            }
         }
+    }
 
-        return Self { instances };
+    /// Draw the translucent blocks of a section.
+    ///
+    /// The pipeline and the uniforms have to be set already, since every translucent section in
+    /// the world is drawn with the same ones.
+    pub fn draw_translucent<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, index: usize) {
+        let section = &self.sections[index];
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        section.draw(render_pass, section.translucent());
     }
 }
 
 impl Draw for DrawChunk {
-    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, uniforms: &'a wgpu::BindGroup) {
-        self.instances.iter().for_each(|i| {
-            i.draw(render_pass, uniforms);
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, cx: &'a InnerContext) {
+        let mut sections = self
+            .sections
+            .iter()
+            .filter(|s| return s.visible && !s.opaque().is_empty())
+            .peekable();
+        if sections.peek().is_none() {
+            return;
+        }
+        render_pass.set_pipeline(cx.pipeline(Shader::Chunk, Blend::Opaque));
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_bind_group(1, &cx.uniform_bind_group, &[]);
+        for section in sections {
+            section.draw(render_pass, section.opaque());
+        }
+    }
+}
+
+/// The greedy mesh of a section of a chunk.
+#[derive(Debug)]
+struct DrawSection {
+    /// The bounds of the quads in world space.
+    aabb: Aabb,
+    /// Whether the section was inside the frustum when the world was last culled.
+    visible: bool,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    /// The indices of the opaque quads, which come before the translucent ones.
+    num_opaque: u32,
+    num_indices: u32,
+    /// The centers of the translucent quads in world space, in the order of their vertices.
+    centers: Vec<Vector3<f32>>,
+    /// The camera chunk and section the translucent quads were last sorted for.
+    sorted_for: Option<(ChunkOffset, i32)>,
+}
+
+impl DrawSection {
+    #[allow(clippy::cast_precision_loss, reason = "no other way")]
+    fn new(
+        offset: ChunkOffset,
+        level: LodLevel,
+        quads: &[Quad],
+        renderer: &Renderer,
+        textures: &BlockTextures,
+    ) -> Self {
+        let scale = level.scale() as f32;
+        let origin = Vector3::new(
+            (offset.x() * CHUNK_SIZE) as f32,
+            (offset.y() * CHUNK_SIZE) as f32,
+            0.0,
+        );
+
+        let corners = quads.iter().flat_map(|quad| {
+            let position = Vector3::from(quad.position.map(|p| return p as f32));
+            let size = Vector3::from(quad.size.map(|s| return s as f32));
+            return [
+                origin + position * scale,
+                origin + (position + size) * scale,
+            ];
+        });
+        let aabb = Aabb::from_points(corners).unwrap_or(Aabb::new(origin, origin));
+
+        let (translucent, opaque): (Vec<&Quad>, Vec<&Quad>) =
+            quads.iter().partition(|q| return q.ty.is_translucent());
+        let mut vertices = Vec::with_capacity(quads.len() * 4);
+        let mut indices = Vec::with_capacity(quads.len() * 6);
+        for quad in opaque.iter().chain(&translucent) {
+            let base = u32::try_from(vertices.len()).unwrap_or_default();
+            let layers = textures.layers(quad.ty);
+            vertices.extend(Self::vertices(quad, origin, scale, layers));
+            indices.extend([0, 1, 2, 2, 3, 0].map(|i| return base + i));
+        }
+
+        let vertex_buffer = renderer
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Section Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertices),
+                usage: wgpu::BufferUsages::VERTEX,
+            });
+        let index_buffer = renderer
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Section Index Buffer"),
+                contents: bytemuck::cast_slice(&indices),
+                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+            });
+        let centers = translucent
+            .iter()
+            .map(|quad| {
+                let position = Vector3::from(quad.position.map(|p| return p as f32));
+                let size = Vector3::from(quad.size.map(|s| return s as f32));
+                return origin + (position + size / 2.0) * scale;
+            })
+            .collect();
+
+        return Self {
+            aabb,
+            visible: true,
+            vertex_buffer,
+            index_buffer,
+            num_opaque: u32::try_from(opaque.len() * 6).unwrap_or_default(),
+            num_indices: u32::try_from(indices.len()).unwrap_or_default(),
+            centers,
+            sorted_for: None,
+        };
+    }
+
+    /// Rewrite the indices of the translucent quads in back to front order.
+    fn sort(&self, camera: Vector3<f32>, queue: &wgpu::Queue) {
+        if self.centers.is_empty() {
+            return;
+        }
+        // the opaque quads use 4 vertices for every 6 indices
+        let first_vertex = self.num_opaque / 6 * 4;
+        let indices = back_to_front(&self.centers, first_vertex, camera);
+        let offset = u64::from(self.num_opaque) * std::mem::size_of::<u32>() as u64;
+        queue.write_buffer(&self.index_buffer, offset, bytemuck::cast_slice(&indices));
+    }
+
+    /// The corners of a quad in world space, textured with the layers of its block type.
+    ///
+    /// The texture coordinates are scaled by the size of the quad, so the texture is repeated on
+    /// every cell instead of being stretched over the whole quad.
+    #[allow(clippy::cast_precision_loss, reason = "sizes are small")]
+    fn vertices(
+        quad: &Quad,
+        origin: Vector3<f32>,
+        scale: f32,
+        layers: [u32; 6],
+    ) -> [BlockVertex; 4] {
+        let face = FaceDirection::from(quad.face);
+        let (u, v) = face.tex_axes();
+        let position = Vector3::from(quad.position.map(|p| return p as f32));
+        let size = Vector3::from(quad.size.map(|s| return s as f32));
+        return face.get_vertices().map(|vertex| {
+            let corner = Vector3::from(vertex.position()).component_mul(&size);
+            let [tu, tv] = vertex.tex_coords();
+            return BlockVertex::new(
+                origin + (position + corner) * scale,
+                Vector2::new(tu * size[u], tv * size[v]),
+                layers[vertex.tex_index() as usize],
+            );
         });
     }
+
+    fn opaque(&self) -> Range<u32> {
+        return 0..self.num_opaque;
+    }
+
+    fn translucent(&self) -> Range<u32> {
+        return self.num_opaque..self.num_indices;
+    }
+
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, indices: Range<u32>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(indices, 0, 0..1);
+    }
+}
+
+/// The indices of quads whose vertices start at `first_vertex`, ordered from the furthest
+/// center to the closest one to `camera`.
+fn back_to_front(centers: &[Vector3<f32>], first_vertex: u32, camera: Vector3<f32>) -> Vec<u32> {
+    let mut order = (0..centers.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let a = (centers[a] - camera).norm_squared();
+        let b = (centers[b] - camera).norm_squared();
+        return b.total_cmp(&a);
+    });
+    return order
+        .into_iter()
+        .flat_map(|i| {
+            let base = first_vertex + u32::try_from(i * 4).unwrap_or_default();
+            return [0, 1, 2, 2, 3, 0].map(|i| return base + i);
+        })
+        .collect();
 }
 
 #[derive(Debug)]
 pub(crate) struct DrawInstancedBlocks {
-    render_pipeline: wgpu::RenderPipeline,
     bind_group: Arc<wgpu::BindGroup>,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
@@ -76,17 +293,29 @@ pub(crate) struct DrawInstancedBlocks {
 
 impl DrawInstancedBlocks {
     pub fn new(
-        cx: Context,
         renderer: &Renderer,
-        blocks: &[ge_world::Block],
-        textures: &TextureArray,
+        ty: BlockType,
+        instances: &[Instance],
+        textures: &BlockTextures,
     ) -> Self {
         let block = Block::new();
+        let layers = textures.layers(ty);
+        let vertices = block
+            .get_vertices()
+            .into_iter()
+            .map(|v| {
+                return BlockVertex::new(
+                    v.position().into(),
+                    v.tex_coords().into(),
+                    layers[v.tex_index() as usize],
+                );
+            })
+            .collect::<Vec<_>>();
         let vertex_buffer = renderer
             .device
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: Some("Vertex Buffer"),
-                contents: bytemuck::cast_slice(&block.get_vertices()),
+                contents: bytemuck::cast_slice(&vertices),
                 usage: wgpu::BufferUsages::VERTEX,
             });
         let index_buffer = renderer
@@ -99,44 +328,6 @@ impl DrawInstancedBlocks {
 
         let num_indices = u32::try_from(block.get_indices().len()).unwrap_or_default();
 
-        let layout = renderer
-            .device
-            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Block Pipeline Layout"),
-                bind_group_layouts: &[
-                    &textures.bind_group_layout,
-                    &cx.lock().uniform_bind_group_layout,
-                ],
-                push_constant_ranges: &[],
-            });
-
-        let shader = wgpu::include_wgsl!("../shaders/block.wgsl");
-
-        let render_pipeline = create_render_pipeline(
-            &renderer.device,
-            &layout,
-            renderer.config.format,
-            Some(Texture::DEPTH_FORMAT),
-            &[BlockVertex::desc(), InstanceRaw::desc()],
-            shader,
-            &cx.lock().config,
-        );
-
-        let instances = blocks
-            .iter()
-            .filter(|&b| return b.ty() != ge_world::BlockType::Air)
-            .map(|&b| {
-                #[allow(clippy::cast_precision_loss, reason = "no other way")]
-                return Instance {
-                    position: Vector3::new(
-                        b.world_pos().x() as f32,
-                        b.world_pos().y() as f32,
-                        b.world_pos().z() as f32,
-                    ),
-                };
-            })
-            .collect::<Vec<_>>();
-
         let instance_data: Vec<InstanceRaw> = instances.iter().map(|&i| return i.into()).collect();
         let instance_buffer =
             renderer
@@ -149,7 +340,6 @@ impl DrawInstancedBlocks {
         let num_instances = u32::try_from(instances.len()).unwrap_or_default();
 
         return Self {
-            render_pipeline,
             bind_group: Arc::clone(&textures.bind_group),
             vertex_buffer,
             index_buffer,
@@ -161,10 +351,10 @@ impl DrawInstancedBlocks {
 }
 
 impl Draw for DrawInstancedBlocks {
-    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, uniforms: &'a wgpu::BindGroup) {
-        render_pass.set_pipeline(&self.render_pipeline);
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, cx: &'a InnerContext) {
+        render_pass.set_pipeline(cx.pipeline(Shader::Blocks, Blend::Opaque));
         render_pass.set_bind_group(0, &self.bind_group, &[]);
-        render_pass.set_bind_group(1, uniforms, &[]);
+        render_pass.set_bind_group(1, &cx.uniform_bind_group, &[]);
 
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
@@ -173,19 +363,27 @@ impl Draw for DrawInstancedBlocks {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Instance {
     position: Vector3<f32>,
+    scale: f32,
+}
+
+impl Instance {
+    #[must_use]
+    pub fn new(position: Vector3<f32>, scale: f32) -> Self {
+        return Self { position, scale };
+    }
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct InstanceRaw {
+pub(crate) struct InstanceRaw {
     model: [[f32; 4]; 4],
 }
 
 impl InstanceRaw {
-    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+    pub(crate) fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
         use std::mem;
         return wgpu::VertexBufferLayout {
             array_stride: mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
@@ -219,7 +417,33 @@ impl InstanceRaw {
 impl From<Instance> for InstanceRaw {
     fn from(value: Instance) -> Self {
         return Self {
-            model: Matrix4::new_translation(&value.position).into(),
+            model: (Matrix4::new_translation(&value.position) * Matrix4::new_scaling(value.scale))
+                .into(),
         };
     }
 }
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translucent_quads_are_sorted_back_to_front() {
+        let centers = [
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(5.0, 0.0, 0.0),
+            Vector3::new(3.0, 0.0, 0.0),
+        ];
+        let indices = back_to_front(&centers, 8, Vector3::zeros());
+        assert_eq!(
+            indices,
+            [12, 13, 14, 14, 15, 12, 16, 17, 18, 18, 19, 16, 8, 9, 10, 10, 11, 8]
+        );
+
+        // from the other side the order flips
+        let indices = back_to_front(&centers, 0, Vector3::new(6.0, 0.0, 0.0));
+        assert_eq!(indices[..6], [0, 1, 2, 2, 3, 0]);
+        assert_eq!(indices[12..], [4, 5, 6, 6, 7, 4]);
+    }
+}
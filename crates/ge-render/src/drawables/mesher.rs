@@ -0,0 +1,74 @@
+use ge_util::{ChunkOffset, EngineConfig};
+use ge_world::{
+    lod::LodLevel,
+    mesh,
+    mesh::{Neighbours, Quad},
+    Chunk,
+};
+use std::sync::{mpsc, Arc};
+
+/// The quads of every visible section of a chunk, ready to be uploaded.
+#[derive(Debug)]
+pub(crate) struct ChunkMesh {
+    pub offset: ChunkOffset,
+    pub level: LodLevel,
+    /// The version of the chunk the mesh was built from.
+    pub version: u64,
+    pub sections: Vec<(usize, Vec<Quad>)>,
+}
+
+/// Meshes chunks on rayon's global thread pool, so the frame never waits on meshing.
+///
+/// Meshes are handed back in the order they finish and are collected with `try_recv`.
+#[derive(Debug)]
+pub(crate) struct Mesher {
+    sender: mpsc::Sender<ChunkMesh>,
+    receiver: mpsc::Receiver<ChunkMesh>,
+    /// The number of meshes that were submitted but not received yet.
+    pending: usize,
+}
+
+impl Mesher {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        return Self {
+            sender,
+            receiver,
+            pending: 0,
+        };
+    }
+
+    /// Mesh a copy of a chunk in the background, culling its sides against `neighbours`.
+    pub fn submit(
+        &mut self,
+        chunk: Chunk,
+        neighbours: Neighbours,
+        level: LodLevel,
+        version: u64,
+        config: Arc<EngineConfig>,
+    ) {
+        let sender = self.sender.clone();
+        self.pending += 1;
+        rayon::spawn(move || {
+            let mesh = ChunkMesh {
+                offset: chunk.position,
+                level,
+                version,
+                sections: mesh::sections(&chunk, &neighbours, level, &config),
+            };
+            // the receiver only goes away with the world, at which point the mesh is not needed
+            let _ = sender.send(mesh);
+        });
+    }
+
+    /// Get a finished mesh, if there is one.
+    pub fn try_recv(&mut self) -> Option<ChunkMesh> {
+        let mesh = self.receiver.try_recv().ok()?;
+        self.pending -= 1;
+        return Some(mesh);
+    }
+
+    pub fn pending(&self) -> usize {
+        return self.pending;
+    }
+}
@@ -1,47 +1,188 @@
 use crate::{
-    context::Context,
-    drawables::chunk::DrawChunk,
+    context::{Context, InnerContext},
+    drawables::{
+        chunk::{DrawChunk, DrawInstancedBlocks, Instance},
+        mesher::{ChunkMesh, Mesher},
+    },
+    pipeline::{Blend, Shader},
     renderer::{Draw, Renderer},
+    text::DrawText,
 };
 use ge_resource::ResourceManager;
-use ge_util::ChunkOffset;
-use ge_world::Chunk;
+use ge_util::{coords::CHUNK_SIZE, ChunkOffset, Frustum, WorldPos};
+use ge_world::{lod::LodLevel, mesh::Neighbours, BlockType, Chunk};
 use nalgebra::Vector3;
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// The longest time spent each frame on uploading finished meshes.
+const UPLOAD_BUDGET: Duration = Duration::from_millis(4);
 
+/// The loaded chunks and the buffers they are drawn with.
+///
+/// Chunks are only re-meshed when they change or their level of detail does. Meshing happens on
+/// worker threads, and every chunk keeps drawing its old buffers until its new mesh is uploaded.
 #[derive(Debug)]
 pub(crate) struct DrawWorld {
     context: Context,
     camera_position: ChunkOffset,
+    chunks: HashMap<ChunkOffset, Chunk>,
     instances: HashMap<ChunkOffset, DrawChunk>,
-    pub chunks: Vec<Chunk>,
-    pub dirty: bool,
+    levels: HashMap<ChunkOffset, LodLevel>,
+    /// The version of every chunk, which changes whenever the chunk is sent to the mesher.
+    versions: HashMap<ChunkOffset, u64>,
+    next_version: u64,
+    /// The chunks that changed since they were last sent to the mesher.
+    dirty: HashSet<ChunkOffset>,
+    /// The chunks that were changed with `set_block` since they were added.
+    edited: HashSet<ChunkOffset>,
+    mesher: Mesher,
+    /// Meshes that are waiting to be uploaded, closest to the camera first.
+    ready: VecDeque<ChunkMesh>,
+    /// The number of chunks and sections that were inside the frustum in the last frame.
+    visible: (usize, usize),
+    /// The visible sections with translucent blocks, from the furthest to the closest.
+    translucent: Vec<(ChunkOffset, usize)>,
+    entities: Vec<DrawInstancedBlocks>,
+    entity_instances: Vec<(BlockType, Instance)>,
 }
 
 impl DrawWorld {
     #[must_use]
     pub fn new(cx: Context, camera_position: ChunkOffset) -> Self {
-        let config = cx.lock().config;
-        let cap = (config.world_gen.render_distance).pow(2);
-        let instances = HashMap::with_capacity(cap);
-        let chunks = Vec::with_capacity(cap);
+        let cap = (cx.lock().config.world_gen.render_distance).pow(2);
 
         return Self {
             context: cx,
             camera_position,
-            instances,
-            chunks,
-            dirty: true,
+            chunks: HashMap::with_capacity(cap),
+            instances: HashMap::with_capacity(cap),
+            levels: HashMap::with_capacity(cap),
+            versions: HashMap::with_capacity(cap),
+            next_version: 0,
+            dirty: HashSet::new(),
+            edited: HashSet::new(),
+            mesher: Mesher::new(),
+            ready: VecDeque::new(),
+            visible: (0, 0),
+            translucent: Vec::new(),
+            entities: Vec::new(),
+            entity_instances: Vec::new(),
+        };
+    }
+
+    /// The loaded chunks that were changed with `set_block` since they were added.
+    pub fn edited(&self) -> impl Iterator<Item = &Chunk> + '_ {
+        return self
+            .edited
+            .iter()
+            .filter_map(|offset| return self.chunks.get(offset));
+    }
+
+    #[must_use]
+    pub fn contains(&self, offset: ChunkOffset) -> bool {
+        return self.chunks.contains_key(&offset);
+    }
+
+    /// Add a chunk to the world, replacing the chunk at the same offset.
+    ///
+    /// The loaded neighbours are re-meshed too, since their sides now face this chunk.
+    pub fn insert(&mut self, chunk: Chunk) {
+        let offset = chunk.position;
+        let lod = self.context.lock().config.world_gen.lod;
+        let distance = LodLevel::distance(offset, self.camera_position);
+        let current = self.levels.get(&offset).copied();
+        self.levels
+            .insert(offset, LodLevel::select(distance, current, &lod));
+        self.chunks.insert(offset, chunk);
+        self.dirty.insert(offset);
+        self.edited.remove(&offset);
+        self.touch_neighbours(offset, [(-1, 0), (1, 0), (0, -1), (0, 1)]);
+    }
+
+    /// Mark the loaded neighbours of a chunk in the directions `sides` as dirty.
+    fn touch_neighbours(
+        &mut self,
+        offset: ChunkOffset,
+        sides: impl IntoIterator<Item = (i32, i32)>,
+    ) {
+        for (dx, dy) in sides {
+            let Ok(neighbour) = ChunkOffset::new(offset.x() + dx, offset.y() + dy, 0) else {
+                continue;
+            };
+            if self.chunks.contains_key(&neighbour) {
+                self.dirty.insert(neighbour);
+            }
+        }
+    }
+
+    /// Remove a chunk and its buffers from the world.
+    pub fn remove(&mut self, offset: ChunkOffset) -> Option<Chunk> {
+        self.instances.remove(&offset);
+        self.levels.remove(&offset);
+        self.versions.remove(&offset);
+        self.dirty.remove(&offset);
+        self.edited.remove(&offset);
+        return self.chunks.remove(&offset);
+    }
+
+    /// Remove every chunk that `keep` returns `false` for, returning the removed chunks that were
+    /// edited.
+    pub fn retain(&mut self, mut keep: impl FnMut(ChunkOffset) -> bool) -> Vec<Chunk> {
+        let removed = self
+            .chunks
+            .keys()
+            .copied()
+            .filter(|&offset| return !keep(offset))
+            .collect::<Vec<_>>();
+        return removed
+            .into_iter()
+            .filter_map(|offset| {
+                let edited = self.edited.contains(&offset);
+                return self.remove(offset).filter(|_| return edited);
+            })
+            .collect();
+    }
+
+    /// Change a block, re-meshing its chunk and the neighbours whose border touches it.
+    ///
+    /// Returns `false` if the chunk of the block is not loaded.
+    pub fn set_block(&mut self, pos: WorldPos, block: BlockType) -> bool {
+        let offset = pos.to_chunk_offset();
+        let Some(chunk) = self.chunks.get_mut(&offset) else {
+            return false;
         };
+        let local = pos.to_chunk_pos();
+        chunk.set(local, block);
+        self.dirty.insert(offset);
+        self.edited.insert(offset);
+
+        let edges = [
+            (local.x() == 0, -1, 0),
+            (local.x() == CHUNK_SIZE - 1, 1, 0),
+            (local.y() == 0, 0, -1),
+            (local.y() == CHUNK_SIZE - 1, 0, 1),
+        ];
+        let sides = edges
+            .into_iter()
+            .filter(|&(edge, _, _)| return edge)
+            .map(|(_, dx, dy)| return (dx, dy));
+        self.touch_neighbours(offset, sides);
+        return true;
     }
 
     /// Update the world!
     ///
-    /// # Panics
-    /// If the chunk offset is invalid.
+    /// Changed chunks are sent to the mesher, and finished meshes are uploaded until the frame
+    /// budget runs out. The rest are uploaded in the following frames. Sections outside of
+    /// `frustum` are not drawn.
     pub fn update(
         &mut self,
         new_pos: Vector3<f32>,
+        frustum: &Frustum,
         renderer: &Renderer,
         resources: &mut ResourceManager,
     ) {
@@ -50,34 +191,219 @@ impl DrawWorld {
 
         if last_pos != self.camera_position {
             trace!("camera position changed: {:?}", self.camera_position);
+            self.update_levels();
         }
 
-        if !self.dirty {
-            // TODO: replace self.dirty with a check if the pos is the same chunk as last frame
+        self.queue_meshes();
+        self.upload_meshes(renderer, resources);
+        self.cull(frustum);
+        self.sort_translucent(new_pos, &renderer.queue);
+    }
+
+    /// Hide the sections of every chunk that are outside of the frustum.
+    fn cull(&mut self, frustum: &Frustum) {
+        self.visible = self
+            .instances
+            .values_mut()
+            .map(|chunk| return chunk.cull(frustum))
+            .filter(|&sections| return sections > 0)
+            .fold((0, 0), |(chunks, total), sections| {
+                return (chunks + 1, total + sections);
+            });
+    }
+
+    /// Send every dirty chunk to the mesher, under a new version.
+    fn queue_meshes(&mut self) {
+        if self.dirty.is_empty() {
             return;
         }
 
-        dbg_time! {
-        self.instances = self
-            .chunks
+        let config = Arc::new(self.context.lock().config.clone());
+        for offset in std::mem::take(&mut self.dirty) {
+            let (Some(chunk), Some(&level)) = (self.chunks.get(&offset), self.levels.get(&offset))
+            else {
+                continue;
+            };
+            self.next_version += 1;
+            self.versions.insert(offset, self.next_version);
+            // only full detail culls against the neighbours
+            let neighbours = if level == LodLevel::Full {
+                Neighbours::new(offset, |offset| return self.chunks.get(&offset))
+            } else {
+                Neighbours::default()
+            };
+            self.mesher.submit(
+                chunk.clone(),
+                neighbours,
+                level,
+                self.next_version,
+                Arc::clone(&config),
+            );
+        }
+        trace!("{} chunks waiting to be meshed", self.mesher.pending());
+    }
+
+    /// Returns `true` if a mesh was built from the current version of its chunk.
+    fn is_current(&self, mesh: &ChunkMesh) -> bool {
+        return self.versions.get(&mesh.offset) == Some(&mesh.version);
+    }
+
+    /// Upload finished meshes, closest to the camera first, until the frame budget runs out.
+    fn upload_meshes(&mut self, renderer: &Renderer, resources: &mut ResourceManager) {
+        let mut received = false;
+        while let Some(mesh) = self.mesher.try_recv() {
+            if self.is_current(&mesh) {
+                self.ready.push_back(mesh);
+                received = true;
+            }
+        }
+        if received {
+            let camera = self.camera_position;
+            self.ready
+                .make_contiguous()
+                .sort_unstable_by_key(|mesh| return LodLevel::distance(mesh.offset, camera));
+        }
+        if self.ready.is_empty() {
+            return;
+        }
+
+        let start = Instant::now();
+        let textures = resources.load_block_textures(&renderer.device, &renderer.queue);
+        let mut uploaded = 0;
+        while start.elapsed() < UPLOAD_BUDGET {
+            let Some(mesh) = self.ready.pop_front() else {
+                break;
+            };
+            // the chunk changed or was unloaded while the mesh was waiting
+            if !self.is_current(&mesh) {
+                continue;
+            }
+            self.instances
+                .insert(mesh.offset, DrawChunk::new(&mesh, renderer, textures));
+            uploaded += 1;
+        }
+        trace!(
+            "uploaded {uploaded} chunk meshes, {} waiting",
+            self.ready.len()
+        );
+    }
+
+    /// Order the visible translucent sections back to front, so the closer ones blend over the
+    /// ones behind them. The quads within every section are sorted the same way.
+    fn sort_translucent(&mut self, camera: Vector3<f32>, queue: &wgpu::Queue) {
+        for chunk in self.instances.values_mut() {
+            chunk.sort_quads(camera, queue);
+        }
+        let mut sections = self
+            .instances
             .iter()
-            .map(|chunk| {
-                return (
-                    chunk.position,
-                    DrawChunk::new(self.context.clone(), chunk, renderer, resources),
-                );
+            .flat_map(|(&offset, chunk)| {
+                return chunk
+                    .translucent(camera)
+                    .map(move |(index, distance)| return (offset, index, distance));
             })
-            .collect::<HashMap<_, _>>();
+            .collect::<Vec<_>>();
+        sections.sort_unstable_by(|a, b| return b.2.total_cmp(&a.2));
+        self.translucent = sections
+            .into_iter()
+            .map(|(offset, index, _)| return (offset, index))
+            .collect();
+    }
+
+    /// Set the blocks that entities are drawn as.
+    ///
+    /// The buffers are only rebuilt when an entity changed since the last call.
+    pub fn update_entities(
+        &mut self,
+        instances: Vec<(BlockType, Instance)>,
+        renderer: &Renderer,
+        resources: &mut ResourceManager,
+    ) {
+        if instances == self.entity_instances {
+            return;
         }
 
-        self.dirty = false;
+        let mut types = instances
+            .iter()
+            .map(|&(ty, _)| return ty)
+            .collect::<Vec<_>>();
+        types.sort_unstable_by_key(|ty| return ty.id());
+        types.dedup();
+        let textures = resources.load_block_textures(&renderer.device, &renderer.queue);
+        self.entities = types
+            .into_iter()
+            .map(|ty| {
+                let blocks = instances
+                    .iter()
+                    .filter(|&&(t, _)| return t == ty)
+                    .map(|&(_, i)| return i)
+                    .collect::<Vec<_>>();
+                return DrawInstancedBlocks::new(renderer, ty, &blocks, textures);
+            })
+            .collect();
+        self.entity_instances = instances;
+    }
+
+    /// Pick the level of detail of every chunk based on its distance to the camera.
+    ///
+    /// Chunks whose level changed are marked dirty.
+    fn update_levels(&mut self) {
+        let lod = self.context.lock().config.world_gen.lod;
+        for (&offset, level) in &mut self.levels {
+            let distance = LodLevel::distance(offset, self.camera_position);
+            let selected = LodLevel::select(distance, Some(*level), &lod);
+            if selected != *level {
+                *level = selected;
+                self.dirty.insert(offset);
+            }
+        }
     }
 }
 
 impl Draw for DrawWorld {
-    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, uniforms: &'a wgpu::BindGroup) {
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, cx: &'a InnerContext) {
         self.instances
             .iter()
-            .for_each(|(_, d)| d.draw(render_pass, uniforms));
+            .for_each(|(_, d)| d.draw(render_pass, cx));
+        self.entities.iter().for_each(|d| d.draw(render_pass, cx));
+
+        // translucent blocks go last, so everything behind them has been drawn already
+        if self.translucent.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(cx.pipeline(Shader::Chunk, Blend::Alpha));
+        render_pass.set_bind_group(1, &cx.uniform_bind_group, &[]);
+        for &(offset, index) in &self.translucent {
+            self.instances[&offset].draw_translucent(render_pass, index);
+        }
+    }
+}
+
+impl DrawText for DrawWorld {
+    #[inline]
+    fn name(&self) -> &'static str {
+        return "chunks";
+    }
+
+    #[inline]
+    fn priority(&self) -> u8 {
+        return 90;
+    }
+
+    #[inline]
+    fn text(&self) -> String {
+        let sections = self
+            .instances
+            .values()
+            .map(DrawChunk::section_count)
+            .sum::<usize>();
+        let (visible_chunks, visible_sections) = self.visible;
+        return format!(
+            "Chunks {}/{} Sections {}/{}",
+            visible_chunks,
+            self.instances.len(),
+            visible_sections,
+            sections
+        );
     }
 }
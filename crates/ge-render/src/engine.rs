@@ -1,22 +1,49 @@
 use crate::{
+    args::Args,
     camera::{
         controller::CameraController, projection::Projection, uniform::CameraUniform, Camera,
     },
+    console::{Command, Console},
     context::Context,
-    drawables::world::DrawWorld,
+    drawables::{chunk::Instance, world::DrawWorld},
+    net::NetSystem,
+    pipeline::PipelineCache,
     renderer::Renderer,
     stats::FrameStats,
+    time,
     world::{WorldState, WorldSystem},
 };
-use ge_resource::ResourceManager;
-use ge_util::{deg_to_rad, ChunkOffset, EngineConfig};
-use std::sync::{Arc, Mutex};
+use ge_ecs::{
+    components::{Collider, Follow, Player, Renderable, Transform, Velocity},
+    systems, Entity, Schedule,
+};
+use ge_net::Client;
+use ge_resource::{
+    level::{DeltaStore, Level},
+    ResourceManager,
+};
+use ge_util::{deg_to_rad, ChunkOffset, EngineConfig, WorldClock};
+use ge_world::{
+    delta::{generator_fingerprint, DeltaError},
+    trns::StageError,
+};
+use nalgebra::Vector3;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use wgpu::util::DeviceExt;
 use winit::{
     event::{KeyboardInput, WindowEvent},
     window::Window,
 };
 
+/// The number of times per second that the systems of the entities run.
+const TICK_RATE: u32 = 60;
+
+/// How far the eyes of the player are above the center of its collider.
+const EYE_OFFSET: [f32; 3] = [0.0, 0.0, 0.7];
+
 /// The `Engine` struct is the main entry point for the game engine.
 #[derive(Debug)]
 pub(crate) struct Engine {
@@ -28,6 +55,13 @@ pub(crate) struct Engine {
 
     pub world: WorldState,
     pub world_sys: WorldSystem,
+    /// The connection to a server, which replaces `world_sys` when the world is not local.
+    pub net: Option<NetSystem>,
+
+    pub ecs: ge_ecs::World,
+    pub schedule: Schedule,
+    pub player: Entity,
+    pub camera_entity: Entity,
 
     pub camera: Camera,
     pub projection: Projection,
@@ -37,18 +71,115 @@ pub(crate) struct Engine {
     pub uniform_buffer: wgpu::Buffer,
 
     pub stats: FrameStats,
+    pub clock: WorldClock,
+    pub console: Console,
+
+    pub level: Option<OpenLevel>,
+}
+
+/// A world that was opened with `--world`, which is saved when the engine exits.
+#[derive(Debug)]
+pub(crate) struct OpenLevel {
+    pub name: String,
+    pub level: Level,
+    /// Where the edits to the chunks are saved, or `None` if they cannot be.
+    pub deltas: Option<DeltaStore>,
+}
+
+impl OpenLevel {
+    /// Open or create a named world and apply its settings to `config`.
+    fn open(resources: &ResourceManager, name: &str, config: &mut EngineConfig) -> Option<Self> {
+        let create = || {
+            let mut level = Level::new(&config.world_gen, config.camera.initial_position);
+            level.time = WorldClock::new(&config.time).seconds();
+            return level;
+        };
+        let level = match resources.open_world(name, create) {
+            Ok(level) => level,
+            Err(e) => {
+                error!("failed to open world '{}': {}", name, e);
+                return None;
+            }
+        };
+
+        if config.world_gen.seed != level.seed {
+            warn!(
+                "world '{}' already exists, ignoring the seed {}",
+                name, config.world_gen.seed
+            );
+        }
+        level.apply(&mut config.world_gen);
+        config.camera.initial_position = level.player_or_spawn();
+        info!("opened world '{}'", name);
+
+        return Some(Self {
+            name: name.to_owned(),
+            level,
+            deltas: Self::deltas(resources, name, config),
+        });
+    }
+
+    /// Get the chunk delta store of a world, if its deltas match the current generator.
+    ///
+    /// Deltas that were saved for a different generator are left alone, since applying them to
+    /// the new terrain would scatter the edits in the wrong places.
+    fn deltas(
+        resources: &ResourceManager,
+        name: &str,
+        config: &EngineConfig,
+    ) -> Option<DeltaStore> {
+        let fingerprint = generator_fingerprint(&config.world_gen)
+            .map_err(|e| {
+                error!(
+                    "failed to build the generator of world '{}', edits are disabled: {}",
+                    name, e
+                );
+            })
+            .ok()?;
+        let store = resources.delta_store(name, fingerprint).ok()?;
+        return match store.verify() {
+            Ok(count) => {
+                info!("world '{}' has {} edited chunks", name, count);
+                Some(store)
+            }
+            Err(e @ DeltaError::GeneratorMismatch { .. }) => {
+                error!(
+                    "the generator of world '{}' changed, edits are disabled: {}",
+                    name, e
+                );
+                None
+            }
+            Err(e) => {
+                error!(
+                    "failed to check the chunk deltas of world '{}': {}",
+                    name, e
+                );
+                None
+            }
+        };
+    }
 }
 
 impl Engine {
-    pub fn new(window: Window, mut renderer: Renderer) -> Self {
-        let resources = ResourceManager::default();
-        let config: EngineConfig = resources.load_config("engine.toml").unwrap_or_default();
+    /// Create the engine and start generating the world around the player.
+    ///
+    /// # Errors
+    /// Errors if the world-gen pipeline in the config cannot be built.
+    pub fn new(window: Window, mut renderer: Renderer, args: &Args) -> Result<Self, StageError> {
+        let mut resources = ResourceManager::default();
+        let mut config: EngineConfig = resources.load_config("engine.toml").unwrap_or_default();
+        if let Some(seed) = args.seed {
+            config.world_gen.seed = seed;
+        }
+        let (client, level) = Self::open_world(&resources, args, &mut config);
+        info!("world seed: {}", config.world_gen.seed);
 
-        let camera = Camera::new(
-            config.camera.initial_position,
-            deg_to_rad(config.camera.initial_yaw_pitch[0]),
-            deg_to_rad(config.camera.initial_yaw_pitch[1]),
-        );
+        let (mut ecs, player, camera_entity) = Self::spawn_player(&config);
+        let schedule = Schedule::new(TICK_RATE)
+            .with_system("movement", systems::movement)
+            .with_system("follow", systems::follow);
+        systems::follow(&mut ecs, 0.0);
+        let camera = Camera::from(*ecs.get::<Transform>(camera_entity).unwrap());
         let projection = Projection::new(
             renderer.config.width,
             renderer.config.height,
@@ -63,23 +194,6 @@ impl Engine {
             renderer.config.height,
         );
 
-        let uniform_bind_group_layout =
-            renderer
-                .device
-                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                    entries: &[wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Uniform,
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    }],
-                    label: Some("uniform_bind_group_layout"),
-                });
-
         let mut camera_uniform = CameraUniform::new();
         camera_uniform.update_view_proj(
             camera.position,
@@ -96,26 +210,21 @@ impl Engine {
                     usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
                 });
 
-        let uniform_bind_group = renderer
-            .device
-            .create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &uniform_bind_group_layout,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: uniform_buffer.as_entire_binding(),
-                }],
-                label: Some("uniform_bind_group"),
-            });
-
         let stats = FrameStats::default();
+        let clock = Self::clock(level.as_ref(), &config);
 
-        let context = Context::new(config, uniform_bind_group, uniform_bind_group_layout);
-        let world = Arc::new(Mutex::new(DrawWorld::new(context.clone(), ChunkOffset::default())));
-        let world_sys = WorldSystem::new(context.clone(), Arc::clone(&world));
+        let context = Self::create_context(&renderer, &mut resources, config, &uniform_buffer);
+        let world = Arc::new(Mutex::new(DrawWorld::new(
+            context.clone(),
+            ChunkOffset::default(),
+        )));
+        let deltas = level.as_ref().and_then(|l| return l.deltas.clone());
+        let world_sys = WorldSystem::new(context.clone(), Arc::clone(&world), deltas)?;
+        let net = client.map(|c| return NetSystem::new(c, Arc::clone(&world)));
         renderer.set_world(&world);
 
         trace!("created engine");
-        return Self {
+        return Ok(Self {
             context,
 
             window,
@@ -124,6 +233,13 @@ impl Engine {
 
             world,
             world_sys,
+            net,
+
+            ecs,
+            schedule,
+            player,
+            camera_entity,
+
             camera,
             projection,
             camera_controller,
@@ -132,13 +248,160 @@ impl Engine {
             uniform_buffer,
 
             stats,
+            clock,
+            console: Console::spawn(),
+
+            level,
+        });
+    }
+
+    /// Create the context with the bind group of the uniforms and the cached pipelines.
+    fn create_context(
+        renderer: &Renderer,
+        resources: &mut ResourceManager,
+        config: EngineConfig,
+        uniform_buffer: &wgpu::Buffer,
+    ) -> Context {
+        let uniform_bind_group_layout =
+            renderer
+                .device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                    label: Some("uniform_bind_group_layout"),
+                });
+
+        let uniform_bind_group = renderer
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: &uniform_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform_buffer.as_entire_binding(),
+                }],
+                label: Some("uniform_bind_group"),
+            });
+
+        let textures = resources.load_block_textures(&renderer.device, &renderer.queue);
+        let pipelines = PipelineCache::new(
+            &renderer.device,
+            renderer.config.format,
+            &[&textures.bind_group_layout, &uniform_bind_group_layout],
+        );
+        return Context::new(config, uniform_bind_group, pipelines);
+    }
+
+    /// Join the server or open the world that was given on the command line, if any.
+    fn open_world(
+        resources: &ResourceManager,
+        args: &Args,
+        config: &mut EngineConfig,
+    ) -> (Option<Client>, Option<OpenLevel>) {
+        let client = args
+            .connect
+            .as_deref()
+            .and_then(|addr| return Self::connect(addr, args.name.as_deref(), config));
+        let level = match args.world.as_deref() {
+            Some(name) if client.is_some() => {
+                warn!("ignoring the world '{}' while connected to a server", name);
+                None
+            }
+            Some(name) => OpenLevel::open(resources, name, config),
+            None => None,
         };
+        return (client, level);
+    }
+
+    /// Join a server, taking the seed and spawn point of its world.
+    fn connect(addr: &str, name: Option<&str>, config: &mut EngineConfig) -> Option<Client> {
+        return match Client::connect(addr, name.unwrap_or("player")) {
+            Ok(client) => {
+                let welcome = client.welcome();
+                info!("connected to {} as player {}", addr, welcome.player);
+                config.world_gen.seed = welcome.seed;
+                config.camera.initial_position = welcome.spawn;
+                Some(client)
+            }
+            Err(e) => {
+                error!(
+                    "failed to connect to {}, generating the world locally: {}",
+                    addr, e
+                );
+                None
+            }
+        };
+    }
+
+    /// Create the world clock, continuing from the time of the open world if there is one.
+    fn clock(level: Option<&OpenLevel>, config: &EngineConfig) -> WorldClock {
+        return level.map_or_else(
+            || return WorldClock::new(&config.time),
+            |l| return WorldClock::from_seconds(&config.time, l.level.time),
+        );
+    }
+
+    /// Create the entities world with the player and the camera that follows it.
+    fn spawn_player(config: &EngineConfig) -> (ge_ecs::World, Entity, Entity) {
+        let eyes = Vector3::from(EYE_OFFSET);
+        let transform = Transform::new(
+            Vector3::from(config.camera.initial_position) - eyes,
+            deg_to_rad(config.camera.initial_yaw_pitch[0]),
+            deg_to_rad(config.camera.initial_yaw_pitch[1]),
+        );
+
+        let mut ecs = ge_ecs::World::new();
+        let player = ecs.spawn();
+        ecs.insert(player, Player);
+        ecs.insert(player, transform);
+        ecs.insert(player, Velocity::default());
+        ecs.insert(player, Collider::new([0.3, 0.3, 0.9]));
+
+        let camera = ecs.spawn();
+        ecs.insert(camera, transform);
+        ecs.insert(
+            camera,
+            Follow {
+                target: player,
+                offset: eyes,
+            },
+        );
+        return (ecs, player, camera);
     }
 
     pub fn update(&mut self) {
         self.stats.fps();
+        for command in self.console.commands() {
+            match command {
+                Ok(Command::Wireframe(enabled)) => self.set_wireframe(enabled),
+                Ok(command) => time::run_command(&mut self.clock, command),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        self.clock.advance(self.stats.delta_time);
+        let sky = self.clock.sky();
+        self.camera_uniform
+            .update_sky(&sky, self.clock.time_of_day());
+        self.renderer.clear_color = time::clear_color(&sky);
+
         self.camera_controller
-            .update_camera(&mut self.camera, self.stats.delta_time);
+            .update_player(&mut self.ecs, self.player, self.stats.delta_time);
+        self.schedule.update(
+            &mut self.ecs,
+            Duration::from_secs_f64(self.stats.delta_time),
+        );
+        // follow the player every frame as well, so looking around is not tied to the tick rate
+        systems::follow(&mut self.ecs, 0.0);
+        if let Some(&transform) = self.ecs.get::<Transform>(self.camera_entity) {
+            self.camera = Camera::from(transform);
+        }
         self.camera_uniform.update_view_proj(
             self.camera.position,
             self.camera.calc_matrix(),
@@ -149,14 +412,63 @@ impl Engine {
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
-        self.world.lock().unwrap().update(
+        if let Some(net) = &mut self.net {
+            let player = *self.ecs.get::<Transform>(self.player).unwrap();
+            net.update(&mut self.ecs, player);
+        }
+        let entities = self
+            .ecs
+            .join::<Renderable, Transform>()
+            .map(|(_, r, t)| return (r.block, Instance::new(t.position, r.scale)))
+            .collect();
+        let mut world = self.world.lock().unwrap();
+        let frustum = self.camera_uniform.frustum();
+        world.update(
             self.camera.position,
+            &frustum,
             &self.renderer,
             &mut self.resources,
         );
-        self.world_sys.update(self.camera.position);
+        world.update_entities(entities, &self.renderer, &mut self.resources);
+        self.renderer.debug_text.add_entry(&*world);
+        drop(world);
         self.renderer.debug_text.add_entry(&self.stats);
         self.renderer.debug_text.add_entry(&self.camera);
+        if let Some(net) = &self.net {
+            self.renderer.debug_text.add_entry(net);
+        } else {
+            self.world_sys.update(&self.camera);
+            self.renderer.debug_text.add_entry(&self.world_sys);
+        }
+        self.renderer.debug_text.add_entry(&self.clock);
+    }
+
+    /// Switch wireframe mode on or off, or toggle it if `enabled` is `None`.
+    ///
+    /// The pipelines for both modes are cached, so the world does not have to be rebuilt.
+    fn set_wireframe(&self, enabled: Option<bool>) {
+        let mut cx = self.context.lock();
+        let renderer = &mut cx.config.renderer;
+        renderer.wireframe_mode = enabled.unwrap_or(!renderer.wireframe_mode);
+        info!(
+            "wireframe mode {}",
+            if renderer.wireframe_mode { "on" } else { "off" }
+        );
+    }
+
+    /// Save the player position, world time and chunk edits of the open world, if there is one.
+    pub fn save(&mut self) {
+        let Some(open) = &mut self.level else {
+            return;
+        };
+        self.world_sys.save();
+
+        open.level.time = self.clock.seconds();
+        open.level.player = Some(self.camera.position.into());
+        match self.resources.save_level(&open.name, &open.level) {
+            Ok(()) => info!("saved world '{}'", open.name),
+            Err(e) => error!("failed to save world '{}': {}", open.name, e),
+        }
     }
 
     /// Renders the game.
@@ -0,0 +1,558 @@
+//! Export generated terrain as a mesh for viewing in other tools.
+//!
+//! Meshes are built on the CPU, so exporting does not need a GPU. Positions are converted from
+//! the engine's Z-up coordinates to the Y-up coordinates expected by OBJ and glTF, so `(x, y, z)`
+//! becomes `(x, z, -y)`.
+use crate::block::FaceDirection;
+use ge_resource::{block::BlockMeta, data::DataError, ResourceManager};
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE},
+    ChunkOffset, ChunkPos,
+};
+use ge_world::{BlockType, Chunk, World};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+use thiserror::Error;
+
+/// Block types that have textures.
+const TEXTURED: [BlockType; 6] = [
+    BlockType::Dev,
+    BlockType::Dirt,
+    BlockType::Grass,
+    BlockType::Stone,
+    BlockType::Water,
+    BlockType::Wood,
+];
+
+// glTF enum values
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const NEAREST: u32 = 9728;
+
+/// The triangles of a `Mesh` that share a texture.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MeshGroup {
+    pub indices: Vec<u32>,
+    /// Whether the texture belongs to a transparent block type.
+    pub transparent: bool,
+}
+
+/// A triangle mesh of the visible block faces in a region, grouped by texture.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    /// The triangles for each texture, keyed by texture name.
+    pub groups: BTreeMap<String, MeshGroup>,
+    /// The directory that texture paths are relative to, as written into the exported files.
+    pub texture_dir: String,
+}
+
+/// Builds `Mesh`es from chunks using the face textures in the block metadata.
+#[derive(Debug, Clone)]
+pub struct MeshExporter {
+    faces: HashMap<BlockType, [String; 6]>,
+    texture_dir: String,
+}
+
+fn to_y_up(v: [f32; 3]) -> [f32; 3] {
+    return [v[0], v[2], -v[1]];
+}
+
+impl MeshExporter {
+    /// Create an exporter, loading the metadata of every block type.
+    ///
+    /// # Errors
+    /// Errors if the metadata of a block type cannot be loaded.
+    pub fn new(resources: &ResourceManager) -> Result<Self, ExportError> {
+        let mut faces = HashMap::new();
+        for ty in TEXTURED {
+            faces.insert(
+                ty,
+                BlockMeta::load_from_disk(resources, ty)?
+                    .get_faces()
+                    .clone(),
+            );
+        }
+
+        return Ok(Self {
+            faces,
+            texture_dir: resources.asset_path().to_string_lossy().replace('\\', "/"),
+        });
+    }
+
+    /// Set the directory that texture paths in the exported files are relative to.
+    ///
+    /// Most tools resolve these paths relative to the exported file, so this is usually the path
+    /// from the output directory to the assets directory.
+    #[must_use]
+    pub fn with_texture_dir(mut self, dir: impl Into<String>) -> Self {
+        self.texture_dir = dir.into();
+        return self;
+    }
+
+    /// Build a mesh of every chunk in `world`.
+    #[must_use]
+    pub fn mesh(&self, world: &World) -> Mesh {
+        return self.mesh_chunks(&world.chunks);
+    }
+
+    /// Build a mesh of the given chunks.
+    ///
+    /// Faces between two blocks are culled unless the neighbour is transparent and of a different
+    /// type. Faces at the edge of the region are always kept.
+    #[allow(clippy::missing_panics_doc, reason = "the vertex count fits in a u32")]
+    #[must_use]
+    pub fn mesh_chunks(&self, chunks: &[Chunk]) -> Mesh {
+        let lookup = chunks
+            .iter()
+            .map(|c| return (c.position, c))
+            .collect::<HashMap<_, _>>();
+        let block_at = |x: i32, y: i32, z: i32| {
+            if !(0..CHUNK_HEIGHT).contains(&z) {
+                return None;
+            }
+            let offset =
+                ChunkOffset::new(x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE), 0).ok()?;
+            let pos = ChunkPos::new(x.rem_euclid(CHUNK_SIZE), y.rem_euclid(CHUNK_SIZE), z).ok()?;
+            return Some(lookup.get(&offset)?.get(pos));
+        };
+
+        // sort the chunks so the output does not depend on the order they were generated in
+        let mut sorted = chunks.iter().collect::<Vec<_>>();
+        sorted.sort_by_key(|c| return (c.position.x(), c.position.y()));
+
+        let mut mesh = Mesh {
+            texture_dir: self.texture_dir.clone(),
+            ..Default::default()
+        };
+        for blk in sorted.into_iter().flat_map(Chunk::blocks) {
+            let Some(textures) = self.faces.get(&blk.ty()) else {
+                continue;
+            };
+            let p = blk.world_pos();
+
+            for face in FaceDirection::ALL {
+                let n = face.normal();
+                let visible = match block_at(p.x() + n[0], p.y() + n[1], p.z() + n[2]) {
+                    Some(other) => other.is_transparent() && other != blk.ty(),
+                    None => true,
+                };
+                if !visible {
+                    continue;
+                }
+
+                let base = u32::try_from(mesh.positions.len()).unwrap();
+                let vertices = face.get_vertices();
+                #[allow(clippy::cast_precision_loss, reason = "no other way")]
+                for v in vertices {
+                    let [vx, vy, vz] = v.position();
+                    mesh.positions.push(to_y_up([
+                        p.x() as f32 + vx,
+                        p.y() as f32 + vy,
+                        p.z() as f32 + vz,
+                    ]));
+                    mesh.normals
+                        .push(to_y_up([n[0] as f32, n[1] as f32, n[2] as f32]));
+                    mesh.uvs.push(v.tex_coords());
+                }
+
+                let texture = &textures[vertices[0].tex_index() as usize];
+                let group = mesh.groups.entry(texture.clone()).or_default();
+                group.transparent = blk.ty().is_transparent();
+                // same winding as the faces drawn by the renderer
+                group
+                    .indices
+                    .extend([base, base + 1, base + 2, base + 2, base + 3, base]);
+            }
+        }
+
+        return mesh;
+    }
+}
+
+impl Mesh {
+    fn texture_path(&self, name: &str) -> String {
+        if self.texture_dir.is_empty() {
+            return format!("{name}.png");
+        }
+        return format!("{}/{name}.png", self.texture_dir.trim_end_matches('/'));
+    }
+
+    /// Write the mesh as a Wavefront OBJ file that uses the materials in `mtl_name`.
+    ///
+    /// # Errors
+    /// Errors if writing fails.
+    pub fn write_obj(&self, w: &mut impl Write, mtl_name: &str) -> io::Result<()> {
+        writeln!(w, "mtllib {mtl_name}")?;
+        for [x, y, z] in &self.positions {
+            writeln!(w, "v {x} {y} {z}")?;
+        }
+        for [u, v] in &self.uvs {
+            // OBJ texture coordinates start at the bottom left
+            writeln!(w, "vt {u} {}", 1.0 - v)?;
+        }
+        for [x, y, z] in &self.normals {
+            writeln!(w, "vn {x} {y} {z}")?;
+        }
+        for (name, group) in &self.groups {
+            writeln!(w, "usemtl {name}")?;
+            for tri in group.indices.chunks_exact(3) {
+                // OBJ indices start at 1, and every attribute shares the same index
+                let [a, b, c] = [tri[0] + 1, tri[1] + 1, tri[2] + 1];
+                writeln!(w, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Write the materials used by `write_obj` as a Wavefront MTL file.
+    ///
+    /// # Errors
+    /// Errors if writing fails.
+    pub fn write_mtl(&self, w: &mut impl Write) -> io::Result<()> {
+        for (name, group) in &self.groups {
+            writeln!(w, "newmtl {name}")?;
+            writeln!(w, "Kd 1 1 1")?;
+            writeln!(w, "d {}", if group.transparent { 0.6 } else { 1.0 })?;
+            writeln!(w, "map_Kd {}", self.texture_path(name))?;
+            writeln!(w)?;
+        }
+        return Ok(());
+    }
+
+    /// Save the mesh to an OBJ file at `path`, with its materials next to it in an MTL file.
+    ///
+    /// # Errors
+    /// Errors if a file cannot be written.
+    pub fn save_obj(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mtl_path = path.with_extension("mtl");
+        let mtl_name = mtl_path
+            .file_name()
+            .map(|n| return n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut obj = BufWriter::new(File::create(path)?);
+        self.write_obj(&mut obj, &mtl_name)?;
+        obj.flush()?;
+        let mut mtl = BufWriter::new(File::create(&mtl_path)?);
+        self.write_mtl(&mut mtl)?;
+        return mtl.flush();
+    }
+
+    /// Build the glTF JSON and the binary buffer it refers to.
+    fn gltf(&self) -> (serde_json::Value, Vec<u8>) {
+        let mut bin = Vec::new();
+        let mut views = Vec::new();
+        let mut push_view = |bytes: Vec<u8>, target: u32| {
+            views.push(serde_json::json!({
+                "buffer": 0,
+                "byteOffset": bin.len(),
+                "byteLength": bytes.len(),
+                "target": target,
+            }));
+            bin.extend(bytes);
+            return views.len() - 1;
+        };
+        let floats =
+            |data: &[f32]| return data.iter().flat_map(|f| return f.to_le_bytes()).collect();
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for p in &self.positions {
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+
+        let count = self.positions.len();
+        let mut accessors = vec![
+            serde_json::json!({
+                "bufferView": push_view(floats(self.positions.concat().as_slice()), ARRAY_BUFFER),
+                "componentType": FLOAT,
+                "count": count,
+                "type": "VEC3",
+                "min": min,
+                "max": max,
+            }),
+            serde_json::json!({
+                "bufferView": push_view(floats(self.normals.concat().as_slice()), ARRAY_BUFFER),
+                "componentType": FLOAT,
+                "count": count,
+                "type": "VEC3",
+            }),
+            serde_json::json!({
+                "bufferView": push_view(floats(self.uvs.concat().as_slice()), ARRAY_BUFFER),
+                "componentType": FLOAT,
+                "count": count,
+                "type": "VEC2",
+            }),
+        ];
+
+        let mut primitives = Vec::new();
+        let mut materials = Vec::new();
+        let mut textures = Vec::new();
+        let mut images = Vec::new();
+        for (i, (name, group)) in self.groups.iter().enumerate() {
+            let bytes = group
+                .indices
+                .iter()
+                .flat_map(|i| return i.to_le_bytes())
+                .collect();
+            accessors.push(serde_json::json!({
+                "bufferView": push_view(bytes, ELEMENT_ARRAY_BUFFER),
+                "componentType": UNSIGNED_INT,
+                "count": group.indices.len(),
+                "type": "SCALAR",
+            }));
+            primitives.push(serde_json::json!({
+                "attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 },
+                "indices": accessors.len() - 1,
+                "material": i,
+            }));
+            materials.push(serde_json::json!({
+                "name": name,
+                "pbrMetallicRoughness": {
+                    "baseColorTexture": { "index": i },
+                    "baseColorFactor": [1.0, 1.0, 1.0, if group.transparent { 0.6 } else { 1.0 }],
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 1.0,
+                },
+                "alphaMode": if group.transparent { "BLEND" } else { "OPAQUE" },
+            }));
+            textures.push(serde_json::json!({ "sampler": 0, "source": i }));
+            images.push(serde_json::json!({ "uri": self.texture_path(name) }));
+        }
+
+        let mut json = serde_json::json!({
+            "asset": { "version": "2.0", "generator": "ge-render" },
+            "scene": 0,
+            "scenes": [{ "nodes": [] }],
+            "buffers": [{ "byteLength": bin.len() }],
+            "bufferViews": views,
+            "accessors": accessors,
+            "samplers": [{ "magFilter": NEAREST, "minFilter": NEAREST }],
+            "images": images,
+            "textures": textures,
+            "materials": materials,
+        });
+        if !primitives.is_empty() {
+            json["meshes"] = serde_json::json!([{ "primitives": primitives }]);
+            json["nodes"] = serde_json::json!([{ "mesh": 0 }]);
+            json["scenes"][0]["nodes"] = serde_json::json!([0]);
+        }
+
+        return (json, bin);
+    }
+
+    /// Write the mesh as a binary glTF (`.glb`) file.
+    ///
+    /// Textures are referenced by path rather than embedded.
+    ///
+    /// # Errors
+    /// Errors if writing fails.
+    #[allow(clippy::missing_panics_doc, reason = "the glTF JSON is always valid")]
+    pub fn write_glb(&self, w: &mut impl Write) -> io::Result<()> {
+        let (json, mut bin) = self.gltf();
+
+        // both chunks must be padded to a multiple of 4 bytes
+        let mut json = serde_json::to_vec(&json).unwrap();
+        json.resize(json.len() + (4 - json.len() % 4) % 4, b' ');
+        bin.resize(bin.len() + (4 - bin.len() % 4) % 4, 0);
+
+        let to_u32 = |n: usize| {
+            return u32::try_from(n)
+                .map_err(|e| return io::Error::new(io::ErrorKind::InvalidData, e));
+        };
+        w.write_all(b"glTF")?;
+        w.write_all(&2u32.to_le_bytes())?;
+        w.write_all(&to_u32(12 + 8 + json.len() + 8 + bin.len())?.to_le_bytes())?;
+        w.write_all(&to_u32(json.len())?.to_le_bytes())?;
+        w.write_all(b"JSON")?;
+        w.write_all(&json)?;
+        w.write_all(&to_u32(bin.len())?.to_le_bytes())?;
+        w.write_all(b"BIN\0")?;
+        w.write_all(&bin)?;
+        return Ok(());
+    }
+
+    /// Save the mesh to a binary glTF file at `path`.
+    ///
+    /// # Errors
+    /// Errors if the file cannot be written.
+    pub fn save_glb(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write_glb(&mut w)?;
+        return w.flush();
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ExportError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("data error: {0}")]
+    Data(#[from] DataError),
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exporter() -> MeshExporter {
+        let faces = [BlockType::Stone, BlockType::Water]
+            .into_iter()
+            .map(|ty| return (ty, [(); 6].map(|_| return format!("{ty:?}").to_lowercase())))
+            .collect();
+        return MeshExporter {
+            faces,
+            texture_dir: "textures/".to_owned(),
+        };
+    }
+
+    /// A world with the given blocks in the chunk at the origin.
+    fn world(blocks: &[([i32; 3], BlockType)]) -> World {
+        let mut chunk = Chunk::new(ChunkOffset::default());
+        for &([x, y, z], ty) in blocks {
+            chunk.set(ChunkPos::new(x, y, z).unwrap(), ty);
+        }
+        return World {
+            chunks: vec![chunk],
+        };
+    }
+
+    #[test]
+    fn culls_hidden_faces() {
+        // two stones next to each other, with water on top of the first one
+        let mesh = exporter().mesh(&world(&[
+            ([1, 1, 1], BlockType::Stone),
+            ([2, 1, 1], BlockType::Stone),
+            ([1, 1, 2], BlockType::Water),
+        ]));
+
+        // the faces between the stones are hidden, but the stone below the water is not
+        let stone = &mesh.groups["stone"];
+        assert_eq!(10 * 6, stone.indices.len());
+        assert!(!stone.transparent);
+        // the water does not draw the face it shares with the stone
+        let water = &mesh.groups["water"];
+        assert_eq!(5 * 6, water.indices.len());
+        assert!(water.transparent);
+
+        assert_eq!(15 * 4, mesh.positions.len());
+        assert_eq!(mesh.positions.len(), mesh.normals.len());
+        assert_eq!(mesh.positions.len(), mesh.uvs.len());
+        assert!(mesh
+            .groups
+            .values()
+            .flat_map(|g| return &g.indices)
+            .all(|&i| return (i as usize) < mesh.positions.len()));
+    }
+
+    #[test]
+    fn y_up() {
+        let mesh = exporter().mesh(&world(&[([1, 2, 3], BlockType::Stone)]));
+        assert_eq!(6 * 4, mesh.positions.len());
+        for [x, y, z] in &mesh.positions {
+            assert!((1.0..=2.0).contains(x));
+            assert!((3.0..=4.0).contains(y), "z becomes y");
+            assert!((-3.0..=-2.0).contains(z), "y becomes -z");
+        }
+        // the top face points up along y, and the face towards +y points towards -z
+        let face = |normal: [f32; 3]| {
+            return mesh
+                .positions
+                .iter()
+                .zip(&mesh.normals)
+                .filter(|(_, &n)| return n == normal)
+                .map(|(p, _)| return *p)
+                .collect::<Vec<_>>();
+        };
+        assert_eq!(4, face([0.0, 1.0, 0.0]).len());
+        assert!(face([0.0, 1.0, 0.0]).iter().all(|p| return p[1] == 4.0));
+        assert_eq!(4, face([0.0, 0.0, -1.0]).len());
+        assert!(face([0.0, 0.0, -1.0]).iter().all(|p| return p[2] == -3.0));
+    }
+
+    #[test]
+    fn obj_and_mtl() {
+        let mesh = exporter().mesh(&world(&[
+            ([1, 1, 1], BlockType::Stone),
+            ([1, 1, 2], BlockType::Water),
+        ]));
+
+        let mut obj = Vec::new();
+        mesh.write_obj(&mut obj, "world.mtl").unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        let lines = obj.lines().collect::<Vec<_>>();
+        assert_eq!("mtllib world.mtl", lines[0]);
+        let count = |prefix: &str| {
+            return lines
+                .iter()
+                .filter(|l| return l.starts_with(prefix))
+                .count();
+        };
+        assert_eq!(mesh.positions.len(), count("v "));
+        assert_eq!(mesh.positions.len(), count("vt "));
+        assert_eq!(mesh.positions.len(), count("vn "));
+        assert_eq!((6 + 5) * 2, count("f "));
+        // materials follow the sorted group names, and indices start at 1
+        let usemtl = lines
+            .iter()
+            .filter(|l| return l.starts_with("usemtl"))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![&"usemtl stone", &"usemtl water"], usemtl);
+        assert!(lines.contains(&"f 1/1/1 2/2/2 3/3/3"));
+
+        let mut mtl = Vec::new();
+        mesh.write_mtl(&mut mtl).unwrap();
+        let mtl = String::from_utf8(mtl).unwrap();
+        assert_eq!(
+            "newmtl stone\nKd 1 1 1\nd 1\nmap_Kd textures/stone.png\n\n\
+             newmtl water\nKd 1 1 1\nd 0.6\nmap_Kd textures/water.png\n\n",
+            mtl
+        );
+    }
+
+    #[test]
+    fn glb() {
+        let mesh = exporter().mesh(&world(&[([1, 1, 1], BlockType::Stone)]));
+        let mut glb = Vec::new();
+        mesh.write_glb(&mut glb).unwrap();
+
+        let u32_at =
+            |i: usize| return u32::from_le_bytes(glb[i..i + 4].try_into().unwrap()) as usize;
+        assert_eq!(b"glTF", &glb[0..4]);
+        assert_eq!(2, u32_at(4));
+        assert_eq!(glb.len(), u32_at(8));
+
+        let json_len = u32_at(12);
+        assert_eq!(b"JSON", &glb[16..20]);
+        assert_eq!(0, json_len % 4);
+        let json: serde_json::Value = serde_json::from_slice(&glb[20..20 + json_len]).unwrap();
+
+        let bin = 20 + json_len;
+        let bin_len = u32_at(bin);
+        assert_eq!(b"BIN\0", &glb[bin + 4..bin + 8]);
+        assert_eq!(0, bin_len % 4);
+        assert_eq!(glb.len(), bin + 8 + bin_len);
+
+        // 24 vertices with a position, normal and uv, and 36 indices
+        let expected = 24 * (3 + 3 + 2) * 4 + 36 * 4;
+        assert_eq!(expected, json["buffers"][0]["byteLength"]);
+        assert!(expected <= bin_len);
+        assert_eq!(4, json["accessors"].as_array().unwrap().len());
+        assert_eq!(36, json["accessors"][3]["count"]);
+        assert_eq!("OPAQUE", json["materials"][0]["alphaMode"]);
+    }
+}
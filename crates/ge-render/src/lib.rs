@@ -20,14 +20,20 @@ extern crate ge_macros;
 #[macro_use]
 extern crate tracing;
 
-pub(crate) mod block;
+pub(crate) mod args;
+pub mod block;
 pub(crate) mod camera;
+pub(crate) mod console;
 pub(crate) mod context;
 pub(crate) mod drawables;
 pub(crate) mod engine;
+pub mod export;
+pub(crate) mod net;
+pub(crate) mod pipeline;
 pub(crate) mod renderer;
 pub(crate) mod stats;
 pub(crate) mod text;
+pub(crate) mod time;
 pub(crate) mod world;
 
 use winit::{
@@ -42,6 +48,7 @@ use winit::{
 /// Possible causes of panic include denied permission, incompatible system, and lack of memory.
 pub async fn run() {
     setup_logging();
+    let args = args::Args::parse(std::env::args().skip(1));
 
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
@@ -49,7 +56,13 @@ pub async fn run() {
     trace!("created window");
 
     let renderer = renderer::Renderer::new(&window, window.inner_size()).await;
-    let mut engine = engine::Engine::new(window, renderer);
+    let mut engine = match engine::Engine::new(window, renderer, &args) {
+        Ok(engine) => engine,
+        Err(e) => {
+            error!("could not create the world: {}", e);
+            return;
+        }
+    };
 
     let mut try_grab_cursor = false;
     event_loop.run(move |event, _, control_flow| match event {
@@ -91,7 +104,10 @@ pub async fn run() {
                         ..
                     },
                 ..
-            } => *control_flow = ControlFlow::Exit,
+            } => {
+                engine.save();
+                *control_flow = ControlFlow::Exit;
+            }
             WindowEvent::Resized(physical_size) => {
                 engine.resize(*physical_size);
             }
@@ -0,0 +1,122 @@
+use crate::{drawables::world::DrawWorld, text::DrawText, world::WorldState};
+use ge_ecs::{
+    components::{Renderable, Transform},
+    Entity,
+};
+use ge_net::{Client, ClientMessage, ServerMessage};
+use ge_world::BlockType;
+use std::collections::HashMap;
+
+/// How far the player has to move or turn before the server is told about it.
+const MOVE_EPSILON: f32 = 0.01;
+
+/// Keeps the world in sync with a server, instead of generating it locally.
+///
+/// The server is authoritative, so chunks and block changes are only ever applied when it sends
+/// them, and other players are drawn as entities.
+#[derive(Debug)]
+pub(crate) struct NetSystem {
+    client: Client,
+    state: WorldState,
+    /// The entities of the other players, by their id on the server.
+    players: HashMap<u32, Entity>,
+    last_sent: Option<Transform>,
+}
+
+impl NetSystem {
+    pub fn new(client: Client, state: WorldState) -> Self {
+        return Self {
+            client,
+            state,
+            players: HashMap::new(),
+            last_sent: None,
+        };
+    }
+
+    /// Apply every message from the server and send the position of the player.
+    pub fn update(&mut self, ecs: &mut ge_ecs::World, player: Transform) {
+        let mut state = self.state.lock().unwrap();
+        for message in self.client.poll() {
+            match message {
+                ServerMessage::PlayerJoined { player, name } => {
+                    info!("player {} '{}' joined", player, name);
+                    let entity = ecs.spawn();
+                    ecs.insert(entity, Renderable::new(BlockType::Wood, 0.8));
+                    self.players.insert(player, entity);
+                }
+                ServerMessage::PlayerMoved {
+                    player,
+                    position,
+                    yaw,
+                    pitch,
+                } => {
+                    if let Some(&entity) = self.players.get(&player) {
+                        ecs.insert(entity, Transform::new(position, yaw, pitch));
+                    }
+                }
+                ServerMessage::PlayerLeft { player } => {
+                    info!("player {} left", player);
+                    if let Some(entity) = self.players.remove(&player) {
+                        ecs.despawn(entity);
+                    }
+                }
+                message => Self::apply(&mut state, message),
+            }
+        }
+        drop(state);
+
+        let moved = self.last_sent.is_none_or(|last| {
+            return (last.position - player.position).norm() > MOVE_EPSILON
+                || (last.yaw - player.yaw).abs() > MOVE_EPSILON
+                || (last.pitch - player.pitch).abs() > MOVE_EPSILON;
+        });
+        if moved {
+            let message = ClientMessage::Move {
+                position: player.position.into(),
+                yaw: player.yaw,
+                pitch: player.pitch,
+            };
+            if let Err(e) = self.client.send(&message) {
+                error!("failed to send the player position: {}", e);
+            }
+            self.last_sent = Some(player);
+        }
+    }
+
+    /// Apply a chunk or block change from the server to the world.
+    fn apply(world: &mut DrawWorld, message: ServerMessage) {
+        match message {
+            ServerMessage::Chunk(chunk) => world.insert(chunk),
+            ServerMessage::Unload(offset) => {
+                world.remove(offset);
+            }
+            ServerMessage::BlockChanged { pos, block } => {
+                world.set_block(pos, block);
+            }
+            message => warn!("unexpected message from the server: {:?}", message),
+        }
+    }
+}
+
+impl DrawText for NetSystem {
+    #[inline]
+    fn name(&self) -> &'static str {
+        return "net";
+    }
+
+    #[inline]
+    fn priority(&self) -> u8 {
+        return 100;
+    }
+
+    #[inline]
+    fn text(&self) -> String {
+        let welcome = self.client.welcome();
+        return format!(
+            "Seed {} Player {} Players {}",
+            welcome.seed,
+            welcome.player,
+            self.players.len() + 1
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{block::BlockVertex, drawables::chunk::InstanceRaw, renderer::Vertex};
+use ge_resource::texture::Texture;
+use std::collections::HashMap;
+
+/// A shader together with the layout of the vertices it is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Shader {
+    /// Unit cubes that are placed by an instance buffer, used for entities.
+    Blocks,
+    /// Chunk meshes whose vertices are already in world space.
+    Chunk,
+}
+
+impl Shader {
+    pub const ALL: [Shader; 2] = [Shader::Blocks, Shader::Chunk];
+
+    fn source(self) -> wgpu::ShaderModuleDescriptor<'static> {
+        return match self {
+            Shader::Blocks => wgpu::include_wgsl!("shaders/block.wgsl"),
+            Shader::Chunk => wgpu::include_wgsl!("shaders/chunk.wgsl"),
+        };
+    }
+
+    fn vertex_layouts(self) -> Vec<wgpu::VertexBufferLayout<'static>> {
+        return match self {
+            Shader::Blocks => vec![BlockVertex::desc(), InstanceRaw::desc()],
+            Shader::Chunk => vec![BlockVertex::desc()],
+        };
+    }
+}
+
+/// How the colour of a fragment is combined with the colour behind it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Blend {
+    /// The fragment replaces what is behind it.
+    Opaque,
+    /// The fragment is mixed with what is behind it by its alpha.
+    Alpha,
+}
+
+impl Blend {
+    pub const ALL: [Blend; 2] = [Blend::Opaque, Blend::Alpha];
+
+    /// The fragment shader, where blended fragments are made see-through.
+    fn fragment_entry(self) -> &'static str {
+        return match self {
+            Blend::Opaque => "fs_main",
+            Blend::Alpha => "fs_translucent",
+        };
+    }
+
+    fn state(self) -> wgpu::BlendState {
+        return match self {
+            Blend::Opaque => wgpu::BlendState::REPLACE,
+            Blend::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+        };
+    }
+}
+
+/// Everything that a cached pipeline differs in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct PipelineKey {
+    pub shader: Shader,
+    pub blend: Blend,
+    pub polygon_mode: wgpu::PolygonMode,
+}
+
+/// Every render pipeline the world is drawn with, created once when the engine starts.
+///
+/// There is a pipeline for both polygon modes, so wireframe mode can be switched at any time.
+#[derive(Debug)]
+pub(crate) struct PipelineCache {
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+}
+
+impl PipelineCache {
+    /// Create the pipelines for every shader, blend mode and polygon mode.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+    ) -> Self {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Block Pipeline Layout"),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        let mut pipelines = HashMap::new();
+        for shader in Shader::ALL {
+            let module = device.create_shader_module(shader.source());
+            for blend in Blend::ALL {
+                for polygon_mode in [wgpu::PolygonMode::Fill, wgpu::PolygonMode::Line] {
+                    let key = PipelineKey {
+                        shader,
+                        blend,
+                        polygon_mode,
+                    };
+                    let pipeline = Self::create(device, &layout, &module, format, key);
+                    pipelines.insert(key, pipeline);
+                }
+            }
+        }
+        trace!("created {} render pipelines", pipelines.len());
+
+        return Self { pipelines };
+    }
+
+    /// Get the pipeline for a key, which always exists since they are all created up front.
+    pub fn get(&self, key: PipelineKey) -> &wgpu::RenderPipeline {
+        return &self.pipelines[&key];
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        module: &wgpu::ShaderModule,
+        format: wgpu::TextureFormat,
+        key: PipelineKey,
+    ) -> wgpu::RenderPipeline {
+        return device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Render Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module,
+                entry_point: "vs_main",
+                buffers: &key.shader.vertex_layouts(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module,
+                entry_point: key.blend.fragment_entry(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(key.blend.state()),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: key.polygon_mode,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                // blended fragments must not hide what is drawn behind them afterwards
+                depth_write_enabled: key.blend == Blend::Opaque,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+    }
+}
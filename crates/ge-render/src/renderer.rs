@@ -1,12 +1,14 @@
-use crate::{context::Context, world::WorldState};
+use crate::{
+    context::{Context, InnerContext},
+    world::WorldState,
+};
 use ge_resource::texture::Texture;
-use ge_util::EngineConfig;
 use std::sync::Arc;
 use winit::window::Window;
 
 /// The `Draw` trait is implemented by types that can be drawn by the `Renderer`.
 pub trait Draw {
-    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, uniforms: &'a wgpu::BindGroup);
+    fn draw<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>, cx: &'a InnerContext);
 }
 
 /// The `Vertex` trait is implemented by types that can be used as vertices in a mesh.
@@ -24,6 +26,8 @@ pub(crate) struct Renderer {
     pub size: winit::dpi::PhysicalSize<u32>,
     pub depth_texture: Texture,
     world: Option<WorldState>,
+    /// The colour the screen is cleared to, which is the colour of the sky.
+    pub clear_color: wgpu::Color,
 
     pub staging_belt: wgpu::util::StagingBelt,
     pub debug_text: crate::text::TextRenderer,
@@ -33,9 +37,7 @@ impl Renderer {
     #[allow(clippy::implicit_return)]
     fn features() -> wgpu::Features {
         use wgpu::Features;
-        Features::TEXTURE_BINDING_ARRAY
-            | Features::SAMPLED_TEXTURE_AND_STORAGE_BUFFER_ARRAY_NON_UNIFORM_INDEXING
-            | Features::POLYGON_MODE_LINE
+        Features::POLYGON_MODE_LINE
     }
 
     pub async fn new(window: &Window, size: winit::dpi::PhysicalSize<u32>) -> Self {
@@ -105,6 +107,12 @@ impl Renderer {
             size,
             depth_texture,
             world: None,
+            clear_color: wgpu::Color {
+                r: 0.1,
+                g: 0.2,
+                b: 0.3,
+                a: 1.0,
+            },
 
             staging_belt,
             debug_text,
@@ -149,12 +157,7 @@ impl Renderer {
                     view: &view,
                     resolve_target: None,
                     ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color {
-                            r: 0.1,
-                            g: 0.2,
-                            b: 0.3,
-                            a: 1.0,
-                        }),
+                        load: wgpu::LoadOp::Clear(self.clear_color),
                         store: true,
                     },
                 })],
@@ -169,7 +172,7 @@ impl Renderer {
             });
 
             // render the world
-            world.draw(&mut render_pass, &cx.uniform_bind_group);
+            world.draw(&mut render_pass, &cx);
         }
 
         // render text
@@ -193,61 +196,3 @@ impl Renderer {
         self.world = Some(Arc::clone(world));
     }
 }
-
-pub(crate) fn create_render_pipeline(
-    device: &wgpu::Device,
-    layout: &wgpu::PipelineLayout,
-    format: wgpu::TextureFormat,
-    depth_format: Option<wgpu::TextureFormat>,
-    vertex_layouts: &[wgpu::VertexBufferLayout],
-    shader: wgpu::ShaderModuleDescriptor,
-    config: &EngineConfig,
-) -> wgpu::RenderPipeline {
-    let shader = device.create_shader_module(shader);
-
-    return device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("Render Pipeline"),
-        layout: Some(layout),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: vertex_layouts,
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main",
-            targets: &[Some(wgpu::ColorTargetState {
-                format,
-                blend: Some(wgpu::BlendState {
-                    alpha: wgpu::BlendComponent::REPLACE,
-                    color: wgpu::BlendComponent::REPLACE,
-                }),
-                write_mask: wgpu::ColorWrites::ALL,
-            })],
-        }),
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList,
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw,
-            cull_mode: Some(wgpu::Face::Back),
-            polygon_mode: config.renderer.polygon_mode(),
-            unclipped_depth: false,
-            conservative: false,
-        },
-        depth_stencil: depth_format.map(|format| {
-            return wgpu::DepthStencilState {
-                format,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            };
-        }),
-        multisample: wgpu::MultisampleState {
-            count: 1,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        multiview: None,
-    });
-}
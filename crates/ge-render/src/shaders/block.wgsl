@@ -1,6 +1,11 @@
 struct CameraUniform {
     view_pos: vec4<f32>,
     view_proj: mat4x4<f32>,
+    sun_direction: vec4<f32>,
+    moon_direction: vec4<f32>,
+    sky_color: vec4<f32>,
+    // x: ambient light, y: sky light intensity, z: time of day
+    light: vec4<f32>,
 };
 @group(1) @binding(0)
 var<uniform> camera: CameraUniform;
@@ -14,7 +19,7 @@ struct VertexInput {
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
-    @location(1) tex_index: u32,
+    @location(1) @interpolate(flat) tex_index: u32,
 }
 
 struct InstanceInput {
@@ -42,12 +47,27 @@ fn vs_main(
     return out;
 }
 
+// how much of the colour of translucent blocks covers what is behind them
+const TRANSLUCENT_ALPHA: f32 = 0.6;
+
 @group(0) @binding(0)
-var texture_array: binding_array<texture_2d<f32>>;
-@group(0)@binding(1)
-var sampler_array: binding_array<sampler>;
+var block_textures: texture_2d_array<f32>;
+@group(0) @binding(1)
+var block_sampler: sampler;
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let color = textureSample(block_textures, block_sampler, in.tex_coords, in.tex_index);
+    let light = mix(camera.light.x, 1.0, camera.light.y);
+    return vec4<f32>(color.rgb * light, color.a);
+}
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(texture_array[in.tex_index], sampler_array[in.tex_index], in.tex_coords);
+    return shade(in);
+}
+
+@fragment
+fn fs_translucent(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    return vec4<f32>(color.rgb, color.a * TRANSLUCENT_ALPHA);
 }
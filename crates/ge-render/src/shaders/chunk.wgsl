@@ -0,0 +1,57 @@
+struct CameraUniform {
+    view_pos: vec4<f32>,
+    view_proj: mat4x4<f32>,
+    sun_direction: vec4<f32>,
+    moon_direction: vec4<f32>,
+    sky_color: vec4<f32>,
+    // x: ambient light, y: sky light intensity, z: time of day
+    light: vec4<f32>,
+};
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) tex_index: u32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) @interpolate(flat) tex_index: u32,
+}
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.tex_index = model.tex_index;
+    out.clip_position = camera.view_proj * vec4<f32>(model.position, 1.0);
+    return out;
+}
+
+// how much of the colour of translucent blocks covers what is behind them
+const TRANSLUCENT_ALPHA: f32 = 0.6;
+
+@group(0) @binding(0)
+var block_textures: texture_2d_array<f32>;
+@group(0) @binding(1)
+var block_sampler: sampler;
+
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let color = textureSample(block_textures, block_sampler, in.tex_coords, in.tex_index);
+    let light = mix(camera.light.x, 1.0, camera.light.y);
+    return vec4<f32>(color.rgb * light, color.a);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return shade(in);
+}
+
+@fragment
+fn fs_translucent(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    return vec4<f32>(color.rgb, color.a * TRANSLUCENT_ALPHA);
+}
@@ -0,0 +1,60 @@
+use crate::{console::Command, text::DrawText};
+use ge_util::{clock::Sky, WorldClock};
+
+/// Run a `time` command on the clock.
+pub(crate) fn run_command(clock: &mut WorldClock, command: Command) {
+    match command {
+        Command::Time => {}
+        Command::SetTime(time) => clock.set_time_of_day(time),
+        Command::Freeze(frozen) => clock.set_frozen(frozen),
+        Command::Rate(rate) => clock.set_rate(rate),
+        Command::Wireframe(_) => return,
+    }
+    info!(
+        "day {} {} (rate {}{})",
+        clock.day(),
+        clock_time(clock),
+        clock.rate(),
+        if clock.is_frozen() { ", frozen" } else { "" }
+    );
+}
+
+/// Format the time of day as hours and minutes.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "the time of day is between 0 and 1"
+)]
+fn clock_time(clock: &WorldClock) -> String {
+    let minutes = (clock.time_of_day() * 24.0 * 60.0) as u32;
+    return format!("{:02}:{:02}", minutes / 60, minutes % 60);
+}
+
+/// The colour to clear the screen with.
+pub(crate) fn clear_color(sky: &Sky) -> wgpu::Color {
+    let [r, g, b] = sky.color;
+    return wgpu::Color {
+        r: r.into(),
+        g: g.into(),
+        b: b.into(),
+        a: 1.0,
+    };
+}
+
+impl DrawText for WorldClock {
+    #[inline]
+    fn name(&self) -> &'static str {
+        return "time";
+    }
+
+    #[inline]
+    fn priority(&self) -> u8 {
+        return 150;
+    }
+
+    #[inline]
+    fn text(&self) -> String {
+        let frozen = if self.is_frozen() { " (frozen)" } else { "" };
+        return format!("Day {} {}{}", self.day(), clock_time(self), frozen);
+    }
+}
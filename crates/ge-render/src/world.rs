@@ -1,75 +1,262 @@
-use crate::{context::Context, drawables::world::DrawWorld};
-use ge_util::ChunkOffset;
+use crate::{camera::Camera, context::Context, drawables::world::DrawWorld, text::DrawText};
+use ge_resource::level::DeltaStore;
+use ge_util::{coords::CHUNK_SIZE, ChunkOffset, Seed};
 use ge_world::{
-    gen::{AsyncWorldGenerator, WorldGenerator},
-    noise::Noise,
-    trns::{SeaLevel, SimpleSurfacePainter, Transformation},
+    delta::ChunkDelta,
+    gen::AsyncWorldGenerator,
+    jobs::{ChunkJobs, Priority},
+    trns::StageError,
+    Chunk,
 };
-use nalgebra::Vector3;
-use std::sync::{Arc, Mutex};
+use nalgebra::Vector2;
+use rayon::{prelude::*, ThreadPool};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// The longest time spent each frame on adding finished chunks to the world.
+const FRAME_BUDGET: Duration = Duration::from_millis(4);
+
+/// Chunks within this angle of the view direction are treated as being in view.
+///
+/// This is wider than the field of view, so chunks at the edge of the screen are not missed.
+const VIEW_ANGLE: f32 = std::f32::consts::FRAC_PI_3;
 
 #[derive(Debug)]
 pub(crate) struct WorldSystem {
-    pool: rayon::ThreadPool,
+    jobs: ChunkJobs,
+    pool: Arc<ThreadPool>,
+    world_gen: Arc<AsyncWorldGenerator>,
+    deltas: Option<DeltaStore>,
+    /// Edited chunks that were unloaded but are not saved yet, with the number of their save.
+    unsaved: Unsaved,
+    next_save: u64,
     state: WorldState,
-    world_gen: AsyncWorldGenerator,
-    last_pos: ChunkOffset,
+    render_distance: i32,
+    last_pos: Option<ChunkOffset>,
+    seed: Seed,
 }
 
 pub(crate) type WorldState = Arc<Mutex<DrawWorld>>;
 
+type Unsaved = Arc<Mutex<HashMap<ChunkOffset, (u64, Chunk)>>>;
+
 impl WorldSystem {
-    pub fn new(cx: Context, state: WorldState) -> Self {
+    /// Create the world system.
+    ///
+    /// If `deltas` is given, the saved edits of every chunk are applied after it is generated.
+    /// Chunks whose edits are still being saved are loaded as they were unloaded instead.
+    ///
+    /// # Errors
+    /// Errors if the world-gen pipeline cannot be built.
+    pub fn new(
+        cx: Context,
+        state: WorldState,
+        deltas: Option<DeltaStore>,
+    ) -> Result<Self, StageError> {
         let num_cpus = num_cpus::get();
-        let pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(num_cpus)
-            .build()
-            .unwrap();
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(num_cpus)
+                .build()
+                .unwrap(),
+        );
 
         let cx = cx.lock();
-        let count = {
-            #[allow(
-                clippy::cast_possible_wrap,
-                clippy::cast_possible_truncation,
-                reason = "value should not be large enought to wrap or truncate"
-            )]
-            let rd = cx.config.world_gen.render_distance as i32;
-            (rd, rd)
-        };
-        let noise = Noise::from(&cx.config);
-        let trns: Vec<Transformation> = vec![
-            SeaLevel::new(&cx.config).into(),
-            SimpleSurfacePainter.into(),
-        ];
-        let world_gen = AsyncWorldGenerator::new(noise, count, trns, &cx.config);
-        let last_pos = ChunkOffset::default();
-        return Self {
+        #[allow(
+            clippy::cast_possible_wrap,
+            clippy::cast_possible_truncation,
+            reason = "value should not be large enought to wrap or truncate"
+        )]
+        let render_distance = cx.config.world_gen.render_distance as i32;
+        let count = (render_distance, render_distance);
+        let world_gen = Arc::new(AsyncWorldGenerator::from_config(count, &cx.config)?);
+        let unsaved = Unsaved::default();
+        let (gen, store, pending) = (Arc::clone(&world_gen), deltas.clone(), Arc::clone(&unsaved));
+        let jobs = ChunkJobs::new(Arc::clone(&pool), move |offset| {
+            if let Some((_, chunk)) = pending.lock().unwrap().get(&offset) {
+                return chunk.clone();
+            }
+            let mut chunk = gen.generate_chunk(offset);
+            match store.as_ref().map(|s| return s.load(offset)) {
+                Some(Ok(Some(delta))) => delta.apply(&mut chunk),
+                Some(Err(e)) => error!("failed to load chunk delta {:?}: {}", offset, e),
+                _ => {}
+            }
+            return chunk;
+        });
+        return Ok(Self {
+            jobs,
             pool,
-            state,
             world_gen,
-            last_pos,
+            deltas,
+            unsaved,
+            next_save: 0,
+            state,
+            render_distance,
+            last_pos: None,
+            seed: cx.config.world_gen.seed,
+        });
+    }
+
+    /// Save the difference between a chunk and its generated terrain.
+    fn save_chunk(world_gen: &AsyncWorldGenerator, store: &DeltaStore, chunk: &Chunk) {
+        let generated = world_gen.generate_chunk(chunk.position);
+        Self::write_delta(store, &ChunkDelta::diff(&generated, chunk));
+    }
+
+    fn write_delta(store: &DeltaStore, delta: &ChunkDelta) {
+        if let Err(e) = store.save(delta) {
+            error!("failed to save chunk delta {:?}: {}", delta.position, e);
+        }
+    }
+
+    /// Save an unloaded chunk in the background, keeping it in `unsaved` until it is written.
+    ///
+    /// The delta is only written if no later save of the chunk was queued in the meantime, so an
+    /// old save can never overwrite a newer one.
+    fn save_unloaded(&mut self, store: &DeltaStore, chunks: Vec<Chunk>) {
+        let mut unsaved = self.unsaved.lock().unwrap();
+        let saves = chunks
+            .into_iter()
+            .map(|chunk| {
+                self.next_save += 1;
+                unsaved.insert(chunk.position, (self.next_save, chunk.clone()));
+                return (self.next_save, chunk);
+            })
+            .collect::<Vec<_>>();
+        drop(unsaved);
+
+        let (gen, store, unsaved) = (
+            Arc::clone(&self.world_gen),
+            store.clone(),
+            Arc::clone(&self.unsaved),
+        );
+        self.pool.spawn(move || {
+            for (save, chunk) in saves {
+                let delta = ChunkDelta::diff(&gen.generate_chunk(chunk.position), &chunk);
+                let mut unsaved = unsaved.lock().unwrap();
+                if unsaved
+                    .get(&chunk.position)
+                    .map(|&(latest, _)| return latest)
+                    == Some(save)
+                {
+                    Self::write_delta(&store, &delta);
+                    unsaved.remove(&chunk.position);
+                }
+            }
+        });
+    }
+
+    /// Save the edits of every chunk that was changed, blocking until they are written.
+    pub fn save(&self) {
+        let Some(store) = &self.deltas else {
+            return;
         };
+        let state = self.state.lock().unwrap();
+        // the background saves skip the chunks that are taken here
+        let unsaved = std::mem::take(&mut *self.unsaved.lock().unwrap());
+        let mut chunks = unsaved
+            .values()
+            .map(|(_, chunk)| return (chunk.position, chunk))
+            .collect::<HashMap<_, _>>();
+        chunks.extend(state.edited().map(|chunk| return (chunk.position, chunk)));
+
+        let world_gen = &self.world_gen;
+        self.pool.install(|| {
+            chunks
+                .par_iter()
+                .for_each(|(_, c)| Self::save_chunk(world_gen, store, c));
+        });
     }
 
-    pub fn update(&mut self, camera_pos: Vector3<f32>) {
-        let pos = ChunkOffset::from(camera_pos);
-        if pos != self.last_pos {
-            self.pool.install(|| {
-                let val = pos;
-                trace!("received chunk offset: {:?}", val);
-
-                // calculate which chunks to generate
-                self.world_gen.center = (val.x(), val.y());
-
-                // generate new chunks
-                let world = self.world_gen.generate();
-
-                // update the world state
-                let mut state = self.state.lock().unwrap();
-                state.chunks = world.chunks;
-                state.dirty = true;
-            });
-            self.last_pos = pos;
+    /// Returns `true` if a chunk is close enough to the camera's chunk to be loaded.
+    fn in_range(rd: i32, center: ChunkOffset, offset: ChunkOffset) -> bool {
+        return (1 - rd..rd).contains(&(offset.x() - center.x()))
+            && (1 - rd..rd).contains(&(offset.y() - center.y()));
+    }
+
+    /// Get the priority of a chunk, based on where the camera is and where it is looking.
+    #[allow(clippy::cast_precision_loss, reason = "precision is not important")]
+    fn priority(camera: &Camera, offset: ChunkOffset) -> Priority {
+        let half = CHUNK_SIZE as f32 / 2.0;
+        let center = Vector2::new(
+            (offset.x() * CHUNK_SIZE) as f32 + half,
+            (offset.y() * CHUNK_SIZE) as f32 + half,
+        );
+        let delta = center - camera.position.xy();
+        let distance = delta.norm();
+
+        // the chunk the camera is in and its neighbours are always in view
+        let forward = camera.forward().xy();
+        let in_view = distance < CHUNK_SIZE as f32 * 1.5
+            || forward.norm() < f32::EPSILON
+            || forward.angle(&delta) < VIEW_ANGLE;
+        return Priority::new(in_view, distance);
+    }
+
+    /// Queue the chunks around the camera and add any finished chunks to the world.
+    pub fn update(&mut self, camera: &Camera) {
+        let pos = ChunkOffset::from(camera.position);
+        if self.last_pos != Some(pos) {
+            trace!("received chunk offset: {:?}", pos);
+
+            // forget about chunks that are no longer in range
+            let rd = self.render_distance;
+            self.jobs.retain(|o| return Self::in_range(rd, pos, o));
+            let mut state = self.state.lock().unwrap();
+            let unloaded = state.retain(|o| return Self::in_range(rd, pos, o));
+
+            for x in pos.x() + 1 - rd..pos.x() + rd {
+                for y in pos.y() + 1 - rd..pos.y() + rd {
+                    let offset = ChunkOffset::new(x, y, 0).unwrap();
+                    if !state.contains(offset) {
+                        self.jobs.submit(offset, Self::priority(camera, offset));
+                    }
+                }
+            }
+            drop(state);
+
+            // keep the edits of unloaded chunks, without holding up the frame
+            if let Some(store) = self.deltas.clone() {
+                if !unloaded.is_empty() {
+                    self.save_unloaded(&store, unloaded);
+                }
+            }
+            self.last_pos = Some(pos);
+        }
+
+        if self.jobs.pending() == 0 {
+            return;
         }
+        self.jobs.prioritise(|o| return Self::priority(camera, o));
+
+        let mut state = self.state.lock().unwrap();
+        let received = self.jobs.drain(FRAME_BUDGET, |chunk| state.insert(chunk));
+        if received > 0 {
+            trace!(
+                "received {received} chunks, {} pending",
+                self.jobs.pending()
+            );
+        }
+    }
+}
+
+impl DrawText for WorldSystem {
+    #[inline]
+    fn name(&self) -> &'static str {
+        return "world";
+    }
+
+    #[inline]
+    fn priority(&self) -> u8 {
+        return 100;
+    }
+
+    #[inline]
+    fn text(&self) -> String {
+        return format!("Seed {} Jobs {}", self.seed, self.jobs.pending());
     }
 }
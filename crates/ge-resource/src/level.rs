@@ -0,0 +1,379 @@
+use ge_util::{ChunkOffset, Seed, WorldGenConfig};
+use ge_world::delta::{ChunkDelta, DeltaError};
+use ron::ser::PrettyConfig;
+use std::{io::ErrorKind, path::PathBuf};
+use thiserror::Error;
+
+/// The name of the metadata file inside a world directory.
+pub const LEVEL_FILE: &str = "level.ron";
+
+/// The name of the directory inside a world directory that chunk deltas are stored in.
+pub const CHUNKS_DIR: &str = "chunks";
+
+/// The newest version of the level format that can be read.
+pub const LEVEL_VERSION: u32 = 1;
+
+/// The metadata of a world, stored in `worlds/<name>/level.ron`.
+///
+/// The generator settings are copied from the config when the world is created, so editing
+/// `engine.toml` afterwards does not change the terrain of existing worlds.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Level {
+    pub version: u32,
+    pub seed: Seed,
+    pub generator: WorldGenConfig,
+    pub spawn: [f32; 3],
+    /// Time in seconds that has passed in the world.
+    pub time: f64,
+    /// Where the player was when the world was last saved.
+    pub player: Option<[f32; 3]>,
+}
+
+impl Level {
+    /// Create the metadata for a new world.
+    #[must_use]
+    pub fn new(generator: &WorldGenConfig, spawn: [f32; 3]) -> Self {
+        return Self {
+            version: LEVEL_VERSION,
+            seed: generator.seed,
+            generator: generator.clone(),
+            spawn,
+            time: 0.0,
+            player: None,
+        };
+    }
+
+    /// Replace the generator settings in `config` with the ones of this world.
+    ///
+    /// Settings that only affect how the world is viewed, like the render distance, culling and
+    /// level of detail, are kept.
+    pub fn apply(&self, config: &mut WorldGenConfig) {
+        *config = WorldGenConfig {
+            seed: self.seed,
+            render_distance: config.render_distance,
+            culling: config.culling,
+            cull_border: config.cull_border,
+            lod: config.lod,
+            ..self.generator.clone()
+        };
+    }
+
+    /// Where the player should appear when the world is opened.
+    #[must_use]
+    pub fn player_or_spawn(&self) -> [f32; 3] {
+        return self.player.unwrap_or(self.spawn);
+    }
+}
+
+impl crate::ResourceManager {
+    /// Get the directory of a named world.
+    ///
+    /// # Errors
+    /// Errors if the name is empty or is not a single path component.
+    pub fn world_path(&self, name: &str) -> Result<PathBuf, LevelError> {
+        let valid = !name.is_empty()
+            && name != "."
+            && name != ".."
+            && !name.contains(|c| return c == '/' || c == '\\');
+        if !valid {
+            return Err(LevelError::InvalidName(name.to_owned()));
+        }
+        return Ok(self.worlds_path.join(name));
+    }
+
+    /// Load the metadata of a named world.
+    ///
+    /// # Errors
+    /// Errors if the file cannot be read or parsed, or was written by a newer version.
+    pub fn load_level(&self, name: &str) -> Result<Level, LevelError> {
+        let str = std::fs::read_to_string(self.world_path(name)?.join(LEVEL_FILE))?;
+        let level: Level = ron::from_str(&str)?;
+        if level.version > LEVEL_VERSION {
+            return Err(LevelError::UnsupportedVersion(level.version));
+        }
+        return Ok(level);
+    }
+
+    /// Save the metadata of a named world, creating its directory if needed.
+    ///
+    /// The file is written next to the old one first, so a crash cannot leave a broken level.
+    ///
+    /// # Errors
+    /// Errors if the file cannot be written to disk.
+    pub fn save_level(&self, name: &str, level: &Level) -> Result<(), LevelError> {
+        let dir = self.world_path(name)?;
+        std::fs::create_dir_all(&dir)?;
+
+        let contents = ron::ser::to_string_pretty(level, PrettyConfig::new())?;
+        let tmp = dir.join(format!("{LEVEL_FILE}.tmp"));
+        std::fs::write(&tmp, contents)?;
+        std::fs::rename(tmp, dir.join(LEVEL_FILE))?;
+        return Ok(());
+    }
+
+    /// Open a named world, creating it with the level returned by `create` if it does not exist.
+    ///
+    /// # Errors
+    /// Errors if the level cannot be loaded or the new level cannot be saved.
+    pub fn open_world(
+        &self,
+        name: &str,
+        create: impl FnOnce() -> Level,
+    ) -> Result<Level, LevelError> {
+        if self.world_path(name)?.join(LEVEL_FILE).exists() {
+            return self.load_level(name);
+        }
+
+        let level = create();
+        self.save_level(name, &level)?;
+        return Ok(level);
+    }
+
+    /// Get the store for the chunk deltas of a named world.
+    ///
+    /// `fingerprint` identifies the generator that the world is generated with, see
+    /// `ge_world::delta::generator_fingerprint`.
+    ///
+    /// # Errors
+    /// Errors if the name is not a valid world name.
+    pub fn delta_store(&self, name: &str, fingerprint: u64) -> Result<DeltaStore, LevelError> {
+        return Ok(DeltaStore {
+            dir: self.world_path(name)?.join(CHUNKS_DIR),
+            fingerprint,
+        });
+    }
+
+    /// Get the names of all worlds, sorted alphabetically.
+    ///
+    /// # Errors
+    /// Errors if the worlds directory exists but cannot be read.
+    pub fn worlds(&self) -> Result<Vec<String>, LevelError> {
+        if !self.worlds_path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&self.worlds_path)? {
+            let entry = entry?;
+            if entry.path().join(LEVEL_FILE).is_file() {
+                names.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+        names.sort();
+        return Ok(names);
+    }
+}
+
+/// Loads and saves the edits made to the chunks of a world, stored in `worlds/<name>/chunks`.
+///
+/// Only the blocks that differ from the generated terrain are stored, one file per chunk. Chunks
+/// that were never changed have no file.
+#[derive(Debug, Clone)]
+pub struct DeltaStore {
+    dir: PathBuf,
+    fingerprint: u64,
+}
+
+impl DeltaStore {
+    /// The fingerprint of the generator that deltas are saved for.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        return self.fingerprint;
+    }
+
+    fn path(&self, offset: ChunkOffset) -> PathBuf {
+        return self
+            .dir
+            .join(format!("{}_{}.delta", offset.x(), offset.y()));
+    }
+
+    /// Load the delta of a chunk, or `None` if the chunk was never changed.
+    ///
+    /// # Errors
+    /// Errors if the file cannot be read or decoded, or was saved for a different generator.
+    pub fn load(&self, offset: ChunkOffset) -> Result<Option<ChunkDelta>, DeltaError> {
+        let bytes = match std::fs::read(self.path(offset)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let delta = ChunkDelta::from_bytes(&bytes, self.fingerprint)?;
+        if delta.position != offset {
+            return Err(DeltaError::Invalid("delta belongs to a different chunk"));
+        }
+        return Ok(Some(delta));
+    }
+
+    /// Save the delta of a chunk, removing the file if the delta is empty.
+    ///
+    /// # Errors
+    /// Errors if the file cannot be written to disk.
+    pub fn save(&self, delta: &ChunkDelta) -> Result<(), DeltaError> {
+        let path = self.path(delta.position);
+        if delta.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+                _ => Ok(()),
+            };
+        }
+
+        std::fs::create_dir_all(&self.dir)?;
+        let tmp = path.with_extension("delta.tmp");
+        std::fs::write(&tmp, delta.to_bytes(self.fingerprint))?;
+        std::fs::rename(tmp, path)?;
+        return Ok(());
+    }
+
+    /// Check that every saved delta was made for the current generator and return how many
+    /// there are.
+    ///
+    /// # Errors
+    /// Errors if a delta cannot be read, or was saved for a different generator, in which case
+    /// applying it would corrupt the terrain.
+    pub fn verify(&self) -> Result<usize, DeltaError> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+
+        let mut count = 0;
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|e| return e != "delta") {
+                continue;
+            }
+            let saved = ChunkDelta::fingerprint(&std::fs::read(path)?)?;
+            if saved != self.fingerprint {
+                return Err(DeltaError::GeneratorMismatch {
+                    saved,
+                    current: self.fingerprint,
+                });
+            }
+            count += 1;
+        }
+        return Ok(count);
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LevelError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("ron deserialize error: {0}")]
+    RonDe(#[from] ron::de::SpannedError),
+    #[error("ron serialize error: {0}")]
+    RonSer(#[from] ron::Error),
+    #[error("invalid world name: {0:?}")]
+    InvalidName(String),
+    #[error("level version {0} is newer than the supported version {LEVEL_VERSION}")]
+    UnsupportedVersion(u32),
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ResourceManager;
+    use ge_util::ChunkPos;
+    use ge_world::{BlockType, Chunk};
+
+    fn resources(test: &str) -> ResourceManager {
+        let dir = std::env::temp_dir().join(format!("ge-level-{test}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        return ResourceManager::default().with_worlds_path(dir);
+    }
+
+    #[test]
+    fn open_and_save() {
+        let rm = resources("open");
+        let config = WorldGenConfig {
+            seed: Seed::from_text("level"),
+            base_height: 80,
+            ..Default::default()
+        };
+
+        let level = rm
+            .open_world("test", || return Level::new(&config, [1.0, 2.0, 3.0]))
+            .unwrap();
+        assert_eq!(vec!["test".to_owned()], rm.worlds().unwrap());
+        assert_eq!([1.0, 2.0, 3.0], level.player_or_spawn());
+
+        // the generator is frozen when the world is created
+        let mut saved = level;
+        saved.player = Some([5.0, 6.0, 7.0]);
+        saved.time = 12.5;
+        rm.save_level("test", &saved).unwrap();
+        let loaded = rm.open_world("test", || unreachable!()).unwrap();
+        assert_eq!(Seed::from_text("level"), loaded.seed);
+        assert_eq!([5.0, 6.0, 7.0], loaded.player_or_spawn());
+        assert_eq!(12.5, loaded.time);
+
+        let mut changed = WorldGenConfig {
+            render_distance: 9,
+            ..Default::default()
+        };
+        loaded.apply(&mut changed);
+        assert_eq!(80, changed.base_height);
+        assert_eq!(Seed::from_text("level"), changed.seed);
+        assert_eq!(9, changed.render_distance);
+
+        std::fs::remove_dir_all(rm.worlds_path).unwrap();
+    }
+
+    #[test]
+    fn deltas() {
+        let rm = resources("deltas");
+        let offset = ChunkOffset::new(-2, 7, 0).unwrap();
+        let store = rm.delta_store("test", 1).unwrap();
+        assert_eq!(0, store.verify().unwrap());
+        assert_eq!(None, store.load(offset).unwrap());
+
+        let generated = Chunk::new(offset);
+        let mut edited = generated.clone();
+        edited.set(ChunkPos::new(3, 4, 5).unwrap(), BlockType::Wood);
+        let delta = ChunkDelta::diff(&generated, &edited);
+        store.save(&delta).unwrap();
+        assert_eq!(Some(delta), store.load(offset).unwrap());
+        assert_eq!(1, store.verify().unwrap());
+
+        // deltas of another generator are not applied
+        let other = rm.delta_store("test", 2).unwrap();
+        assert!(matches!(
+            other.verify(),
+            Err(DeltaError::GeneratorMismatch {
+                saved: 1,
+                current: 2
+            })
+        ));
+        assert!(other.load(offset).is_err());
+
+        // reverting every edit removes the file
+        store
+            .save(&ChunkDelta::diff(&generated, &generated))
+            .unwrap();
+        assert_eq!(None, store.load(offset).unwrap());
+        assert_eq!(0, store.verify().unwrap());
+
+        std::fs::remove_dir_all(rm.worlds_path).unwrap();
+    }
+
+    #[test]
+    fn invalid() {
+        let rm = resources("invalid");
+        for name in ["", "..", "a/b", "a\\b"] {
+            assert!(matches!(
+                rm.load_level(name),
+                Err(LevelError::InvalidName(_))
+            ));
+        }
+
+        let mut level = Level::new(&WorldGenConfig::default(), [0.0; 3]);
+        level.version = LEVEL_VERSION + 1;
+        rm.save_level("future", &level).unwrap();
+        assert!(matches!(
+            rm.load_level("future"),
+            Err(LevelError::UnsupportedVersion(_))
+        ));
+
+        std::fs::remove_dir_all(rm.worlds_path).unwrap();
+    }
+}
@@ -13,12 +13,12 @@
 pub mod block;
 pub mod config;
 pub mod data;
+pub mod level;
 pub mod parse;
 pub mod texture;
 
-use ge_world::BlockType;
-use std::{collections::HashMap, path::PathBuf};
-use texture::TextureArray;
+use std::path::{Path, PathBuf};
+use texture::BlockTextures;
 
 /// A resource manager that caches textures.
 #[derive(Debug)]
@@ -26,8 +26,9 @@ pub struct ResourceManager {
     asset_path: PathBuf,
     config_path: PathBuf,
     data_path: PathBuf,
+    worlds_path: PathBuf,
 
-    map: HashMap<BlockType, TextureArray>,
+    block_textures: Option<BlockTextures>,
 }
 
 impl Default for ResourceManager {
@@ -36,8 +37,24 @@ impl Default for ResourceManager {
             asset_path: PathBuf::from("assets"),
             config_path: PathBuf::from("config"),
             data_path: PathBuf::from("data"),
+            worlds_path: PathBuf::from("worlds"),
 
-            map: HashMap::new(),
+            block_textures: None,
         };
     }
 }
+
+impl ResourceManager {
+    /// The directory that textures and other assets are loaded from.
+    #[must_use]
+    pub fn asset_path(&self) -> &Path {
+        return &self.asset_path;
+    }
+
+    /// Use a different directory for the worlds, instead of `worlds`.
+    #[must_use]
+    pub fn with_worlds_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.worlds_path = path.into();
+        return self;
+    }
+}
@@ -1,56 +1,24 @@
 use crate::block::BlockMeta;
 use ge_world::BlockType;
 use image::GenericImageView;
-use std::{num::NonZeroU32, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
 
 impl crate::ResourceManager {
-    /// Load a texture array from disk. If the texture array has already been loaded, it will be
-    /// returned from the cache.
+    /// Load the textures of every block type from disk. If they have already been loaded, they
+    /// will be returned from the cache.
     ///
     /// # Panics
-    /// Panics if the textures don't exist.
-    pub fn load_texture_array(
+    /// Panics if the textures don't exist or don't all have the same size.
+    pub fn load_block_textures(
         &mut self,
-        block_type: BlockType,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-    ) -> &TextureArray {
-        #[allow(clippy::map_entry, reason = "would require double mutable borrow")]
-        if !self.map.contains_key(&block_type) {
-            let ta = self.load_from_disk(block_type, device, queue);
-            self.map.insert(block_type, ta);
+    ) -> &BlockTextures {
+        if self.block_textures.is_none() {
+            self.block_textures = Some(BlockTextures::load_from_disk(self, device, queue));
         }
 
-        return self.map.get(&block_type).unwrap();
-    }
-
-    /// Loads a texture array from disk.
-    ///
-    /// # Panics
-    /// Panics if the textures don't exist.
-    #[must_use]
-    pub fn load_from_disk(
-        &self,
-        block_type: BlockType,
-        device: &wgpu::Device,
-        queue: &wgpu::Queue,
-    ) -> TextureArray {
-        let block_meta = BlockMeta::load_from_disk(self, block_type).unwrap();
-
-        let textures = block_meta
-            .get_faces()
-            .iter()
-            .map(|s| {
-                let s = format!("{s}.png");
-                return Self::load_texture(self.asset_path.join(s), device, queue);
-            })
-            .collect::<Vec<_>>();
-
-        assert!(
-            !textures.is_empty(),
-            "the texture array must contain at least 1 texture"
-        );
-        return TextureArray::new(device, textures, block_meta);
+        return self.block_textures.as_ref().unwrap();
     }
 
     /// Loads a single texture from disk.
@@ -64,30 +32,74 @@ impl crate::ResourceManager {
     }
 }
 
+/// The textures of every block face, packed into the layers of one `texture_2d_array`.
+///
+/// Faces that use the same image share a layer. Meshes store the layer of every face in their
+/// vertices, so all block types are drawn with the same bind group.
 #[derive(Debug)]
-pub struct TextureArray {
-    pub textures: Vec<Texture>,
+pub struct BlockTextures {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub bind_group: Arc<wgpu::BindGroup>,
-    pub block_meta: BlockMeta,
+    layers: HashMap<BlockType, [u32; 6]>,
 }
 
-impl TextureArray {
-    /// Create a new [`TextureArray`] from a list of [`Texture`]s.
+impl BlockTextures {
+    /// Load the faces of every block type and pack them into a texture array.
     ///
     /// # Panics
-    /// Panics if the length of `textures` is 0.
+    /// Panics if the textures don't exist or don't all have the same size.
     #[must_use]
-    pub fn new(device: &wgpu::Device, textures: Vec<Texture>, block_meta: BlockMeta) -> Self {
-        let texture_views = textures
-            .iter()
-            .map(|texture| return &texture.view)
-            .collect::<Vec<_>>();
+    pub fn load_from_disk(
+        rm: &crate::ResourceManager,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Self {
+        let mut names: Vec<String> = Vec::new();
+        let mut layers = HashMap::new();
+        for ty in BlockType::ALL {
+            if ty == BlockType::Air {
+                continue;
+            }
+            let block_meta = BlockMeta::load_from_disk(rm, ty).unwrap();
+            let faces = block_meta.get_faces().each_ref().map(|name| {
+                let layer = names
+                    .iter()
+                    .position(|n| return n == name)
+                    .unwrap_or_else(|| {
+                        names.push(name.clone());
+                        return names.len() - 1;
+                    });
+                return u32::try_from(layer).unwrap();
+            });
+            layers.insert(ty, faces);
+        }
 
-        let texture_samplers = textures
+        let images = names
             .iter()
-            .map(|texture| return &texture.sampler)
+            .map(|name| {
+                let path = rm.asset_path().join(format!("{name}.png"));
+                return image::open(path).unwrap().to_rgba8();
+            })
             .collect::<Vec<_>>();
+        assert!(!images.is_empty(), "there must be at least 1 block texture");
+        let texture = Self::create_texture(device, queue, &images);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        // merged faces have texture coordinates past 1, so the texture has to repeat
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
@@ -96,20 +108,16 @@ impl TextureArray {
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
                         sample_type: wgpu::TextureSampleType::Float { filterable: true },
                     },
-                    count: Some(
-                        NonZeroU32::new(u32::try_from(textures.len()).unwrap_or(1)).unwrap(),
-                    ),
+                    count: None,
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: Some(
-                        NonZeroU32::new(u32::try_from(textures.len()).unwrap_or(1)).unwrap(),
-                    ),
+                    count: None,
                 },
             ],
             label: Some("block_bind_group_layout"),
@@ -120,22 +128,88 @@ impl TextureArray {
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureViewArray(&texture_views),
+                    resource: wgpu::BindingResource::TextureView(&view),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::SamplerArray(&texture_samplers),
+                    resource: wgpu::BindingResource::Sampler(&sampler),
                 },
             ],
-            label: Some("diffuse_bind_group"),
+            label: Some("block_bind_group"),
         });
 
         return Self {
-            textures,
+            texture,
+            view,
+            sampler,
             bind_group_layout,
             bind_group: Arc::new(bind_group),
-            block_meta,
+            layers,
+        };
+    }
+
+    /// Create a texture with one layer for every image.
+    fn create_texture(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        images: &[image::RgbaImage],
+    ) -> wgpu::Texture {
+        let (width, height) = images[0].dimensions();
+        assert!(
+            images
+                .iter()
+                .all(|img| return img.dimensions() == (width, height)),
+            "all block textures must have the same size"
+        );
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: u32::try_from(images.len()).unwrap(),
         };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("block_textures"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (layer, img) in (0..).zip(images) {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer,
+                    },
+                },
+                img,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+        }
+        return texture;
+    }
+
+    /// The layers of the faces of a block type, in the order used by `BlockMeta`.
+    ///
+    /// Air has no textures, so all of its faces use the first layer.
+    #[must_use]
+    pub fn layers(&self, ty: BlockType) -> [u32; 6] {
+        return self.layers.get(&ty).copied().unwrap_or_default();
     }
 }
 
@@ -0,0 +1,133 @@
+//! Helpers for the command line tools, which all take `--key value` arguments and a config file.
+use crate::{EngineConfig, Seed};
+use std::path::Path;
+
+/// Parse `--key value` and `--key=value` arguments, calling `apply` with every pair.
+///
+/// A value is never taken from an argument that starts with `--`, so a key without a value does
+/// not swallow the key after it. Every argument is parsed, even after an error.
+///
+/// # Errors
+/// Errors with the first key that has no value or the first error returned by `apply`.
+pub fn parse_args(
+    args: impl IntoIterator<Item = String>,
+    mut apply: impl FnMut(&str, String) -> Result<(), String>,
+) -> Result<(), String> {
+    let mut args = args.into_iter().peekable();
+    let mut result = Ok(());
+    while let Some(arg) = args.next() {
+        let (key, value) = match arg.split_once('=') {
+            Some((key, value)) => (key.to_owned(), Some(value.to_owned())),
+            None => (arg, None),
+        };
+        let applied = match value.or_else(|| return args.next_if(|a| return !a.starts_with("--"))) {
+            Some(value) => apply(&key, value),
+            None => Err(format!("missing value for {key}")),
+        };
+        if result.is_ok() {
+            result = applied;
+        }
+    }
+    return result;
+}
+
+/// Parse a pair of integers written as `x,y`.
+#[must_use]
+pub fn parse_pair(value: &str) -> Option<(i32, i32)> {
+    let (a, b) = value.split_once(',')?;
+    return Some((a.trim().parse().ok()?, b.trim().parse().ok()?));
+}
+
+/// Load the config of a tool and override its seed.
+///
+/// If the file cannot be read, this falls back to the default config and returns why as a
+/// warning next to it, for the tool to print.
+///
+/// # Errors
+/// Errors if the file is not a valid config.
+pub fn load_config(
+    path: &Path,
+    seed: Option<Seed>,
+) -> Result<(EngineConfig, Option<String>), String> {
+    let (mut config, warning): (EngineConfig, _) = match std::fs::read_to_string(path) {
+        Ok(s) => (
+            toml::from_str(&s).map_err(|e| return format!("invalid config: {e}"))?,
+            None,
+        ),
+        Err(e) => (
+            EngineConfig::default(),
+            Some(format!(
+                "could not read {}: {e}, using defaults",
+                path.display()
+            )),
+        ),
+    };
+    if let Some(seed) = seed {
+        config.world_gen.seed = seed;
+    }
+    return Ok((config, warning));
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Vec<(String, String)>, String> {
+        let mut pairs = Vec::new();
+        parse_args(args.iter().map(|a| return a.to_string()), |key, value| {
+            if key == "--bad" {
+                return Err(format!("unknown argument: {key}"));
+            }
+            pairs.push((key.to_owned(), value));
+            return Ok(());
+        })?;
+        return Ok(pairs);
+    }
+
+    #[test]
+    fn args() {
+        assert_eq!(
+            Ok(vec![
+                ("--seed".to_owned(), "4".to_owned()),
+                ("--out".to_owned(), "a=b".to_owned()),
+            ]),
+            parse(&["--seed", "4", "--out=a=b"])
+        );
+        assert_eq!(
+            Err("missing value for --seed".to_owned()),
+            parse(&["--seed"])
+        );
+        assert_eq!(
+            Err("unknown argument: --bad".to_owned()),
+            parse(&["--bad", "1", "--seed", "4"])
+        );
+        // a key without a value leaves the next key alone, and the first error is kept
+        assert_eq!(
+            Err("missing value for --seed".to_owned()),
+            parse(&["--seed", "--out", "x", "--bad", "1"])
+        );
+    }
+
+    #[test]
+    fn pairs() {
+        assert_eq!(Some((-8, 16)), parse_pair("-8, 16"));
+        assert_eq!(None, parse_pair("8"));
+        assert_eq!(None, parse_pair("a,1"));
+    }
+
+    #[test]
+    fn config() {
+        let (missing, warning) =
+            load_config(Path::new("missing/engine.toml"), Some(Seed::new(3))).unwrap();
+        assert_eq!(Seed::new(3), missing.world_gen.seed);
+        assert!(warning
+            .unwrap()
+            .starts_with("could not read missing/engine.toml"));
+
+        let path = std::env::temp_dir().join("ge-util-cli-invalid.toml");
+        std::fs::write(&path, "world_gen = 1").unwrap();
+        assert!(load_config(&path, None).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
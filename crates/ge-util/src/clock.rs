@@ -0,0 +1,241 @@
+use crate::{lerp, TimeConfig};
+use nalgebra::Vector3;
+use std::f64::consts::TAU;
+
+const NIGHT_SKY: [f64; 3] = [0.01, 0.01, 0.04];
+const DAY_SKY: [f64; 3] = [0.45, 0.65, 0.95];
+const SUNSET_SKY: [f64; 3] = [0.85, 0.45, 0.25];
+
+/// The lowest light level, at midnight.
+const MIN_AMBIENT: f64 = 0.1;
+/// The light level that is always there during the day, even in the shade.
+const MAX_AMBIENT: f64 = 0.4;
+
+/// Keeps track of the time in the world.
+///
+/// Time is counted in days, so the time of day is the fractional part, with 0 at midnight, 0.25
+/// at sunrise, 0.5 at noon and 0.75 at sunset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldClock {
+    days: f64,
+    day_length: f64,
+    rate: f64,
+    frozen: bool,
+}
+
+impl WorldClock {
+    /// Create a clock at the start time of the config.
+    #[must_use]
+    pub fn new(config: &TimeConfig) -> Self {
+        return Self {
+            days: config.start,
+            day_length: config.day_length,
+            rate: config.rate,
+            frozen: false,
+        };
+    }
+
+    /// Create a clock that continues from `seconds` of world time.
+    #[must_use]
+    pub fn from_seconds(config: &TimeConfig, seconds: f64) -> Self {
+        return Self {
+            days: seconds / config.day_length,
+            ..Self::new(config)
+        };
+    }
+
+    /// Advance the clock by `dt` real seconds, unless it is frozen.
+    pub fn advance(&mut self, dt: f64) {
+        if !self.frozen {
+            self.days += dt * self.rate / self.day_length;
+        }
+    }
+
+    /// The time that passed in the world in seconds.
+    #[must_use]
+    pub fn seconds(&self) -> f64 {
+        return self.days * self.day_length;
+    }
+
+    /// The number of whole days that passed.
+    #[allow(clippy::cast_possible_truncation, reason = "days fit in a u64")]
+    #[allow(clippy::cast_sign_loss, reason = "days is never negative")]
+    #[must_use]
+    pub fn day(&self) -> u64 {
+        return self.days.floor() as u64;
+    }
+
+    /// The time of day, from 0 (midnight) up to 1 (the next midnight).
+    #[must_use]
+    pub fn time_of_day(&self) -> f64 {
+        return self.days.fract();
+    }
+
+    /// Set the time of day, keeping the current day.
+    ///
+    /// Values outside of `0..1` wrap around, without changing the day. Infinite and NaN values
+    /// are ignored.
+    pub fn set_time_of_day(&mut self, time: f64) {
+        if time.is_finite() {
+            self.days = self.days.floor() + time.rem_euclid(1.0);
+        }
+    }
+
+    #[must_use]
+    pub fn rate(&self) -> f64 {
+        return self.rate;
+    }
+
+    /// Set how many times faster than real time the clock runs.
+    ///
+    /// Negative rates stop the clock, and infinite and NaN rates are ignored.
+    pub fn set_rate(&mut self, rate: f64) {
+        if rate.is_finite() {
+            self.rate = rate.max(0.0);
+        }
+    }
+
+    #[must_use]
+    pub fn is_frozen(&self) -> bool {
+        return self.frozen;
+    }
+
+    /// Stop or resume the clock.
+    pub fn set_frozen(&mut self, frozen: bool) {
+        self.frozen = frozen;
+    }
+
+    /// Get the position of the sun and moon and the light in the sky.
+    #[must_use]
+    pub fn sky(&self) -> Sky {
+        return Sky::at(self.time_of_day());
+    }
+}
+
+/// The state of the sky at a time of day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sky {
+    /// The direction towards the sun, `z` is up.
+    pub sun: Vector3<f32>,
+    /// The direction towards the moon, which is always opposite the sun.
+    pub moon: Vector3<f32>,
+    /// The colour of the sky, used to clear the screen.
+    pub color: [f32; 3],
+    /// The light level in the shade, from 0 to 1.
+    pub ambient: f32,
+    /// How much of the light of the sky reaches the world, from 0 at night to 1 at day.
+    pub sky_light: f32,
+}
+
+impl Sky {
+    /// Get the sky at a time of day, from 0 (midnight) to 1 (the next midnight).
+    #[allow(
+        clippy::cast_possible_truncation,
+        reason = "precision is not important"
+    )]
+    #[must_use]
+    pub fn at(time: f64) -> Self {
+        // the sun rises in the east (+x) at 0.25 and is straight up at noon
+        let angle = (time - 0.25) * TAU;
+        let sun = Vector3::new(angle.cos(), 0.2, angle.sin()).normalize();
+
+        let sky_light = smoothstep(-0.1, 0.2, sun.z);
+        // the sky turns orange while the sun is close to the horizon
+        let twilight = (1.0 - sun.z.abs() / 0.25).clamp(0.0, 1.0) * 0.6;
+        let color = [0, 1, 2].map(|i| {
+            let sky = lerp(NIGHT_SKY[i], DAY_SKY[i], sky_light);
+            return lerp(sky, SUNSET_SKY[i], twilight) as f32;
+        });
+
+        let sun = sun.cast::<f32>();
+        return Self {
+            sun,
+            moon: -sun,
+            color,
+            ambient: lerp(MIN_AMBIENT, MAX_AMBIENT, sky_light) as f32,
+            sky_light: sky_light as f32,
+        };
+    }
+}
+
+fn smoothstep(from: f64, to: f64, x: f64) -> f64 {
+    let t = ((x - from) / (to - from)).clamp(0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn config() -> TimeConfig {
+        return TimeConfig {
+            day_length: 100.0,
+            rate: 2.0,
+            start: 0.5,
+        };
+    }
+
+    #[test]
+    fn advance() {
+        let mut clock = WorldClock::new(&config());
+        assert_eq!(50.0, clock.seconds());
+
+        clock.advance(15.0);
+        assert_eq!(0, clock.day());
+        assert!((clock.time_of_day() - 0.8).abs() < 1e-9);
+        clock.advance(10.0);
+        assert_eq!(1, clock.day());
+
+        clock.set_frozen(true);
+        clock.advance(1000.0);
+        assert_eq!(1, clock.day());
+
+        clock.set_time_of_day(1.25);
+        assert_eq!(1, clock.day());
+        assert!((clock.time_of_day() - 0.25).abs() < 1e-9);
+
+        let saved = WorldClock::from_seconds(&config(), clock.seconds());
+        assert!((saved.seconds() - clock.seconds()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn non_finite() {
+        let mut clock = WorldClock::new(&config());
+        clock.set_time_of_day(f64::NAN);
+        clock.set_time_of_day(f64::INFINITY);
+        assert_eq!(0.5, clock.time_of_day());
+
+        clock.set_rate(f64::INFINITY);
+        clock.set_rate(f64::NAN);
+        assert_eq!(2.0, clock.rate());
+        clock.set_rate(-1.0);
+        assert_eq!(0.0, clock.rate());
+    }
+
+    #[rstest]
+    #[case(0.0, false)]
+    #[case(0.3, true)]
+    #[case(0.5, true)]
+    #[case(0.8, false)]
+    fn sun_and_moon(#[case] time: f64, #[case] day: bool) {
+        let sky = Sky::at(time);
+        assert_eq!(day, sky.sun.z > 0.0);
+        assert_eq!(sky.sun, -sky.moon);
+        assert_eq!(day, sky.sky_light > 0.5);
+        assert!(sky.ambient >= MIN_AMBIENT as f32 && sky.ambient <= MAX_AMBIENT as f32);
+    }
+
+    #[test]
+    fn noon_and_midnight() {
+        let noon = Sky::at(0.5);
+        assert!((noon.sun.z - noon.sun.norm()).abs() < 0.05);
+        assert_eq!(1.0, noon.sky_light);
+        assert_eq!(DAY_SKY.map(|c| return c as f32), noon.color);
+
+        let midnight = Sky::at(0.0);
+        assert_eq!(0.0, midnight.sky_light);
+        assert_eq!(NIGHT_SKY.map(|c| return c as f32), midnight.color);
+    }
+}
@@ -1,10 +1,13 @@
-use std::time::Duration;
+use crate::seed::Seed;
+use std::{path::PathBuf, time::Duration};
 
-#[derive(Default, Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+#[derive(Default, Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct EngineConfig {
     pub renderer: RendererConfig,
     pub camera: CameraConfig,
     pub world_gen: WorldGenConfig,
+    #[serde(default)]
+    pub time: TimeConfig,
 }
 
 #[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
@@ -23,8 +26,11 @@ pub struct CameraConfig {
     pub sensitivity: f32,
 }
 
-#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct WorldGenConfig {
+    /// The world seed, either an integer or a string that is hashed into one.
+    #[serde(default)]
+    pub seed: Seed,
     pub render_distance: usize,
     pub base_height: i32,
     pub sea_level: i32,
@@ -32,6 +38,42 @@ pub struct WorldGenConfig {
     pub culling: bool,
     pub cull_border: bool,
     pub noise: NoiseConfig,
+    #[serde(default)]
+    pub erosion: ErosionConfig,
+    #[serde(default)]
+    pub rivers: RiverConfig,
+    #[serde(default)]
+    pub lakes: LakeConfig,
+    #[serde(default)]
+    pub lod: LodConfig,
+    #[serde(default)]
+    pub scripts: ScriptConfig,
+    /// The stages of the world-gen pipeline, in the order they are applied.
+    ///
+    /// When this is empty, the built-in stages that are enabled above are used, followed by the
+    /// scripts.
+    #[serde(default)]
+    pub stages: Vec<StageConfig>,
+}
+
+/// A stage of the world-gen pipeline, built by the transformation registered under `name`.
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StageConfig {
+    pub name: String,
+    /// The parameters of the stage, which replace the settings it takes from the config.
+    #[serde(default)]
+    pub params: toml::Table,
+}
+
+impl StageConfig {
+    /// Create a stage without any parameters.
+    #[must_use]
+    pub fn new(name: impl Into<String>) -> Self {
+        return Self {
+            name: name.into(),
+            params: toml::Table::new(),
+        };
+    }
 }
 
 #[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
@@ -43,6 +85,94 @@ pub struct NoiseConfig {
     pub persistence: f32,
 }
 
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct ErosionConfig {
+    pub enabled: bool,
+    /// Number of droplets simulated per chunk column.
+    pub iterations: u32,
+    /// Maximum number of steps a droplet can take before it evaporates.
+    pub max_lifetime: u32,
+    pub inertia: f32,
+    pub capacity: f32,
+    pub min_capacity: f32,
+    pub deposition: f32,
+    pub erosion: f32,
+    pub evaporation: f32,
+    pub gravity: f32,
+    /// Radius of the erosion brush in blocks.
+    pub radius: u32,
+    /// Number of thermal erosion passes, `0` disables thermal erosion.
+    pub thermal_iterations: u32,
+    /// Maximum height difference between neighbouring columns before material slides.
+    pub talus: f32,
+    pub thermal_rate: f32,
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct RiverConfig {
+    pub enabled: bool,
+    /// Size of the grid cells that can each contain one river source.
+    pub spacing: i32,
+    /// Chance of a grid cell containing a river source.
+    pub frequency: f32,
+    /// Minimum surface height for a river source.
+    pub source_height: i32,
+    /// Maximum number of columns a river can flow through.
+    pub max_length: u32,
+    /// Radius of the river bed in blocks.
+    pub width: i32,
+    /// Depth of the water in blocks.
+    pub depth: i32,
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct LakeConfig {
+    pub enabled: bool,
+    /// Size of the grid cells that can each contain one lake.
+    pub spacing: i32,
+    /// Maximum distance from the deepest point of a lake to its shore.
+    pub max_radius: i32,
+    /// Maximum depth of a lake in blocks.
+    pub max_depth: i32,
+    /// Minimum number of columns a lake must cover.
+    pub min_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct LodConfig {
+    pub enabled: bool,
+    /// Distance in chunks up to which chunks are drawn at full detail.
+    ///
+    /// Each further level of detail covers twice the distance of the previous one.
+    pub distance: u32,
+    /// Extra distance in chunks a chunk has to move away before it switches to a coarser level.
+    pub hysteresis: u32,
+    /// Depth in cells of the walls around the edges of coarse chunks that hide seams.
+    pub skirt: u32,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ScriptConfig {
+    /// The directory that scripts are loaded from, as `<dir>/<name>.rhai`.
+    pub dir: PathBuf,
+    /// The scripts that run as stages of the world-gen pipeline, in order.
+    ///
+    /// They run last, after the built-in stages have painted the surface.
+    pub stages: Vec<String>,
+    /// The most operations a script may run for a single chunk before it is stopped.
+    pub max_operations: u64,
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
+pub struct TimeConfig {
+    /// Length of a full day in real seconds.
+    pub day_length: f64,
+    /// How many times faster than real time the world clock runs.
+    pub rate: f64,
+    /// Time of day a new world starts at, from 0 (midnight) to 1 (the next midnight).
+    pub start: f64,
+}
+
 impl RendererConfig {
     pub fn target_frame_time(self) -> Duration {
         return Duration::from_micros(1_000_000 / u64::from(self.target_fps));
@@ -82,6 +212,7 @@ impl Default for CameraConfig {
 impl Default for WorldGenConfig {
     fn default() -> Self {
         return Self {
+            seed: Seed::default(),
             render_distance: 3,
             base_height: 100,
             sea_level: 90,
@@ -89,6 +220,12 @@ impl Default for WorldGenConfig {
             culling: true,
             cull_border: false,
             noise: Default::default(),
+            erosion: Default::default(),
+            rivers: Default::default(),
+            lakes: Default::default(),
+            lod: Default::default(),
+            scripts: Default::default(),
+            stages: Vec::new(),
         };
     }
 }
@@ -104,3 +241,81 @@ impl Default for NoiseConfig {
         };
     }
 }
+
+impl Default for ErosionConfig {
+    fn default() -> Self {
+        return Self {
+            enabled: false,
+            iterations: 64,
+            max_lifetime: 30,
+            inertia: 0.05,
+            capacity: 4.0,
+            min_capacity: 0.01,
+            deposition: 0.3,
+            erosion: 0.3,
+            evaporation: 0.01,
+            gravity: 4.0,
+            radius: 2,
+            thermal_iterations: 0,
+            talus: 1.5,
+            thermal_rate: 0.5,
+        };
+    }
+}
+
+impl Default for RiverConfig {
+    fn default() -> Self {
+        return Self {
+            enabled: false,
+            spacing: 64,
+            frequency: 0.5,
+            source_height: 105,
+            max_length: 256,
+            width: 1,
+            depth: 2,
+        };
+    }
+}
+
+impl Default for LakeConfig {
+    fn default() -> Self {
+        return Self {
+            enabled: false,
+            spacing: 64,
+            max_radius: 24,
+            max_depth: 6,
+            min_size: 8,
+        };
+    }
+}
+
+impl Default for ScriptConfig {
+    fn default() -> Self {
+        return Self {
+            dir: PathBuf::from("scripts"),
+            stages: Vec::new(),
+            max_operations: 10_000_000,
+        };
+    }
+}
+
+impl Default for LodConfig {
+    fn default() -> Self {
+        return Self {
+            enabled: false,
+            distance: 2,
+            hysteresis: 1,
+            skirt: 2,
+        };
+    }
+}
+
+impl Default for TimeConfig {
+    fn default() -> Self {
+        return Self {
+            day_length: 1200.0,
+            rate: 1.0,
+            start: 0.3,
+        };
+    }
+}
@@ -6,6 +6,9 @@ pub const CHUNK_SIZE: i32 = 16;
 pub const CHUNK_SIZE_MASK: i32 = CHUNK_SIZE - 1;
 pub const CHUNK_HEIGHT: i32 = 256;
 pub const CHUNK_HEIGHT_MASK: i32 = CHUNK_HEIGHT - 1;
+/// The height of a vertical chunk section, sections are `CHUNK_SIZE` wide and deep.
+pub const SECTION_SIZE: i32 = 16;
+pub const SECTIONS_PER_CHUNK: i32 = CHUNK_HEIGHT / SECTION_SIZE;
 
 /// A coordinate position in the world.
 ///
@@ -76,6 +79,21 @@ impl WorldPos {
         .expect("chunk pos domain should be smaller than world pos");
     }
 
+    /// Get the offset of the chunk that contains this `WorldPos`.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "dividing by the chunk size keeps the offset in range"
+    )]
+    #[must_use]
+    pub fn to_chunk_offset(&self) -> ChunkOffset {
+        return ChunkOffset::new(
+            self.x.div_euclid(CHUNK_SIZE),
+            self.y.div_euclid(CHUNK_SIZE),
+            0,
+        )
+        .expect("chunk offset domain should be larger than world pos");
+    }
+
     #[inline]
     #[must_use]
     pub fn is_valid(&self) -> bool {
@@ -202,6 +220,24 @@ mod tests {
             assert_eq!(pos.to_world_pos(off), expected);
             Ok(())
         }
+
+        #[rstest]
+        #[case(wpos!(1, 2, 3)?, (0, 0))]
+        #[case(wpos!(16, 31, 3)?, (1, 1))]
+        #[case(wpos!(-1, -16, 3)?, (-1, -1))]
+        #[case(wpos!(-17, 40, 3)?, (-2, 2))]
+        fn to_chunk_offset(
+            #[case] pos: WorldPos,
+            #[case] offset: (i32, i32),
+        ) -> Result<(), crate::coords::CoordError> {
+            assert_eq!(
+                pos.to_chunk_offset(),
+                ChunkOffset::new(offset.0, offset.1, 0)?
+            );
+            let back = pos.to_chunk_pos().to_world_pos(pos.to_chunk_offset());
+            assert_eq!(back, pos);
+            Ok(())
+        }
     }
 
     mod world_pos {
@@ -0,0 +1,173 @@
+use nalgebra::{Matrix4, Vector3, Vector4};
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    #[must_use]
+    pub fn new(min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        return Self { min, max };
+    }
+
+    #[must_use]
+    pub fn center(&self) -> Vector3<f32> {
+        return (self.min + self.max) / 2.0;
+    }
+
+    /// The smallest box that contains every point, or `None` if there are no points.
+    pub fn from_points(points: impl IntoIterator<Item = Vector3<f32>>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        return Some(points.fold(Self::new(first, first), |aabb, p| {
+            return Self::new(aabb.min.inf(&p), aabb.max.sup(&p));
+        }));
+    }
+}
+
+/// A plane that divides space into the points in front of it and the points behind it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    /// The unit normal, which points to the front of the plane.
+    pub normal: Vector3<f32>,
+    pub offset: f32,
+}
+
+impl Plane {
+    /// Create a plane from the coefficients of `ax + by + cz + d = 0`, normalizing them.
+    #[must_use]
+    pub fn from_coefficients(v: Vector4<f32>) -> Self {
+        let length = v.xyz().norm();
+        return Self {
+            normal: v.xyz() / length,
+            offset: v.w / length,
+        };
+    }
+
+    /// The signed distance of a point to the plane, which is negative behind it.
+    #[must_use]
+    pub fn distance(&self, point: Vector3<f32>) -> f32 {
+        return self.normal.dot(&point) + self.offset;
+    }
+}
+
+/// The volume that a camera can see, bounded by six planes facing inwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// The left, right, bottom, top, near and far planes.
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extract the planes of a view-projection matrix.
+    ///
+    /// The near plane assumes a clip space depth of `-1..1`. For a matrix with a depth of `0..1`
+    /// the near plane ends up slightly behind the real one, which only means that fewer boxes are
+    /// culled.
+    #[must_use]
+    pub fn from_matrix(view_proj: &Matrix4<f32>) -> Self {
+        let row = |i| return view_proj.row(i).transpose();
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        return Self {
+            planes: [w + x, w - x, w + y, w - y, w + z, w - z].map(Plane::from_coefficients),
+        };
+    }
+
+    /// Returns `true` if any part of a box might be inside the frustum.
+    ///
+    /// Boxes are only rejected if they are completely behind one of the planes, so a few boxes
+    /// near the corners of the frustum are kept even though they cannot be seen.
+    #[must_use]
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        return self.planes.iter().all(|plane| {
+            // the corner of the box that is the furthest in front of the plane
+            let corner = Vector3::from_fn(|i, _| {
+                return if plane.normal[i] >= 0.0 {
+                    aabb.max[i]
+                } else {
+                    aabb.min[i]
+                };
+            });
+            return plane.distance(corner) >= 0.0;
+        });
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point3;
+
+    /// A camera at the origin looking along +x, with +z up.
+    fn frustum() -> Frustum {
+        let view = Matrix4::look_at_rh(
+            &Point3::origin(),
+            &Point3::new(1.0, 0.0, 0.0),
+            &Vector3::z(),
+        );
+        let projection = Matrix4::new_perspective(1.0, std::f32::consts::FRAC_PI_2, 0.1, 100.0);
+        return Frustum::from_matrix(&(projection * view));
+    }
+
+    fn cube(center: [f32; 3]) -> Aabb {
+        let center = Vector3::from(center);
+        return Aabb::new(center.add_scalar(-0.5), center.add_scalar(0.5));
+    }
+
+    #[test]
+    fn planes() {
+        let frustum = frustum();
+        for plane in frustum.planes {
+            assert!((plane.normal.norm() - 1.0).abs() < 1e-5);
+            // the point in the middle of the view is in front of every plane
+            assert!(plane.distance(Vector3::new(10.0, 0.0, 0.0)) > 0.0);
+        }
+
+        let [left, right, bottom, top, near, far] = frustum.planes;
+        // with a field of view of 90 degrees the sides are at 45 degrees to the view direction
+        assert!(left.distance(Vector3::new(10.0, 9.9, 0.0)) > 0.0);
+        assert!(left.distance(Vector3::new(10.0, 10.1, 0.0)) < 0.0);
+        assert!(right.distance(Vector3::new(10.0, -10.1, 0.0)) < 0.0);
+        assert!(bottom.distance(Vector3::new(10.0, 0.0, -10.1)) < 0.0);
+        assert!(top.distance(Vector3::new(10.0, 0.0, 10.1)) < 0.0);
+        assert!((near.distance(Vector3::zeros()) + 0.1).abs() < 1e-3);
+        assert!((far.distance(Vector3::new(100.0, 0.0, 0.0))).abs() < 1e-2);
+    }
+
+    #[test]
+    fn intersects() {
+        let frustum = frustum();
+        assert!(frustum.intersects(&cube([10.0, 0.0, 0.0])));
+        assert!(!frustum.intersects(&cube([-10.0, 0.0, 0.0])));
+        assert!(!frustum.intersects(&cube([10.0, 20.0, 0.0])));
+        assert!(!frustum.intersects(&cube([10.0, 0.0, -20.0])));
+        assert!(!frustum.intersects(&cube([200.0, 0.0, 0.0])));
+        // boxes that are only partly inside are kept
+        assert!(frustum.intersects(&cube([10.0, 10.4, 0.0])));
+        assert!(frustum.intersects(&Aabb::new(
+            Vector3::new(-50.0, -1.0, -1.0),
+            Vector3::new(50.0, 1.0, 1.0)
+        )));
+    }
+
+    #[test]
+    fn from_points() {
+        assert_eq!(None, Aabb::from_points([]));
+        let aabb = Aabb::from_points([
+            Vector3::new(1.0, -2.0, 3.0),
+            Vector3::new(-1.0, 4.0, 0.0),
+            Vector3::new(0.0, 0.0, 5.0),
+        ]);
+        assert_eq!(
+            Some(Aabb::new(
+                Vector3::new(-1.0, -2.0, 0.0),
+                Vector3::new(1.0, 4.0, 5.0)
+            )),
+            aabb
+        );
+    }
+}
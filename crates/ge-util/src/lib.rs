@@ -10,17 +10,26 @@
 #![feature(lint_reasons)]
 
 mod circle;
+pub mod cli;
+pub mod clock;
 mod config;
 mod convert;
 pub mod coords;
+pub mod frustum;
 mod lerp;
 mod macros;
+pub mod seed;
 
 pub use circle::points_in_circle;
-pub use config::{EngineConfig, WorldGenConfig};
+pub use clock::WorldClock;
+pub use config::{
+    EngineConfig, ErosionConfig, LodConfig, ScriptConfig, StageConfig, TimeConfig, WorldGenConfig,
+};
 pub use convert::{deg_to_rad, rad_to_deg};
 pub use coords::{ChunkOffset, ChunkPos, WorldPos};
+pub use frustum::{Aabb, Frustum};
 pub use lerp::lerp;
+pub use seed::Seed;
 
 #[macro_export]
 macro_rules! wpos {
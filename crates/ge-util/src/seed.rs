@@ -0,0 +1,170 @@
+use std::{convert::Infallible, str::FromStr};
+
+/// A world seed.
+///
+/// A seed can be created from a number or from any string. Strings that are valid integers are
+/// used as numbers, any other string is hashed with FNV-1a. Sub-seeds for individual noise layers
+/// and features are derived from the world seed using [`Seed::derive`], so changing one feature
+/// never affects the randomness of another.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Seed(u64);
+
+impl Seed {
+    #[must_use]
+    pub fn new(value: u64) -> Self {
+        return Self(value);
+    }
+
+    /// Create a seed from arbitrary text.
+    #[must_use]
+    pub fn from_text(text: &str) -> Self {
+        let text = text.trim();
+        if let Ok(value) = text.parse::<u64>() {
+            return Self(value);
+        }
+        if let Ok(value) = text.parse::<i64>() {
+            #[allow(clippy::cast_sign_loss, reason = "only the bits are used")]
+            return Self(value as u64);
+        }
+        return Self(fnv1a(text.as_bytes()));
+    }
+
+    /// Derive a sub-seed for the feature called `label`.
+    #[must_use]
+    pub fn derive(self, label: &str) -> Self {
+        return Self(splitmix(self.0 ^ fnv1a(label.as_bytes())));
+    }
+
+    #[must_use]
+    pub fn value(self) -> u64 {
+        return self.0;
+    }
+}
+
+impl From<u64> for Seed {
+    fn from(value: u64) -> Self {
+        return Self(value);
+    }
+}
+
+impl From<Seed> for u64 {
+    fn from(value: Seed) -> Self {
+        return value.0;
+    }
+}
+
+impl FromStr for Seed {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return Ok(Self::from_text(s));
+    }
+}
+
+impl std::fmt::Display for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "{}", self.0);
+    }
+}
+
+impl serde::Serialize for Seed {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        // TOML integers are signed, so store the bits as an `i64` to keep large seeds valid
+        #[allow(clippy::cast_possible_wrap, reason = "only the bits are used")]
+        return serializer.serialize_i64(self.0 as i64);
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Seed {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct SeedVisitor;
+
+        impl serde::de::Visitor<'_> for SeedVisitor {
+            type Value = Seed;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                return f.write_str("an integer or a string");
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Seed, E> {
+                return Ok(Seed(v));
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Seed, E> {
+                #[allow(clippy::cast_sign_loss, reason = "only the bits are used")]
+                return Ok(Seed(v as u64));
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Seed, E> {
+                return Ok(Seed::from_text(v));
+            }
+        }
+
+        return deserializer.deserialize_any(SeedVisitor);
+    }
+}
+
+/// The 64-bit FNV-1a hash, which is stable across platforms and compiler versions.
+#[must_use]
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut h: u64 = 0xCBF2_9CE4_8422_2325;
+    for &b in bytes {
+        h ^= u64::from(b);
+        h = h.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    return h;
+}
+
+/// The `SplitMix64` finaliser, a fast and well distributed 64-bit mixing function.
+#[must_use]
+pub fn splitmix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    return x ^ (x >> 31);
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{de::value, Deserialize};
+
+    #[test]
+    fn from_text() {
+        assert_eq!(Seed::new(42), Seed::from_text("42"));
+        assert_eq!(Seed::new(u64::MAX), Seed::from_text("-1"));
+        assert_eq!(Seed::new(0xAF63_DC4C_8601_EC8C), Seed::from_text("a"));
+        assert_eq!(
+            Seed::from_text("hello world"),
+            Seed::from_text(" hello world ")
+        );
+        assert_ne!(Seed::from_text("hello world"), Seed::from_text("hello"));
+    }
+
+    #[test]
+    fn derive() {
+        let seed = Seed::new(1234);
+        assert_eq!(seed.derive("terrain"), seed.derive("terrain"));
+        assert_ne!(seed.derive("terrain"), seed.derive("rivers"));
+        assert_ne!(seed.derive("terrain"), Seed::new(1235).derive("terrain"));
+    }
+
+    #[test]
+    fn deserialize() {
+        let de = value::StrDeserializer::<value::Error>::new("my world");
+        assert_eq!(Seed::from_text("my world"), Seed::deserialize(de).unwrap());
+
+        let de = value::I64Deserializer::<value::Error>::new(-5);
+        assert_eq!(Seed::from_text("-5"), Seed::deserialize(de).unwrap());
+
+        let de = value::U64Deserializer::<value::Error>::new(7);
+        assert_eq!(Seed::new(7), Seed::deserialize(de).unwrap());
+    }
+}
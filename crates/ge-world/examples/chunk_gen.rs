@@ -1,4 +1,4 @@
-use ge_util::{coords::CHUNK_SIZE, ChunkOffset};
+use ge_util::{coords::CHUNK_SIZE, ChunkOffset, ChunkPos};
 use ge_world::{
     gen::{ChunkGenerator, NoiseChunkGenerator},
     Chunk,
@@ -18,14 +18,8 @@ impl TestRenderer {
         for z in z_range {
             for y in 0..CHUNK_SIZE {
                 for x in 0..CHUNK_SIZE {
-                    if let Some((_, block)) = self
-                        .0
-                        .blocks
-                        .iter()
-                        .find(|(p, _)| p.x() == x && p.y() == y && p.z() == z)
-                    {
-                        print!("{}", block.ty())
-                    }
+                    let pos = ChunkPos::new(x, y, z).unwrap();
+                    print!("{}", self.0.get(pos))
                 }
                 println!();
             }
@@ -0,0 +1,61 @@
+//! Compare generating chunks one block at a time with generating them column by column.
+//!
+//! Run with `cargo run --release --example column_gen`.
+use ge_util::{ChunkOffset, ChunkPos};
+use ge_world::{
+    gen::{ChunkGenerator, NoiseChunkGenerator},
+    noise::Noise,
+    Block,
+};
+use std::time::{Duration, Instant};
+
+const CHUNKS: i32 = 4;
+
+/// Only implements `generate_at`, which is how every chunk used to be generated.
+struct PerBlock(NoiseChunkGenerator);
+
+impl ChunkGenerator for PerBlock {
+    fn generate_at(
+        &self,
+        chunk_pos: impl Into<ChunkPos>,
+        chunk_offset: impl Into<ChunkOffset> + Copy,
+    ) -> Block {
+        self.0.generate_at(chunk_pos, chunk_offset)
+    }
+
+    fn max_height(&self, chunk_offset: ChunkOffset) -> Option<i32> {
+        self.0.max_height(chunk_offset)
+    }
+}
+
+fn time<G: ChunkGenerator>(gen: &G) -> (Duration, Vec<ge_world::Chunk>) {
+    let start = Instant::now();
+    let chunks = (0..CHUNKS)
+        .flat_map(|x| (0..CHUNKS).map(move |y| ChunkOffset::new(x, y, 0).unwrap()))
+        .map(|offset| gen.generate(offset))
+        .collect();
+    (start.elapsed(), chunks)
+}
+
+fn main() {
+    let noise = Noise::new(0, 5, 16.0, 12.0, 2.0, 0.5);
+    let gen = NoiseChunkGenerator::with_noise(noise, 100);
+
+    let (per_block, a) = time(&PerBlock(gen));
+    let (per_column, b) = time(&gen);
+    assert!(a == b, "column generation differs from block generation");
+
+    let n = CHUNKS * CHUNKS;
+    println!(
+        "per block:  {per_block:?} ({:?} per chunk)",
+        per_block / n as u32
+    );
+    println!(
+        "per column: {per_column:?} ({:?} per chunk)",
+        per_column / n as u32
+    );
+    println!(
+        "speedup:    {:.1}x",
+        per_block.as_secs_f64() / per_column.as_secs_f64()
+    );
+}
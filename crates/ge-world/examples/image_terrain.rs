@@ -0,0 +1,43 @@
+use ge_world::{
+    gen::{FixedWorldGenerator, ImageChunkGenerator},
+    map::WorldMap,
+    trns::{SeaLevel, SimpleSurfacePainter},
+};
+
+const ISLAND: &str = "images/island.png";
+const OUT: &str = "images/island_map";
+const SIZE: u32 = 64;
+
+/// Usage: `cargo run --example image_terrain [heightmap.png]`
+///
+/// Without an argument a round island heightmap is generated and used instead.
+fn main() {
+    let path = std::env::args().nth(1).unwrap_or_else(|| {
+        let island = image::ImageBuffer::from_fn(SIZE, SIZE, |x, y| {
+            let (dx, dy) = (x as f32 / SIZE as f32 - 0.5, y as f32 / SIZE as f32 - 0.5);
+            let d = (dx * dx + dy * dy).sqrt() * 2.0;
+            image::Luma([((1.0 - d).clamp(0.0, 1.0) * u16::MAX as f32) as u16])
+        });
+        std::fs::create_dir_all("images").unwrap();
+        island.save(ISLAND).unwrap();
+        ISLAND.to_owned()
+    });
+
+    let config = ge_util::EngineConfig::default();
+    let terrain = ImageChunkGenerator::open(&path)
+        .unwrap()
+        .with_horizontal_scale(2.0)
+        .with_vertical_scale(40.0)
+        .with_base_height(config.world_gen.sea_level - 10);
+    // the island covers 128x128 blocks, so generate 8x8 chunks from the origin
+    let world = FixedWorldGenerator::with_generator(
+        terrain,
+        (4, 4),
+        vec![SeaLevel::new(&config).into(), SimpleSurfacePainter.into()],
+    )
+    .generate_region((0, 0), (8, 8));
+
+    for path in WorldMap::from_world(&world).save(OUT).unwrap() {
+        println!("Saved image to '{}'", path.display());
+    }
+}
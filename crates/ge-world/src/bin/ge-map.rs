@@ -0,0 +1,105 @@
+//! Generate a rectangular region of the world and save top-down maps of it as PNGs.
+//!
+//! ```text
+//! ge-map [--config config/engine.toml] [--seed <seed>] [--from x,y] [--size w,h] [--out dir]
+//! ```
+//!
+//! `--from` and `--size` are measured in chunks.
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+
+use ge_util::{cli, Seed};
+use ge_world::{gen::AsyncWorldGenerator, map::WorldMap};
+use std::path::PathBuf;
+
+#[derive(Debug)]
+struct Args {
+    config: PathBuf,
+    seed: Option<Seed>,
+    from: (i32, i32),
+    size: (i32, i32),
+    out: PathBuf,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut parsed = Args {
+        config: PathBuf::from("config/engine.toml"),
+        seed: None,
+        from: (-8, -8),
+        size: (16, 16),
+        out: PathBuf::from("images/map"),
+    };
+
+    cli::parse_args(std::env::args().skip(1), |key, value| {
+        let pair = || return cli::parse_pair(&value).ok_or_else(|| return format!("invalid {key}"));
+        match key {
+            "--config" => parsed.config = PathBuf::from(&value),
+            "--seed" => parsed.seed = Some(Seed::from_text(&value)),
+            "--from" => parsed.from = pair()?,
+            "--size" => parsed.size = pair()?,
+            "--out" => parsed.out = PathBuf::from(&value),
+            _ => return Err(format!("unknown argument: {key}")),
+        }
+        return Ok(());
+    })?;
+
+    if parsed.size.0 <= 0 || parsed.size.1 <= 0 {
+        return Err("size must be positive".to_owned());
+    }
+    return Ok(parsed);
+}
+
+fn main() {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            eprintln!(
+                "usage: ge-map [--config path] [--seed seed] [--from x,y] [--size w,h] [--out dir]"
+            );
+            std::process::exit(2);
+        }
+    };
+
+    let config = match cli::load_config(&args.config, args.seed) {
+        Ok((config, warning)) => {
+            if let Some(warning) = warning {
+                eprintln!("{warning}");
+            }
+            config
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    let start = std::time::Instant::now();
+    let gen = match AsyncWorldGenerator::from_config(args.size, &config) {
+        Ok(gen) => gen,
+        Err(e) => {
+            eprintln!("invalid world-gen pipeline: {e}");
+            std::process::exit(1);
+        }
+    };
+    let hi = (args.from.0 + args.size.0, args.from.1 + args.size.1);
+    let world = gen.generate_region(args.from, hi);
+    println!(
+        "seed {}: generated {} chunks in {:?}",
+        config.world_gen.seed,
+        world.chunks.len(),
+        start.elapsed()
+    );
+
+    match WorldMap::from_world(&world).save(&args.out) {
+        Ok(paths) => {
+            for path in paths {
+                println!("saved '{}'", path.display());
+            }
+        }
+        Err(e) => {
+            eprintln!("could not save the maps to {}: {e}", args.out.display());
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use crate::{gen::AsyncWorldGenerator, trns::StageError, BlockType, Chunk};
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE, SECTIONS_PER_CHUNK, SECTION_SIZE},
+    seed::fnv1a,
+    ChunkOffset, ChunkPos, EngineConfig, WorldGenConfig,
+};
+use thiserror::Error;
+
+/// The version of the binary delta format.
+pub const DELTA_VERSION: u16 = 1;
+
+/// The version of the terrain generator.
+///
+/// Bump this whenever a change to the generator is meant to change the terrain, so deltas that
+/// were saved against the old terrain are detected.
+pub const GENERATOR_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 4] = b"GEDT";
+const HEADER_LEN: usize = 4 + 2 + 8 + 4 + 4 + 4;
+const ENTRY_LEN: usize = 3;
+
+/// The blocks of a chunk that differ from what the generator produces.
+///
+/// Generated terrain can always be reproduced from the seed, so only the changes made on top of it
+/// need to be saved. A delta is only valid for the generator it was made with, which is checked
+/// with a fingerprint from `generator_fingerprint`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkDelta {
+    pub position: ChunkOffset,
+    blocks: Vec<(ChunkPos, BlockType)>,
+}
+
+impl ChunkDelta {
+    /// Create an empty delta.
+    #[must_use]
+    pub fn new(position: ChunkOffset) -> Self {
+        return Self {
+            position,
+            blocks: Vec::new(),
+        };
+    }
+
+    /// Find every block in `current` that differs from `generated`.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "positions are always inside the chunk"
+    )]
+    #[must_use]
+    pub fn diff(generated: &Chunk, current: &Chunk) -> Self {
+        let mut delta = Self::new(current.position);
+        #[allow(clippy::cast_sign_loss, reason = "constant is positive")]
+        for i in 0..SECTIONS_PER_CHUNK as usize {
+            // sections that are empty in both chunks cannot differ
+            if generated.section(i).is_none() && current.section(i).is_none() {
+                continue;
+            }
+
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            let base_z = i as i32 * SECTION_SIZE;
+            for z in base_z..base_z + SECTION_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let pos = ChunkPos::new(x, y, z).expect("position is inside the chunk");
+                        let ty = current.get(pos);
+                        if generated.get(pos) != ty {
+                            delta.blocks.push((pos, ty));
+                        }
+                    }
+                }
+            }
+        }
+        return delta;
+    }
+
+    /// Apply the changed blocks to a freshly generated chunk.
+    pub fn apply(&self, chunk: &mut Chunk) {
+        for &(pos, ty) in &self.blocks {
+            chunk.set(pos, ty);
+        }
+    }
+
+    /// The changed blocks and their new types.
+    #[must_use]
+    pub fn blocks(&self) -> &[(ChunkPos, BlockType)] {
+        return &self.blocks;
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        return self.blocks.is_empty();
+    }
+
+    /// Encode the delta for a generator with the given fingerprint.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "chunk positions fit in a u16"
+    )]
+    #[must_use]
+    pub fn to_bytes(&self, fingerprint: u64) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.blocks.len() * ENTRY_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&DELTA_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&fingerprint.to_le_bytes());
+        bytes.extend_from_slice(&self.position.x().to_le_bytes());
+        bytes.extend_from_slice(&self.position.y().to_le_bytes());
+        bytes.extend_from_slice(&(self.blocks.len() as u32).to_le_bytes());
+        for &(pos, ty) in &self.blocks {
+            let index = (pos.z() * CHUNK_SIZE + pos.y()) * CHUNK_SIZE + pos.x();
+            bytes.extend_from_slice(&(index as u16).to_le_bytes());
+            bytes.push(ty.id());
+        }
+        return bytes;
+    }
+
+    /// Decode a delta, checking that it was made for a generator with the given fingerprint.
+    ///
+    /// # Errors
+    /// Errors if the data is not a delta, has an unsupported version or was made for a different
+    /// generator.
+    #[allow(clippy::missing_panics_doc, reason = "the header length is checked")]
+    pub fn from_bytes(bytes: &[u8], fingerprint: u64) -> Result<Self, DeltaError> {
+        let saved = Self::fingerprint(bytes)?;
+        if saved != fingerprint {
+            return Err(DeltaError::GeneratorMismatch {
+                saved,
+                current: fingerprint,
+            });
+        }
+
+        let x = i32::from_le_bytes(bytes[14..18].try_into().expect("length is checked"));
+        let y = i32::from_le_bytes(bytes[18..22].try_into().expect("length is checked"));
+        let count = u32::from_le_bytes(bytes[22..26].try_into().expect("length is checked"));
+        let entries = &bytes[HEADER_LEN..];
+        if entries.len() != count as usize * ENTRY_LEN {
+            return Err(DeltaError::Invalid("wrong number of blocks"));
+        }
+
+        let position =
+            ChunkOffset::new(x, y, 0).map_err(|_| return DeltaError::Invalid("offset"))?;
+        let blocks = entries
+            .chunks_exact(ENTRY_LEN)
+            .map(|e| {
+                let index = i32::from(u16::from_le_bytes([e[0], e[1]]));
+                let pos = ChunkPos::new(
+                    index % CHUNK_SIZE,
+                    index / CHUNK_SIZE % CHUNK_SIZE,
+                    index / (CHUNK_SIZE * CHUNK_SIZE),
+                )
+                .map_err(|_| return DeltaError::Invalid("block position"))?;
+                let ty = BlockType::from_id(e[2]).ok_or(DeltaError::Invalid("block type"))?;
+                return Ok::<_, DeltaError>((pos, ty));
+            })
+            .collect::<Result<_, _>>()?;
+
+        return Ok(Self { position, blocks });
+    }
+
+    /// Read the generator fingerprint from an encoded delta without decoding the rest.
+    ///
+    /// # Errors
+    /// Errors if the data is not a delta or has an unsupported version.
+    #[allow(clippy::missing_panics_doc, reason = "the header length is checked")]
+    pub fn fingerprint(bytes: &[u8]) -> Result<u64, DeltaError> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+            return Err(DeltaError::Invalid("not a chunk delta"));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != DELTA_VERSION {
+            return Err(DeltaError::UnsupportedVersion(version));
+        }
+        return Ok(u64::from_le_bytes(
+            bytes[6..14].try_into().expect("length is checked"),
+        ));
+    }
+}
+
+/// Get a fingerprint of the terrain that the generator produces with the given settings.
+///
+/// This covers `GENERATOR_VERSION`, every setting that affects generation and a hash of a
+/// reference chunk, so changes to the generator that change the terrain are noticed even if the
+/// version was not bumped. Settings that only affect rendering are ignored.
+///
+/// # Errors
+/// Errors if the world-gen pipeline cannot be built.
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "the config can always be serialized"
+)]
+pub fn generator_fingerprint(config: &WorldGenConfig) -> Result<u64, StageError> {
+    let defaults = WorldGenConfig::default();
+    let world_gen = WorldGenConfig {
+        render_distance: defaults.render_distance,
+        culling: defaults.culling,
+        cull_border: defaults.cull_border,
+        lod: defaults.lod,
+        ..config.clone()
+    };
+    let config = EngineConfig {
+        world_gen,
+        ..Default::default()
+    };
+
+    let chunk =
+        AsyncWorldGenerator::from_config((1, 1), &config)?.generate_chunk(ChunkOffset::default());
+    let mut bytes = GENERATOR_VERSION.to_le_bytes().to_vec();
+    bytes.extend_from_slice(
+        toml::to_string(&config.world_gen)
+            .expect("config can be serialized")
+            .as_bytes(),
+    );
+    for z in 0..CHUNK_HEIGHT {
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let pos = ChunkPos::new(x, y, z).expect("position is inside the chunk");
+                bytes.push(chunk.get(pos).id());
+            }
+        }
+    }
+    return Ok(fnv1a(&bytes));
+}
+
+#[derive(Debug, Error)]
+pub enum DeltaError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid chunk delta: {0}")]
+    Invalid(&'static str),
+    #[error("chunk delta version {0} is not supported")]
+    UnsupportedVersion(u16),
+    #[error(
+        "chunk delta was saved for generator {saved:016x}, but the current one is {current:016x}"
+    )]
+    GeneratorMismatch { saved: u64, current: u64 },
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ge_util::Seed;
+
+    fn edited() -> (Chunk, Chunk) {
+        let offset = ChunkOffset::new(-3, 2, 0).unwrap();
+        let mut generated = Chunk::new(offset);
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                generated.fill_column(x, y, 0..60, BlockType::Stone);
+            }
+        }
+
+        let mut current = generated.clone();
+        current.set(ChunkPos::new(1, 2, 59).unwrap(), BlockType::Air);
+        current.set(ChunkPos::new(15, 15, 200).unwrap(), BlockType::Wood);
+        current.set(ChunkPos::new(0, 0, 0).unwrap(), BlockType::Dirt);
+        return (generated, current);
+    }
+
+    #[test]
+    fn diff_and_apply() {
+        let (generated, current) = edited();
+        let delta = ChunkDelta::diff(&generated, &current);
+        assert_eq!(3, delta.blocks().len());
+
+        let mut restored = generated.clone();
+        delta.apply(&mut restored);
+        assert_eq!(current, restored);
+        assert!(ChunkDelta::diff(&generated, &generated).is_empty());
+    }
+
+    #[test]
+    fn encode() {
+        let (generated, current) = edited();
+        let delta = ChunkDelta::diff(&generated, &current);
+        let bytes = delta.to_bytes(42);
+        assert_eq!(HEADER_LEN + 3 * ENTRY_LEN, bytes.len());
+        assert_eq!(delta, ChunkDelta::from_bytes(&bytes, 42).unwrap());
+
+        assert!(matches!(
+            ChunkDelta::from_bytes(&bytes, 43),
+            Err(DeltaError::GeneratorMismatch {
+                saved: 42,
+                current: 43
+            })
+        ));
+        assert!(matches!(
+            ChunkDelta::from_bytes(&bytes[..bytes.len() - 1], 42),
+            Err(DeltaError::Invalid(_))
+        ));
+
+        let mut future = bytes.clone();
+        future[4] = 99;
+        assert!(matches!(
+            ChunkDelta::from_bytes(&future, 42),
+            Err(DeltaError::UnsupportedVersion(99))
+        ));
+    }
+
+    #[test]
+    fn fingerprint() {
+        let config = WorldGenConfig::default();
+        let fingerprint = generator_fingerprint(&config).unwrap();
+        assert_eq!(fingerprint, generator_fingerprint(&config).unwrap());
+
+        let view = WorldGenConfig {
+            render_distance: 12,
+            ..config.clone()
+        };
+        assert_eq!(fingerprint, generator_fingerprint(&view).unwrap());
+
+        let seeded = WorldGenConfig {
+            seed: Seed::new(1),
+            ..config
+        };
+        assert_ne!(fingerprint, generator_fingerprint(&seeded).unwrap());
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{BlockType, World};
+use ge_util::WorldPos;
+use std::collections::{HashMap, VecDeque};
+
+/// A change to a single block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockEdit {
+    pub pos: WorldPos,
+    pub before: BlockType,
+    pub after: BlockType,
+}
+
+/// A named group of block edits that are undone and redone as one step.
+///
+/// Each position is only stored once, with the type it had before the transaction started and the
+/// type it had at the end, so bulk operations take no more space than the blocks they touch.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transaction {
+    name: String,
+    edits: Vec<BlockEdit>,
+    index: HashMap<WorldPos, usize>,
+}
+
+impl Transaction {
+    fn new(name: &str) -> Self {
+        return Self {
+            name: name.to_owned(),
+            ..Default::default()
+        };
+    }
+
+    #[must_use]
+    pub fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    /// The edits in the order the positions were first changed.
+    #[must_use]
+    pub fn edits(&self) -> &[BlockEdit] {
+        return &self.edits;
+    }
+
+    fn record(&mut self, pos: WorldPos, before: BlockType, after: BlockType) {
+        if let Some(&i) = self.index.get(&pos) {
+            self.edits[i].after = after;
+        } else {
+            self.index.insert(pos, self.edits.len());
+            self.edits.push(BlockEdit { pos, before, after });
+        }
+    }
+
+    /// Remove edits that ended up changing nothing.
+    fn finish(mut self) -> Self {
+        self.edits.retain(|e| return e.before != e.after);
+        self.index.clear();
+        return self;
+    }
+}
+
+/// Changes blocks in a `World` and records them into a transaction.
+#[derive(Debug)]
+pub struct Editor<'a> {
+    world: &'a mut World,
+    transaction: Transaction,
+}
+
+impl Editor<'_> {
+    /// Set the type of a block.
+    ///
+    /// Returns `false` if the chunk is not loaded, in which case nothing is recorded.
+    pub fn set(&mut self, pos: WorldPos, ty: BlockType) -> bool {
+        let Some(before) = self.world.set(pos, ty) else {
+            return false;
+        };
+        self.transaction.record(pos, before, ty);
+        return true;
+    }
+
+    /// Set every block in the box between two corners (inclusive) to `ty`.
+    ///
+    /// Returns the number of blocks that were in loaded chunks.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "positions between two valid corners are valid"
+    )]
+    pub fn fill(&mut self, a: WorldPos, b: WorldPos, ty: BlockType) -> usize {
+        let mut count = 0;
+        for z in a.z().min(b.z())..=a.z().max(b.z()) {
+            for y in a.y().min(b.y())..=a.y().max(b.y()) {
+                for x in a.x().min(b.x())..=a.x().max(b.x()) {
+                    let pos = WorldPos::new(x, y, z).expect("corners are valid positions");
+                    count += usize::from(self.set(pos, ty));
+                }
+            }
+        }
+        return count;
+    }
+
+    /// Get the type of a block, or `None` if its chunk is not loaded.
+    #[must_use]
+    pub fn get(&self, pos: WorldPos) -> Option<BlockType> {
+        return self.world.get(pos);
+    }
+}
+
+/// A bounded undo and redo history of block edits.
+#[derive(Debug, Clone, Default)]
+pub struct EditHistory {
+    undo: VecDeque<Transaction>,
+    redo: Vec<Transaction>,
+    limit: usize,
+}
+
+impl EditHistory {
+    /// Create a history that remembers at most `limit` transactions.
+    #[must_use]
+    pub fn new(limit: usize) -> Self {
+        return Self {
+            undo: VecDeque::with_capacity(limit),
+            redo: Vec::new(),
+            limit,
+        };
+    }
+
+    /// Run `f` as a single named transaction.
+    ///
+    /// Everything `f` changes is undone in one step. Transactions that change nothing are not
+    /// recorded, and recording a new transaction clears the redo history.
+    pub fn transaction<R>(
+        &mut self,
+        world: &mut World,
+        name: &str,
+        f: impl FnOnce(&mut Editor<'_>) -> R,
+    ) -> R {
+        let mut editor = Editor {
+            world,
+            transaction: Transaction::new(name),
+        };
+        let result = f(&mut editor);
+
+        let transaction = editor.transaction.finish();
+        if !transaction.edits.is_empty() {
+            self.redo.clear();
+            self.undo.push_back(transaction);
+            while self.undo.len() > self.limit {
+                self.undo.pop_front();
+            }
+        }
+        return result;
+    }
+
+    /// Set a single block as its own transaction.
+    pub fn set(&mut self, world: &mut World, pos: WorldPos, ty: BlockType) -> bool {
+        return self.transaction(world, "set block", |e| return e.set(pos, ty));
+    }
+
+    /// Undo the last transaction and return its name.
+    ///
+    /// Blocks in chunks that are no longer loaded are skipped.
+    pub fn undo(&mut self, world: &mut World) -> Option<&str> {
+        let transaction = self.undo.pop_back()?;
+        for edit in transaction.edits.iter().rev() {
+            world.set(edit.pos, edit.before);
+        }
+        self.redo.push(transaction);
+        return self.redo.last().map(|t| return t.name());
+    }
+
+    /// Redo the last undone transaction and return its name.
+    ///
+    /// Blocks in chunks that are no longer loaded are skipped.
+    pub fn redo(&mut self, world: &mut World) -> Option<&str> {
+        let transaction = self.redo.pop()?;
+        for edit in &transaction.edits {
+            world.set(edit.pos, edit.after);
+        }
+        self.undo.push_back(transaction);
+        return self.undo.back().map(|t| return t.name());
+    }
+
+    /// The transactions that can be undone, oldest first.
+    #[must_use]
+    pub fn undo_stack(&self) -> impl DoubleEndedIterator<Item = &Transaction> + '_ {
+        return self.undo.iter();
+    }
+
+    #[must_use]
+    pub fn can_undo(&self) -> bool {
+        return !self.undo.is_empty();
+    }
+
+    #[must_use]
+    pub fn can_redo(&self) -> bool {
+        return !self.redo.is_empty();
+    }
+
+    /// Forget every transaction.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chunk;
+    use ge_util::ChunkOffset;
+
+    fn world() -> World {
+        let chunks = [(0, 0), (1, 0)]
+            .into_iter()
+            .map(|(x, y)| return Chunk::new(ChunkOffset::new(x, y, 0).unwrap()))
+            .collect();
+        return World { chunks };
+    }
+
+    fn pos(x: i32, y: i32, z: i32) -> WorldPos {
+        return WorldPos::new(x, y, z).unwrap();
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut world = world();
+        let mut history = EditHistory::new(10);
+
+        assert!(history.set(&mut world, pos(1, 1, 1), BlockType::Stone));
+        // a fill across two chunks is a single step
+        let filled = history.transaction(&mut world, "fill", |e| {
+            return e.fill(pos(14, 0, 0), pos(17, 1, 1), BlockType::Dirt);
+        });
+        assert_eq!(16, filled);
+        assert_eq!(Some(BlockType::Dirt), world.get(pos(17, 1, 1)));
+        assert_eq!(Some(BlockType::Stone), world.get(pos(1, 1, 1)));
+
+        assert_eq!(Some("fill"), history.undo(&mut world));
+        assert_eq!(Some(BlockType::Air), world.get(pos(17, 1, 1)));
+        assert_eq!(Some(BlockType::Stone), world.get(pos(1, 1, 1)));
+
+        assert_eq!(Some("set block"), history.undo(&mut world));
+        assert_eq!(Some(BlockType::Air), world.get(pos(1, 1, 1)));
+        assert_eq!(None, history.undo(&mut world));
+
+        assert_eq!(Some("set block"), history.redo(&mut world));
+        assert_eq!(Some("fill"), history.redo(&mut world));
+        assert_eq!(Some(BlockType::Dirt), world.get(pos(14, 0, 0)));
+        assert!(!history.can_redo());
+
+        // a new edit clears the redo history
+        history.undo(&mut world);
+        history.set(&mut world, pos(2, 2, 2), BlockType::Wood);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn merges_and_skips() {
+        let mut world = world();
+        let mut history = EditHistory::new(10);
+
+        history.transaction(&mut world, "paint", |e| {
+            e.set(pos(3, 3, 3), BlockType::Stone);
+            e.set(pos(3, 3, 3), BlockType::Grass);
+            // chunks that are not loaded are ignored
+            assert!(!e.set(pos(-1, 0, 0), BlockType::Stone));
+        });
+        let edits = history.undo_stack().last().unwrap().edits().to_vec();
+        assert_eq!(
+            vec![BlockEdit {
+                pos: pos(3, 3, 3),
+                before: BlockType::Air,
+                after: BlockType::Grass
+            }],
+            edits
+        );
+
+        // edits that are reverted within the transaction are not recorded
+        history.transaction(&mut world, "noop", |e| {
+            e.set(pos(4, 4, 4), BlockType::Stone);
+            e.set(pos(4, 4, 4), BlockType::Air);
+        });
+        assert_eq!(1, history.undo_stack().count());
+    }
+
+    #[test]
+    fn bounded() {
+        let mut world = world();
+        let mut history = EditHistory::new(3);
+        for x in 0..5 {
+            history.set(&mut world, pos(x, 0, 0), BlockType::Stone);
+        }
+        assert_eq!(3, history.undo_stack().count());
+
+        while history.undo(&mut world).is_some() {}
+        // the first two edits fell out of the history
+        assert_eq!(Some(BlockType::Stone), world.get(pos(1, 0, 0)));
+        assert_eq!(Some(BlockType::Air), world.get(pos(2, 0, 0)));
+    }
+}
@@ -0,0 +1,610 @@
+use crate::{
+    gen::{chunk_columns, max_column_height, ChunkGenerator, TerrainGenerator},
+    heightmap::Heightmap,
+    seed::hash_coords,
+    Block, BlockType,
+};
+use ge_util::{coords::CHUNK_SIZE, ChunkOffset, ChunkPos, ErosionConfig};
+use rand::{Rng, SeedableRng};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+/// The distance between the origins of neighbouring erosion tiles.
+///
+/// Each tile is twice this size, so every column is covered by exactly four tiles.
+pub const TILE_STRIDE: i32 = 64;
+
+/// The number of tiles a `TileCache` keeps, which is about 16 MiB of eroded heights.
+const CACHED_TILES: usize = 256;
+
+/// Particle-based hydraulic erosion with an optional thermal erosion pass.
+///
+/// The world is split into half-overlapping tiles that are aligned to the world grid. Each tile
+/// is eroded on its own with droplets seeded from the tile position, and the overlapping results
+/// are blended with tent weights that fall to zero at the tile edges. The eroded height of a
+/// column therefore only depends on its world position, which means neighbouring regions tile
+/// without seams and the same region always erodes the same way.
+#[derive(Debug, Clone, Copy)]
+pub struct Erosion {
+    config: ErosionConfig,
+    seed: u64,
+}
+
+impl Erosion {
+    #[must_use]
+    pub fn new(config: ErosionConfig, seed: u64) -> Self {
+        return Self { config, seed };
+    }
+
+    /// Sample the heights of a rectangular region of chunks using `f` and erode them.
+    ///
+    /// `lo` is the first chunk offset and `count` is the number of chunks along each axis.
+    #[must_use]
+    pub fn erode_region(
+        &self,
+        lo: (i32, i32),
+        count: (i32, i32),
+        f: impl Fn(i32, i32) -> f32 + Sync,
+    ) -> Heightmap {
+        return blend(lo, count, |tx, ty| {
+            return Arc::new(self.erode_tile(tx, ty, &f));
+        });
+    }
+
+    /// Like `erode_region`, but take the eroded tiles from `cache` and add the missing ones to it.
+    ///
+    /// The cache must only ever be used with this erosion and the same `f`.
+    #[must_use]
+    pub fn erode_region_cached(
+        &self,
+        lo: (i32, i32),
+        count: (i32, i32),
+        cache: &TileCache,
+        f: impl Fn(i32, i32) -> f32 + Sync,
+    ) -> Heightmap {
+        return blend(lo, count, |tx, ty| {
+            return cache.get((tx, ty), || return self.erode_tile(tx, ty, &f));
+        });
+    }
+
+    /// Get the eroded height of a single column, taking the tiles from `cache` like
+    /// `erode_region_cached`.
+    ///
+    /// The tiles are blended in the same order as in a region, so the height is exactly the same.
+    pub fn height_at(&self, x: i32, y: i32, cache: &TileCache, f: impl Fn(i32, i32) -> f32) -> f32 {
+        let (tx, ty) = (x.div_euclid(TILE_STRIDE), y.div_euclid(TILE_STRIDE));
+        let mut height = 0.0;
+        for tx in tx - 1..=tx {
+            for ty in ty - 1..=ty {
+                let tile = cache.get((tx, ty), || return self.erode_tile(tx, ty, &f));
+                let weight = tent(x - tx * TILE_STRIDE) * tent(y - ty * TILE_STRIDE);
+                height += weight * tile.get_world(x, y).unwrap_or_default();
+            }
+        }
+        return height;
+    }
+
+    /// Sample and erode a single tile.
+    fn erode_tile(&self, tx: i32, ty: i32, f: impl Fn(i32, i32) -> f32) -> Heightmap {
+        #[allow(clippy::cast_sign_loss, reason = "tile stride is positive")]
+        let size = 2 * TILE_STRIDE as usize;
+        let mut map = Heightmap::from_fn((tx * TILE_STRIDE, ty * TILE_STRIDE), size, size, f);
+        self.hydraulic(&mut map, hash_coords(self.seed, tx, ty));
+        self.thermal(&mut map);
+        return map;
+    }
+
+    /// Erode the heightmap in place, using the heightmap origin to seed the droplets.
+    pub fn erode(&self, map: &mut Heightmap) {
+        let (ox, oy) = map.origin();
+        self.hydraulic(map, hash_coords(self.seed, ox, oy));
+        self.thermal(map);
+    }
+
+    /// Run hydraulic erosion over the heightmap.
+    ///
+    /// The number of droplets is `iterations` for every chunk column worth of area.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "values should be small and positive"
+    )]
+    pub fn hydraulic(&self, map: &mut Heightmap, seed: u64) {
+        if map.width() < 2 || map.height() < 2 {
+            return;
+        }
+
+        let area = (map.width() * map.height()) as f32;
+        let chunk_area = (CHUNK_SIZE * CHUNK_SIZE) as f32;
+        let droplets = (area / chunk_area * self.config.iterations as f32).ceil() as u32;
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(seed);
+        for _ in 0..droplets {
+            let start = (
+                rng.gen::<f32>() * (map.width() - 1) as f32,
+                rng.gen::<f32>() * (map.height() - 1) as f32,
+            );
+            self.droplet(map, start);
+        }
+    }
+
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        reason = "positions are bounds checked before they are used"
+    )]
+    fn droplet(&self, map: &mut Heightmap, start: (f32, f32)) {
+        let cfg = &self.config;
+        let max_x = (map.width() - 1) as f32;
+        let max_y = (map.height() - 1) as f32;
+
+        let (mut x, mut y) = start;
+        let (mut dx, mut dy) = (0.0f32, 0.0f32);
+        let mut speed = 1.0f32;
+        let mut water = 1.0f32;
+        let mut sediment = 0.0f32;
+
+        for _ in 0..cfg.max_lifetime {
+            let (height, gx, gy) = height_and_gradient(map, x, y);
+
+            // blend the previous direction with the downhill direction
+            dx = dx * cfg.inertia - gx * (1.0 - cfg.inertia);
+            dy = dy * cfg.inertia - gy * (1.0 - cfg.inertia);
+            let len = dx.hypot(dy);
+            if len <= f32::EPSILON {
+                break;
+            }
+            dx /= len;
+            dy /= len;
+
+            let (old_x, old_y) = (x, y);
+            x += dx;
+            y += dy;
+            if x < 0.0 || y < 0.0 || x >= max_x || y >= max_y {
+                break;
+            }
+
+            let delta = height_and_gradient(map, x, y).0 - height;
+            let capacity = (-delta * speed * water * cfg.capacity).max(cfg.min_capacity);
+
+            if sediment > capacity || delta > 0.0 {
+                // fill the pit when moving uphill, otherwise drop the excess sediment
+                let amount = if delta > 0.0 {
+                    delta.min(sediment)
+                } else {
+                    (sediment - capacity) * cfg.deposition
+                };
+                sediment -= amount;
+                deposit(map, old_x, old_y, amount);
+            } else {
+                // never erode more than the height difference to avoid digging holes
+                let amount = ((capacity - sediment) * cfg.erosion).min(-delta);
+                sediment += self.erode_brush(map, old_x, old_y, amount);
+            }
+
+            speed = (speed * speed - delta * cfg.gravity).max(0.0).sqrt();
+            water *= 1.0 - cfg.evaporation;
+        }
+    }
+
+    /// Remove `amount` of material around the position using a radial brush.
+    ///
+    /// Returns the amount that was actually removed.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::cast_sign_loss,
+        reason = "positions are bounds checked before they are used"
+    )]
+    fn erode_brush(&self, map: &mut Heightmap, x: f32, y: f32, amount: f32) -> f32 {
+        let radius = self.config.radius.max(1) as i32;
+        let (cx, cy) = (x.floor() as i32, y.floor() as i32);
+        let (w, h) = (map.width() as i32, map.height() as i32);
+
+        let mut cells = Vec::with_capacity((radius * 2 + 1).pow(2) as usize);
+        let mut total = 0.0;
+        for by in cy - radius + 1..=cy + radius {
+            for bx in cx - radius + 1..=cx + radius {
+                if bx < 0 || by < 0 || bx >= w || by >= h {
+                    continue;
+                }
+                let dist = (bx as f32 - x).hypot(by as f32 - y);
+                let weight = radius as f32 - dist;
+                if weight > 0.0 {
+                    cells.push((bx as usize, by as usize, weight));
+                    total += weight;
+                }
+            }
+        }
+
+        let mut removed = 0.0;
+        for (bx, by, weight) in cells {
+            let cell = map.get_mut(bx, by);
+            let delta = amount * weight / total;
+            *cell -= delta;
+            removed += delta;
+        }
+        return removed;
+    }
+
+    /// Run thermal erosion, sliding material down slopes that are steeper than the talus.
+    pub fn thermal(&self, map: &mut Heightmap) {
+        const NEIGHBOURS: [(isize, isize); 8] = [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ];
+
+        let (w, h) = (map.width(), map.height());
+        let mut delta = vec![0.0f32; w * h];
+        for _ in 0..self.config.thermal_iterations {
+            delta.fill(0.0);
+            for y in 0..h {
+                for x in 0..w {
+                    let height = map.get(x, y);
+                    for (nx, ny) in NEIGHBOURS {
+                        let (Some(nx), Some(ny)) =
+                            (x.checked_add_signed(nx), y.checked_add_signed(ny))
+                        else {
+                            continue;
+                        };
+                        if nx >= w || ny >= h {
+                            continue;
+                        }
+
+                        let diff = height - map.get(nx, ny);
+                        if diff > self.config.talus {
+                            let amount =
+                                self.config.thermal_rate * (diff - self.config.talus) / 8.0;
+                            delta[y * w + x] -= amount;
+                            delta[ny * w + nx] += amount;
+                        }
+                    }
+                }
+            }
+
+            for y in 0..h {
+                for x in 0..w {
+                    *map.get_mut(x, y) += delta[y * w + x];
+                }
+            }
+        }
+    }
+}
+
+/// Bilinearly interpolate the height and gradient of the heightmap at a position.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "positions are bounds checked before they are used"
+)]
+fn height_and_gradient(map: &Heightmap, x: f32, y: f32) -> (f32, f32, f32) {
+    let (ix, iy) = (x.floor() as usize, y.floor() as usize);
+    let (u, v) = (x - ix as f32, y - iy as f32);
+
+    let nw = map.get(ix, iy);
+    let ne = map.get(ix + 1, iy);
+    let sw = map.get(ix, iy + 1);
+    let se = map.get(ix + 1, iy + 1);
+
+    let gx = (ne - nw) * (1.0 - v) + (se - sw) * v;
+    let gy = (sw - nw) * (1.0 - u) + (se - ne) * u;
+    let height = nw * (1.0 - u) * (1.0 - v) + ne * u * (1.0 - v) + sw * (1.0 - u) * v + se * u * v;
+
+    return (height, gx, gy);
+}
+
+/// Bilinearly deposit `amount` of sediment onto the four cells around a position.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    reason = "positions are bounds checked before they are used"
+)]
+fn deposit(map: &mut Heightmap, x: f32, y: f32, amount: f32) {
+    let (ix, iy) = (x.floor() as usize, y.floor() as usize);
+    let (u, v) = (x - ix as f32, y - iy as f32);
+
+    *map.get_mut(ix, iy) += amount * (1.0 - u) * (1.0 - v);
+    *map.get_mut(ix + 1, iy) += amount * u * (1.0 - v);
+    *map.get_mut(ix, iy + 1) += amount * (1.0 - u) * v;
+    *map.get_mut(ix + 1, iy + 1) += amount * u * v;
+}
+
+/// Blend the eroded tiles returned by `tile` that overlap a region of chunks.
+fn blend(
+    lo: (i32, i32),
+    count: (i32, i32),
+    tile: impl Fn(i32, i32) -> Arc<Heightmap> + Sync,
+) -> Heightmap {
+    let mut out = Heightmap::from_chunks(lo, count, |_, _| return 0.0);
+    let (ox, oy) = out.origin();
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let (w, h) = (out.width() as i32, out.height() as i32);
+    if w == 0 || h == 0 {
+        return out;
+    }
+
+    // every tile that overlaps the region, tiles start one stride before the first column
+    let tiles = (ox.div_euclid(TILE_STRIDE) - 1..=(ox + w - 1).div_euclid(TILE_STRIDE))
+        .flat_map(|tx| {
+            return (oy.div_euclid(TILE_STRIDE) - 1..=(oy + h - 1).div_euclid(TILE_STRIDE))
+                .map(move |ty| return (tx, ty));
+        })
+        .collect::<Vec<_>>();
+
+    let eroded = tiles
+        .par_iter()
+        .map(|&(tx, ty)| return tile(tx, ty))
+        .collect::<Vec<_>>();
+
+    // blend the tiles in a fixed order so the sums are identical for every region
+    for tile in &eroded {
+        let (tx0, ty0) = tile.origin();
+        for y in oy.max(ty0)..(oy + h).min(ty0 + 2 * TILE_STRIDE) {
+            for x in ox.max(tx0)..(ox + w).min(tx0 + 2 * TILE_STRIDE) {
+                let weight = tent(x - tx0) * tent(y - ty0);
+                #[allow(clippy::cast_sign_loss, reason = "position is within the region")]
+                let cell = out.get_mut((x - ox) as usize, (y - oy) as usize);
+                *cell += weight * tile.get_world(x, y).unwrap_or_default();
+            }
+        }
+    }
+
+    return out;
+}
+
+/// Eroded tiles by tile coordinate, so chunks that are generated one at a time share them.
+///
+/// Other world-space grids are cached the same way, like the paths of rivers by source cell.
+/// Once the cache is full the oldest tiles are dropped.
+#[derive(Debug)]
+pub struct TileCache<T = Heightmap> {
+    tiles: Mutex<CachedTiles<T>>,
+}
+
+impl<T> Default for TileCache<T> {
+    fn default() -> Self {
+        return Self {
+            tiles: Mutex::new(CachedTiles {
+                tiles: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        };
+    }
+}
+
+#[derive(Debug)]
+struct CachedTiles<T> {
+    tiles: HashMap<(i32, i32), Arc<OnceLock<Arc<T>>>>,
+    /// The tiles in the order they were added.
+    order: VecDeque<(i32, i32)>,
+}
+
+impl<T> TileCache<T> {
+    /// Get a tile, building it with `erode` if it is not cached yet.
+    ///
+    /// Threads that ask for the same tile at the same time wait for it to be built once.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "the lock is only poisoned if a tile panicked, which would panic here as well"
+    )]
+    pub fn get(&self, tile: (i32, i32), erode: impl FnOnce() -> T) -> Arc<T> {
+        let slot = {
+            let mut cached = self
+                .tiles
+                .lock()
+                .expect("another user of the cache panicked");
+            let cached = &mut *cached;
+            let slot = cached.tiles.entry(tile).or_insert_with(|| {
+                cached.order.push_back(tile);
+                return Arc::default();
+            });
+            let slot = Arc::clone(slot);
+            if cached.order.len() > CACHED_TILES {
+                let oldest = cached.order.pop_front().expect("the cache is not empty");
+                cached.tiles.remove(&oldest);
+            }
+            slot
+        };
+        return Arc::clone(slot.get_or_init(|| return Arc::new(erode())));
+    }
+
+    /// The number of tiles in the cache.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "the lock is only poisoned if a tile panicked"
+    )]
+    #[must_use]
+    pub fn len(&self) -> usize {
+        return self
+            .tiles
+            .lock()
+            .expect("another user of the cache panicked")
+            .tiles
+            .len();
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+}
+
+/// The surface of a terrain after erosion, for stages that look at single columns.
+///
+/// Columns come from the same tiles as the eroded chunks, so they match the chunks exactly.
+#[derive(Debug, Clone)]
+pub struct ErodedTerrain<G> {
+    gen: G,
+    erosion: Erosion,
+    tiles: Arc<TileCache>,
+}
+
+impl<G: TerrainGenerator> ErodedTerrain<G> {
+    /// Erode the surface of `gen`, sharing the eroded tiles in `tiles`.
+    #[must_use]
+    pub fn new(gen: G, erosion: Erosion, tiles: Arc<TileCache>) -> Self {
+        return Self {
+            gen,
+            erosion,
+            tiles,
+        };
+    }
+}
+
+impl<G: TerrainGenerator> TerrainGenerator for ErodedTerrain<G> {
+    fn surface_height(&self, x: i32, y: i32) -> f32 {
+        return self.erosion.height_at(x, y, &self.tiles, |x, y| {
+            return self.gen.surface_height(x, y);
+        });
+    }
+}
+
+impl<G: TerrainGenerator> ChunkGenerator for ErodedTerrain<G> {
+    fn generate_at(
+        &self,
+        chunk_pos: impl Into<ChunkPos>,
+        chunk_offset: impl Into<ChunkOffset> + Copy,
+    ) -> Block {
+        let chunk_pos: ChunkPos = chunk_pos.into();
+        let world_pos = chunk_pos.to_world_pos(chunk_offset);
+
+        #[allow(clippy::cast_possible_truncation, reason = "truncation is expected")]
+        let surface_z = self.surface_height(world_pos.x(), world_pos.y()) as i32;
+        let ty = match surface_z {
+            z if chunk_pos.z() > z => BlockType::Air,
+            _ => BlockType::Stone,
+        };
+
+        return Block::new(ty, chunk_pos, chunk_offset);
+    }
+
+    fn max_height(&self, chunk_offset: ChunkOffset) -> Option<i32> {
+        return max_column_height(chunk_offset, |x, y| return self.surface_height(x, y));
+    }
+
+    fn column_heights(&self, chunk_offset: ChunkOffset) -> Option<Vec<i32>> {
+        return Some(chunk_columns(chunk_offset, |x, y| {
+            return self.surface_height(x, y);
+        }));
+    }
+}
+
+/// The blend weight of a column `d` blocks from the start of a tile.
+///
+/// The weights of the two tiles covering a column along an axis always add up to `1.0`.
+#[allow(clippy::cast_precision_loss, reason = "values should be small")]
+fn tent(d: i32) -> f32 {
+    let t = d as f32 / TILE_STRIDE as f32;
+    return if t < 1.0 { t } else { 2.0 - t };
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::noise::Noise;
+
+    fn config() -> ErosionConfig {
+        return ErosionConfig {
+            enabled: true,
+            iterations: 32,
+            thermal_iterations: 4,
+            ..Default::default()
+        };
+    }
+
+    fn terrain(noise: &Noise) -> impl Fn(i32, i32) -> f32 + '_ {
+        return |x, y| return 100.0 + noise.fbm(x as f32, y as f32, 0.0);
+    }
+
+    #[test]
+    fn deterministic() {
+        let noise = Noise::new(0, 5, 16.0, 12.0, 2.0, 0.5);
+        let erosion = Erosion::new(config(), 42);
+
+        let a = erosion.erode_region((0, 0), (2, 2), terrain(&noise));
+        let b = erosion.erode_region((0, 0), (2, 2), terrain(&noise));
+        assert_eq!(a, b);
+
+        let c = Erosion::new(config(), 43).erode_region((0, 0), (2, 2), terrain(&noise));
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn changes_terrain() {
+        let noise = Noise::new(0, 5, 16.0, 12.0, 2.0, 0.5);
+        let erosion = Erosion::new(config(), 42);
+
+        let original = Heightmap::from_chunks((0, 0), (2, 2), terrain(&noise));
+        let eroded = erosion.erode_region((0, 0), (2, 2), terrain(&noise));
+        assert_ne!(original, eroded);
+
+        // erosion moves material around, it should not create or destroy much of it
+        let sum = |hm: &Heightmap| return hm.data().iter().map(|&h| return h as f64).sum::<f64>();
+        let avg_diff = (sum(&original) - sum(&eroded)).abs() / original.data().len() as f64;
+        assert!(avg_diff < 0.5, "average height changed by {avg_diff}");
+    }
+
+    #[test]
+    fn tiles_without_seams() {
+        let noise = Noise::new(7, 5, 16.0, 12.0, 2.0, 0.5);
+        let erosion = Erosion::new(config(), 7);
+
+        // both regions contain chunk (1, 0), which must be eroded the same way in both
+        let a = erosion.erode_region((0, 0), (2, 1), terrain(&noise));
+        let b = erosion.erode_region((1, -1), (3, 2), terrain(&noise));
+
+        for y in 0..CHUNK_SIZE {
+            for x in CHUNK_SIZE..CHUNK_SIZE * 2 {
+                assert_eq!(a.get_world(x, y), b.get_world(x, y));
+            }
+        }
+    }
+
+    #[test]
+    fn cached_tiles() {
+        let noise = Noise::new(7, 5, 16.0, 12.0, 2.0, 0.5);
+        let erosion = Erosion::new(config(), 7);
+        let cache = TileCache::default();
+
+        let region = erosion.erode_region((0, 0), (2, 1), terrain(&noise));
+        let a = erosion.erode_region_cached((0, 0), (1, 1), &cache, terrain(&noise));
+        // the chunk next to it lies in the same four tiles
+        assert_eq!(4, cache.len());
+        let b = erosion.erode_region_cached((1, 0), (1, 1), &cache, terrain(&noise));
+        assert_eq!(4, cache.len());
+
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE * 2 {
+                let cached = a.get_world(x, y).or(b.get_world(x, y));
+                assert_eq!(region.get_world(x, y), cached);
+            }
+        }
+
+        // single columns are blended from the same tiles
+        for (x, y) in [(0, 0), (5, 9), (31, 15)] {
+            let column = erosion.height_at(x, y, &cache, terrain(&noise));
+            assert_eq!(region.get_world(x, y), Some(column));
+        }
+
+        // old tiles are dropped once the cache is full
+        for tx in 0..CACHED_TILES as i32 {
+            cache.get((tx, 100), || return Heightmap::new((0, 0), 1, 1));
+        }
+        assert_eq!(CACHED_TILES, cache.len());
+    }
+}
@@ -1,9 +1,20 @@
-use crate::{noise::Noise, trns::Transformation, Block, Chunk, ChunkTransformation, World};
+use crate::{
+    erosion::{ErodedTerrain, Erosion, TileCache},
+    heightmap::Heightmap,
+    noise::Noise,
+    trns::{StageError, Transformation, TransformationRegistry},
+    Block, BlockType, Chunk, World,
+};
 use ge_util::{
     coords::{CHUNK_HEIGHT, CHUNK_SIZE},
     ChunkOffset, ChunkPos, EngineConfig,
 };
 use rayon::prelude::{ParallelBridge, ParallelIterator};
+use std::{fmt, ops::Deref, sync::Arc};
+
+mod heightmap_image;
+
+pub use heightmap_image::{EdgeMode, ImageChunkGenerator};
 
 /// A `WorldGenerator` is a trait that generates a `World`.
 pub trait WorldGenerator {
@@ -11,11 +22,10 @@ pub trait WorldGenerator {
 }
 
 #[derive(Debug, Clone)]
-pub struct AsyncWorldGenerator {
-    gen: NoiseChunkGenerator,
+pub struct AsyncWorldGenerator<G = NoiseChunkGenerator> {
+    pipeline: ChunkPipeline<G>,
     pub count: (i32, i32),
     pub center: (i32, i32),
-    trns: Vec<Transformation>,
 }
 
 impl AsyncWorldGenerator {
@@ -27,47 +37,104 @@ impl AsyncWorldGenerator {
         config: &EngineConfig,
     ) -> Self {
         let gen = NoiseChunkGenerator::with_noise(noise, config.world_gen.base_height);
-        let center = (0, 0);
+        return Self::with_generator(gen, count, trns);
+    }
+
+    /// Create a generator with the terrain, transformations and erosion described by `config`.
+    ///
+    /// # Errors
+    /// Errors if a stage of the pipeline cannot be built.
+    pub fn from_config(count: (i32, i32), config: &EngineConfig) -> Result<Self, StageError> {
+        return Self::with_registry(count, config, &TransformationRegistry::default());
+    }
+
+    /// Like `from_config`, but build the stages of the pipeline from `registry`.
+    ///
+    /// # Errors
+    /// Errors if a stage of the pipeline cannot be built.
+    pub fn with_registry(
+        count: (i32, i32),
+        config: &EngineConfig,
+        registry: &TransformationRegistry,
+    ) -> Result<Self, StageError> {
+        let noise = Noise::from(config);
+        let gen = NoiseChunkGenerator::with_noise(noise, config.world_gen.base_height);
+        return Self::with_stages(gen, count, config, registry);
+    }
+}
+
+impl<G: TerrainGenerator + Clone + Send + Sync + 'static> AsyncWorldGenerator<G> {
+    /// Like `with_registry`, but use `gen` for the terrain instead of noise.
+    ///
+    /// # Errors
+    /// Errors if a stage of the pipeline cannot be built.
+    pub fn with_stages(
+        gen: G,
+        count: (i32, i32),
+        config: &EngineConfig,
+        registry: &TransformationRegistry,
+    ) -> Result<Self, StageError> {
+        return Ok(Self {
+            pipeline: ChunkPipeline::from_registry(gen, config, registry)?,
+            count,
+            center: (0, 0),
+        });
+    }
+}
+
+impl<G: TerrainGenerator + Sync> AsyncWorldGenerator<G> {
+    /// Create a generator that uses `gen` for the terrain instead of noise.
+    #[must_use]
+    pub fn with_generator(gen: G, count: (i32, i32), trns: Vec<Transformation>) -> Self {
         return Self {
-            gen,
+            pipeline: ChunkPipeline::new(gen, trns),
             count,
-            center,
-            trns,
+            center: (0, 0),
         };
     }
-}
 
-impl WorldGenerator for AsyncWorldGenerator {
-    fn generate(&self) -> World {
-        let lo = (1 - self.count.0, 1 - self.count.1, 0);
-        let hi = (self.count.0, self.count.1, 0);
-
-        let chunks = (lo.0..hi.0)
-            .flat_map(|x| {
-                return (lo.1..hi.1).map(move |y| {
-                    return ChunkOffset::new(x + self.center.0, y + self.center.1, 0).unwrap();
-                });
-            })
+    /// Erode the terrain of the whole region before the chunks are filled in.
+    #[must_use]
+    pub fn with_erosion(mut self, erosion: Erosion) -> Self {
+        self.pipeline = self.pipeline.with_erosion(erosion);
+        return self;
+    }
+
+    /// Generate every chunk in the region `lo..hi` of chunk offsets.
+    #[must_use]
+    pub fn generate_region(&self, lo: (i32, i32), hi: (i32, i32)) -> World {
+        let heightmap = self.pipeline.heightmap(lo, hi);
+        let chunks = region(lo, hi)
             .par_bridge()
-            .map(|o| {
-                let mut chunk = self.gen.generate(o);
-                for trns in &self.trns {
-                    trns.transform(&mut chunk);
-                }
-                return chunk;
-            })
+            .map(|o| return self.pipeline.fill_chunk(heightmap.as_ref(), o))
             .collect::<Vec<_>>();
-
         return World { chunks };
     }
+
+    /// Generate a single chunk.
+    ///
+    /// Erosion is deterministic regardless of the region that is eroded, so chunks generated one at
+    /// a time match the chunks from `generate_region`.
+    #[must_use]
+    pub fn generate_chunk(&self, offset: ChunkOffset) -> Chunk {
+        let (x, y) = (offset.x(), offset.y());
+        let heightmap = self.pipeline.heightmap((x, y), (x + 1, y + 1));
+        return self.pipeline.fill_chunk(heightmap.as_ref(), offset);
+    }
+}
+
+impl<G: TerrainGenerator + Sync> WorldGenerator for AsyncWorldGenerator<G> {
+    fn generate(&self) -> World {
+        let (lo, hi) = bounds(self.count, self.center);
+        return self.generate_region(lo, hi);
+    }
 }
 
 #[derive(Debug, Clone)]
-pub struct FixedWorldGenerator {
-    gen: NoiseChunkGenerator,
+pub struct FixedWorldGenerator<G = NoiseChunkGenerator> {
+    pipeline: ChunkPipeline<G>,
     pub count: (i32, i32),
     pub center: (i32, i32),
-    trns: Vec<Transformation>,
 }
 
 impl FixedWorldGenerator {
@@ -79,40 +146,175 @@ impl FixedWorldGenerator {
         config: &EngineConfig,
     ) -> Self {
         let gen = NoiseChunkGenerator::with_noise(noise, config.world_gen.base_height);
-        let center = (0, 0);
+        return Self::with_generator(gen, count, trns);
+    }
+
+    /// Create a generator with the terrain, transformations and erosion described by `config`.
+    ///
+    /// # Errors
+    /// Errors if a stage of the pipeline cannot be built.
+    pub fn from_config(count: (i32, i32), config: &EngineConfig) -> Result<Self, StageError> {
+        return Self::with_registry(count, config, &TransformationRegistry::default());
+    }
+
+    /// Like `from_config`, but build the stages of the pipeline from `registry`.
+    ///
+    /// # Errors
+    /// Errors if a stage of the pipeline cannot be built.
+    pub fn with_registry(
+        count: (i32, i32),
+        config: &EngineConfig,
+        registry: &TransformationRegistry,
+    ) -> Result<Self, StageError> {
+        let noise = Noise::from(config);
+        let gen = NoiseChunkGenerator::with_noise(noise, config.world_gen.base_height);
+        return Self::with_stages(gen, count, config, registry);
+    }
+}
+
+impl<G: TerrainGenerator + Clone + Send + Sync + 'static> FixedWorldGenerator<G> {
+    /// Like `with_registry`, but use `gen` for the terrain instead of noise.
+    ///
+    /// # Errors
+    /// Errors if a stage of the pipeline cannot be built.
+    pub fn with_stages(
+        gen: G,
+        count: (i32, i32),
+        config: &EngineConfig,
+        registry: &TransformationRegistry,
+    ) -> Result<Self, StageError> {
+        return Ok(Self {
+            pipeline: ChunkPipeline::from_registry(gen, config, registry)?,
+            count,
+            center: (0, 0),
+        });
+    }
+}
+
+impl<G: TerrainGenerator + Sync> FixedWorldGenerator<G> {
+    /// Create a generator that uses `gen` for the terrain instead of noise.
+    #[must_use]
+    pub fn with_generator(gen: G, count: (i32, i32), trns: Vec<Transformation>) -> Self {
         return Self {
-            gen,
+            pipeline: ChunkPipeline::new(gen, trns),
             count,
-            center,
-            trns,
+            center: (0, 0),
         };
     }
+
+    /// Erode the terrain of the whole region before the chunks are filled in.
+    #[must_use]
+    pub fn with_erosion(mut self, erosion: Erosion) -> Self {
+        self.pipeline = self.pipeline.with_erosion(erosion);
+        return self;
+    }
+
+    /// Generate every chunk in the region `lo..hi` of chunk offsets.
+    #[must_use]
+    pub fn generate_region(&self, lo: (i32, i32), hi: (i32, i32)) -> World {
+        let heightmap = self.pipeline.heightmap(lo, hi);
+        let chunks = region(lo, hi)
+            .map(|o| return self.pipeline.fill_chunk(heightmap.as_ref(), o))
+            .collect::<Vec<_>>();
+        return World { chunks };
+    }
 }
 
-impl WorldGenerator for FixedWorldGenerator {
+impl<G: TerrainGenerator + Sync> WorldGenerator for FixedWorldGenerator<G> {
     fn generate(&self) -> World {
-        let lo = (1 - self.count.0, 1 - self.count.1, 0);
-        let hi = (self.count.0, self.count.1, 0);
-
-        let chunks = (lo.0..hi.0)
-            .flat_map(|x| {
-                return (lo.1..hi.1).map(move |y| {
-                    return ChunkOffset::new(x + self.center.0, y + self.center.1, 0).unwrap();
-                });
-            })
-            .map(|o| {
-                let mut chunk = self.gen.generate(o);
-                for trns in &self.trns {
-                    trns.transform(&mut chunk);
-                }
-                return chunk;
-            })
-            .collect::<Vec<_>>();
+        let (lo, hi) = bounds(self.count, self.center);
+        return self.generate_region(lo, hi);
+    }
+}
 
-        return World { chunks };
+/// The terrain, transformations and erosion that both world generators fill chunks with.
+#[derive(Debug, Clone)]
+struct ChunkPipeline<G> {
+    gen: G,
+    trns: Vec<Transformation>,
+    erosion: Option<Erosion>,
+    /// The eroded tiles, shared by the clones of the generator.
+    tiles: Arc<TileCache>,
+}
+
+impl<G: TerrainGenerator + Clone + Send + Sync + 'static> ChunkPipeline<G> {
+    /// Build the stages and erosion of `config` on top of `gen`.
+    ///
+    /// The stages see the eroded surface, so rivers and lakes follow the terrain they end up in.
+    fn from_registry(
+        gen: G,
+        config: &EngineConfig,
+        registry: &TransformationRegistry,
+    ) -> Result<Self, StageError> {
+        let mut pipeline = Self::new(gen.clone(), Vec::new());
+        if config.world_gen.erosion.enabled {
+            let seed = config.world_gen.seed.derive("erosion").value();
+            pipeline = pipeline.with_erosion(Erosion::new(config.world_gen.erosion, seed));
+        }
+        let terrain = match pipeline.erosion {
+            Some(erosion) => ErodedTerrain::new(gen, erosion, Arc::clone(&pipeline.tiles)).into(),
+            None => Terrain::from(gen),
+        };
+        pipeline.trns = registry.pipeline(&terrain, config)?;
+        return Ok(pipeline);
+    }
+}
+
+impl<G: TerrainGenerator + Sync> ChunkPipeline<G> {
+    fn new(gen: G, trns: Vec<Transformation>) -> Self {
+        return Self {
+            gen,
+            trns,
+            erosion: None,
+            tiles: Arc::default(),
+        };
+    }
+
+    fn with_erosion(mut self, erosion: Erosion) -> Self {
+        self.erosion = Some(erosion);
+        self.tiles = Arc::default();
+        return self;
+    }
+
+    /// Sample the surface of the region `lo..hi`, eroding it if erosion is enabled.
+    fn heightmap(&self, lo: (i32, i32), hi: (i32, i32)) -> Option<Heightmap> {
+        let count = (hi.0 - lo.0, hi.1 - lo.1);
+        return self.erosion.map(|erosion| {
+            return erosion.erode_region_cached(lo, count, &self.tiles, |x, y| {
+                return self.gen.surface_height(x, y);
+            });
+        });
+    }
+
+    /// Generate the terrain of a chunk and apply every transformation to it.
+    fn fill_chunk(&self, heightmap: Option<&Heightmap>, offset: ChunkOffset) -> Chunk {
+        let mut chunk = match heightmap {
+            Some(hm) => hm.generate(offset),
+            None => self.gen.generate(offset),
+        };
+        for trns in &self.trns {
+            trns.transform(&mut chunk);
+        }
+        return chunk;
     }
 }
 
+/// The chunk offsets in the region `lo..hi`.
+fn region(lo: (i32, i32), hi: (i32, i32)) -> impl Iterator<Item = ChunkOffset> {
+    return (lo.0..hi.0).flat_map(move |x| {
+        return (lo.1..hi.1).map(move |y| {
+            return ChunkOffset::new(x, y, 0).expect("chunk offsets with z = 0 are valid");
+        });
+    });
+}
+
+/// The region of `count` chunks in every direction around `center`.
+fn bounds(count: (i32, i32), center: (i32, i32)) -> ((i32, i32), (i32, i32)) {
+    let lo = (1 - count.0 + center.0, 1 - count.1 + center.1);
+    let hi = (count.0 + center.0, count.1 + center.1);
+    return (lo, hi);
+}
+
 /// A `ChunkGenerator` is a trait that generates a `Chunk`.
 pub trait ChunkGenerator {
     /// Generate a block at a specific position.
@@ -120,19 +322,57 @@ pub trait ChunkGenerator {
         &self,
         chunk_pos: impl Into<ChunkPos>,
         chunk_offset: impl Into<ChunkOffset> + Copy,
-    ) -> Block;
+    ) -> Block
+    where
+        Self: Sized;
+
+    /// Get an upper bound for the height of the highest non-air block in a chunk.
+    ///
+    /// Blocks above this height are not generated, so sections that would only contain air are
+    /// skipped entirely. Returns `None` if there is no known bound.
+    fn max_height(&self, _chunk_offset: ChunkOffset) -> Option<i32> {
+        return None;
+    }
+
+    /// Get the surface height of every column in a chunk, in row-major order.
+    ///
+    /// Generators whose columns are solid stone up to the surface and air above it implement this,
+    /// so `generate` can fill whole columns at once instead of calling `generate_at` for every
+    /// block. Returns `None` if the chunk cannot be described by its columns.
+    fn column_heights(&self, _chunk_offset: ChunkOffset) -> Option<Vec<i32>> {
+        return None;
+    }
 
     /// Generate a `Chunk`.
-    fn generate(&self, chunk_offset: impl Into<ChunkOffset> + Copy) -> Chunk {
+    fn generate(&self, chunk_offset: impl Into<ChunkOffset> + Copy) -> Chunk
+    where
+        Self: Sized,
+    {
         let start = std::time::Instant::now();
-        let mut blocks = std::collections::HashMap::new();
+        let mut chunk = Chunk::new(chunk_offset);
+        if let Some(heights) = self.column_heights(chunk_offset.into()) {
+            for (i, z) in (0i32..).zip(heights) {
+                let (x, y) = (i % CHUNK_SIZE, i / CHUNK_SIZE);
+                chunk.fill_column(x, y, 0..z.saturating_add(1), BlockType::Stone);
+            }
+            trace!(
+                "generated chunk at {:?} by column in {}ms",
+                chunk_offset.into(),
+                start.elapsed().as_millis()
+            );
+            return chunk;
+        }
+
+        let top = self
+            .max_height(chunk_offset.into())
+            .map_or(CHUNK_HEIGHT - 1, |z| return z.min(CHUNK_HEIGHT - 1));
         // TODO: parallelize this
-        for z in 0i32..CHUNK_HEIGHT {
+        for z in 0i32..=top {
             for y in 0i32..CHUNK_SIZE {
                 for x in 0i32..CHUNK_SIZE {
                     let chunk_pos = ChunkPos::new(x, y, z).unwrap();
                     let blk = self.generate_at(chunk_pos, chunk_offset);
-                    blocks.insert(chunk_pos, blk);
+                    chunk.set(chunk_pos, blk.ty());
                 }
             }
         }
@@ -142,10 +382,7 @@ pub trait ChunkGenerator {
             chunk_offset.into(),
             start.elapsed().as_millis()
         );
-        return Chunk {
-            blocks,
-            position: chunk_offset.into(),
-        };
+        return chunk;
     }
 }
 
@@ -164,13 +401,9 @@ impl ChunkGenerator for NoiseChunkGenerator {
     ) -> Block {
         let chunk_pos: ChunkPos = chunk_pos.into();
         let world_pos = chunk_pos.to_world_pos(chunk_offset);
-        let sample_z = self
-            .noise
-            .fbm(world_pos.x() as f32, world_pos.y() as f32, 0.0);
 
         #[allow(clippy::cast_possible_truncation, reason = "truncation is expected")]
-        #[allow(clippy::cast_sign_loss, reason = "value should never be negative")]
-        let surface_z = (self.base_z as f32 + sample_z) as i32;
+        let surface_z = self.surface_height(world_pos.x(), world_pos.y()) as i32;
         let ty = match surface_z {
             z if chunk_pos.z() > z => crate::BlockType::Air,
             _ => crate::BlockType::Stone,
@@ -178,6 +411,84 @@ impl ChunkGenerator for NoiseChunkGenerator {
 
         return Block::new(ty, chunk_pos, chunk_offset);
     }
+
+    fn max_height(&self, chunk_offset: ChunkOffset) -> Option<i32> {
+        return self.column_heights(chunk_offset)?.into_iter().max();
+    }
+
+    /// Sample the noise once per column, in a single batch for the whole chunk.
+    #[allow(clippy::cast_possible_truncation, reason = "truncation is expected")]
+    fn column_heights(&self, chunk_offset: ChunkOffset) -> Option<Vec<i32>> {
+        let (ox, oy) = (chunk_offset.x() * CHUNK_SIZE, chunk_offset.y() * CHUNK_SIZE);
+        let (xs, ys): (Vec<_>, Vec<_>) = (0..CHUNK_SIZE)
+            .flat_map(|y| return (0..CHUNK_SIZE).map(move |x| return (x, y)))
+            .map(|(x, y)| return ((ox + x) as f32, (oy + y) as f32))
+            .unzip();
+        let mut heights = vec![0.0; xs.len()];
+        self.noise.fbm_batch(&xs, &ys, 0.0, &mut heights);
+
+        let base_z = self.base_z as f32;
+        return Some(
+            heights
+                .into_iter()
+                .map(|h| return (base_z + h) as i32)
+                .collect(),
+        );
+    }
+}
+
+/// Get the column heights returned by `f` within a chunk, in row-major order.
+#[allow(clippy::cast_possible_truncation, reason = "truncation is expected")]
+pub(crate) fn chunk_columns(chunk_offset: ChunkOffset, f: impl Fn(i32, i32) -> f32) -> Vec<i32> {
+    let (ox, oy) = (chunk_offset.x() * CHUNK_SIZE, chunk_offset.y() * CHUNK_SIZE);
+    return (0..CHUNK_SIZE)
+        .flat_map(|y| return (0..CHUNK_SIZE).map(move |x| return (x, y)))
+        .map(|(x, y)| return f(ox + x, oy + y) as i32)
+        .collect();
+}
+
+/// Get the highest of the column heights returned by `f` within a chunk.
+pub(crate) fn max_column_height(
+    chunk_offset: ChunkOffset,
+    f: impl Fn(i32, i32) -> f32,
+) -> Option<i32> {
+    return chunk_columns(chunk_offset, f).into_iter().max();
+}
+
+/// A `TerrainGenerator` is a `ChunkGenerator` that is defined by the height of its surface.
+pub trait TerrainGenerator: ChunkGenerator {
+    /// Get the height of the terrain surface at a world column.
+    fn surface_height(&self, x: i32, y: i32) -> f32;
+}
+
+/// The terrain of a world, which stages can share to look at the surface beyond their chunk.
+#[derive(Clone)]
+pub struct Terrain(Arc<dyn TerrainGenerator + Send + Sync>);
+
+impl fmt::Debug for Terrain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_tuple("Terrain").finish_non_exhaustive();
+    }
+}
+
+impl Deref for Terrain {
+    type Target = dyn TerrainGenerator + Send + Sync;
+
+    fn deref(&self) -> &Self::Target {
+        return &*self.0;
+    }
+}
+
+impl<T: TerrainGenerator + Send + Sync + 'static> From<T> for Terrain {
+    fn from(terrain: T) -> Self {
+        return Self(Arc::new(terrain));
+    }
+}
+
+impl TerrainGenerator for NoiseChunkGenerator {
+    fn surface_height(&self, x: i32, y: i32) -> f32 {
+        return NoiseChunkGenerator::surface_height(self, x, y);
+    }
 }
 
 impl NoiseChunkGenerator {
@@ -199,4 +510,47 @@ impl NoiseChunkGenerator {
     pub fn with_noise(noise: Noise, base_z: i32) -> Self {
         return Self { noise, base_z };
     }
+
+    /// Get the height of the terrain surface at a world column.
+    #[allow(clippy::cast_precision_loss, reason = "precisions is not important")]
+    #[must_use]
+    pub fn surface_height(&self, x: i32, y: i32) -> f32 {
+        return self.base_z as f32 + self.noise.fbm(x as f32, y as f32, 0.0);
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Only implements `generate_at`, so chunks are generated one block at a time.
+    struct PerBlock<G>(G);
+
+    impl<G: ChunkGenerator> ChunkGenerator for PerBlock<G> {
+        fn generate_at(
+            &self,
+            chunk_pos: impl Into<ChunkPos>,
+            chunk_offset: impl Into<ChunkOffset> + Copy,
+        ) -> Block {
+            return self.0.generate_at(chunk_pos, chunk_offset);
+        }
+    }
+
+    #[test]
+    fn columns_match_blocks() {
+        let noise = NoiseChunkGenerator::new(7, 100, 5, 16.0, 40.0, 2.0, 0.5);
+        let heightmap = Heightmap::from_fn((-20, -20), 40, 40, |x, y| {
+            return (x * 3 + y) as f32 - 20.0;
+        });
+
+        for (x, y) in [(0, 0), (-1, 3), (4, -2)] {
+            let offset = ChunkOffset::new(x, y, 0).unwrap();
+            assert_eq!(PerBlock(noise).generate(offset), noise.generate(offset));
+            assert_eq!(
+                PerBlock(heightmap.clone()).generate(offset),
+                heightmap.generate(offset)
+            );
+        }
+    }
 }
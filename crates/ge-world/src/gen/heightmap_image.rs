@@ -0,0 +1,218 @@
+use super::{chunk_columns, max_column_height, ChunkGenerator, TerrainGenerator};
+use crate::{Block, BlockType};
+use ge_util::{ChunkOffset, ChunkPos};
+use std::path::Path;
+
+/// How an `ImageChunkGenerator` samples terrain outside of the image.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeMode {
+    /// Repeat the edge pixels forever.
+    #[default]
+    Clamp,
+    /// Repeat the whole image, which works best for images that tile seamlessly.
+    Tile,
+}
+
+/// A `ChunkGenerator` that reads the terrain from a grayscale heightmap image.
+///
+/// Black pixels are at `base_height` and white pixels are `vertical_scale` blocks above it. Each
+/// pixel covers `horizontal_scale` blocks, and heights between pixels are interpolated. 16-bit
+/// images are sampled at full precision.
+#[derive(Debug, Clone)]
+pub struct ImageChunkGenerator {
+    width: usize,
+    height: usize,
+    /// Samples in row-major order, normalised to `0.0..=1.0`.
+    samples: Vec<f32>,
+    horizontal_scale: f32,
+    vertical_scale: f32,
+    base_height: i32,
+    edge: EdgeMode,
+}
+
+impl ImageChunkGenerator {
+    /// Load a heightmap image from disk.
+    ///
+    /// # Errors
+    /// Errors if the file cannot be read or decoded.
+    pub fn open(path: impl AsRef<Path>) -> image::ImageResult<Self> {
+        return Ok(Self::from_image(&image::open(path)?));
+    }
+
+    /// Create a generator from an image, using its luminance as the height.
+    #[must_use]
+    pub fn from_image(image: &image::DynamicImage) -> Self {
+        let luma = image.to_luma16();
+        return Self {
+            width: luma.width() as usize,
+            height: luma.height() as usize,
+            samples: luma
+                .pixels()
+                .map(|p| return f32::from(p.0[0]) / f32::from(u16::MAX))
+                .collect(),
+            horizontal_scale: 1.0,
+            vertical_scale: 64.0,
+            base_height: 64,
+            edge: EdgeMode::default(),
+        };
+    }
+
+    /// Set the number of blocks covered by each pixel.
+    #[must_use]
+    pub fn with_horizontal_scale(mut self, scale: f32) -> Self {
+        self.horizontal_scale = scale;
+        return self;
+    }
+
+    /// Set the height difference in blocks between black and white pixels.
+    #[must_use]
+    pub fn with_vertical_scale(mut self, scale: f32) -> Self {
+        self.vertical_scale = scale;
+        return self;
+    }
+
+    /// Set the height of black pixels.
+    #[must_use]
+    pub fn with_base_height(mut self, base_height: i32) -> Self {
+        self.base_height = base_height;
+        return self;
+    }
+
+    /// Set how terrain outside of the image is sampled.
+    #[must_use]
+    pub fn with_edge_mode(mut self, edge: EdgeMode) -> Self {
+        self.edge = edge;
+        return self;
+    }
+
+    /// Get the sample at a pixel, which may be outside of the image.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::cast_sign_loss,
+        reason = "image sizes fit in an i64 and the index is wrapped or clamped first"
+    )]
+    fn pixel(&self, x: i64, y: i64) -> f32 {
+        let (w, h) = (self.width as i64, self.height as i64);
+        let (x, y) = match self.edge {
+            EdgeMode::Clamp => (x.clamp(0, w - 1), y.clamp(0, h - 1)),
+            EdgeMode::Tile => (x.rem_euclid(w), y.rem_euclid(h)),
+        };
+        return self.samples[y as usize * self.width + x as usize];
+    }
+
+    /// Sample the image at a point in pixel space with bilinear interpolation, in `0.0..=1.0`.
+    #[allow(clippy::cast_possible_truncation, reason = "truncation is expected")]
+    fn sample(&self, u: f32, v: f32) -> f32 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+
+        let (u0, v0) = (u.floor(), v.floor());
+        let (tu, tv) = (u - u0, v - v0);
+        let (x, y) = (u0 as i64, v0 as i64);
+        let lerp = |a: f32, b: f32, t: f32| return a + (b - a) * t;
+        let top = lerp(self.pixel(x, y), self.pixel(x + 1, y), tu);
+        let bottom = lerp(self.pixel(x, y + 1), self.pixel(x + 1, y + 1), tu);
+        return lerp(top, bottom, tv);
+    }
+}
+
+impl TerrainGenerator for ImageChunkGenerator {
+    #[allow(clippy::cast_precision_loss, reason = "precision is not important")]
+    fn surface_height(&self, x: i32, y: i32) -> f32 {
+        let scale = self.horizontal_scale.max(f32::EPSILON);
+        let h = self.sample(x as f32 / scale, y as f32 / scale);
+        return self.base_height as f32 + h * self.vertical_scale;
+    }
+}
+
+impl ChunkGenerator for ImageChunkGenerator {
+    fn generate_at(
+        &self,
+        chunk_pos: impl Into<ChunkPos>,
+        chunk_offset: impl Into<ChunkOffset> + Copy,
+    ) -> Block {
+        let chunk_pos: ChunkPos = chunk_pos.into();
+        let world_pos = chunk_pos.to_world_pos(chunk_offset);
+
+        #[allow(clippy::cast_possible_truncation, reason = "truncation is expected")]
+        let surface_z = self.surface_height(world_pos.x(), world_pos.y()) as i32;
+        let ty = match surface_z {
+            z if chunk_pos.z() > z => BlockType::Air,
+            _ => BlockType::Stone,
+        };
+
+        return Block::new(ty, chunk_pos, chunk_offset);
+    }
+
+    fn max_height(&self, chunk_offset: ChunkOffset) -> Option<i32> {
+        return max_column_height(chunk_offset, |x, y| return self.surface_height(x, y));
+    }
+
+    fn column_heights(&self, chunk_offset: ChunkOffset) -> Option<Vec<i32>> {
+        return Some(chunk_columns(chunk_offset, |x, y| {
+            return self.surface_height(x, y);
+        }));
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gen::WorldGenerator, trns::TransformationRegistry};
+    use image::{DynamicImage, ImageBuffer, Luma};
+
+    fn gen() -> ImageChunkGenerator {
+        // a 2x2 image with a single white pixel
+        let img = ImageBuffer::from_fn(2, 2, |x, y| {
+            return Luma([if (x, y) == (1, 0) { u16::MAX } else { 0 }]);
+        });
+        return ImageChunkGenerator::from_image(&DynamicImage::ImageLuma16(img))
+            .with_base_height(10)
+            .with_vertical_scale(20.0)
+            .with_horizontal_scale(4.0);
+    }
+
+    #[test]
+    fn scales_and_interpolates() {
+        let gen = gen();
+        assert_eq!(10.0, gen.surface_height(0, 0));
+        assert_eq!(30.0, gen.surface_height(4, 0));
+        assert_eq!(20.0, gen.surface_height(2, 0));
+        assert_eq!(20.0, gen.surface_height(4, 2));
+        assert_eq!(15.0, gen.surface_height(2, 2));
+    }
+
+    #[test]
+    fn edges() {
+        let clamp = gen();
+        assert_eq!(30.0, clamp.surface_height(100, -100));
+        assert_eq!(10.0, clamp.surface_height(-100, 100));
+
+        let tile = gen().with_edge_mode(EdgeMode::Tile);
+        assert_eq!(30.0, tile.surface_height(12, 0));
+        assert_eq!(30.0, tile.surface_height(-4, -8));
+        assert_eq!(10.0, tile.surface_height(8, 0));
+    }
+
+    #[test]
+    fn pipeline() {
+        let mut config = ge_util::EngineConfig::default();
+        config.world_gen.sea_level = 20;
+        let registry = TransformationRegistry::default();
+        let world = crate::gen::FixedWorldGenerator::with_stages(gen(), (1, 1), &config, &registry)
+            .unwrap()
+            .generate();
+
+        let chunk = &world.chunks[0];
+        let top = |x, y| return chunk.get(ChunkPos::new(x, y, 20).unwrap());
+        assert_eq!(BlockType::Water, top(0, 0));
+        assert_eq!(BlockType::Stone, top(4, 0));
+        assert_eq!(
+            Some(30),
+            chunk.highest(4, 0, |ty| return ty == BlockType::Grass)
+        );
+    }
+}
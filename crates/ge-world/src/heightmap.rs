@@ -0,0 +1,233 @@
+use crate::{
+    gen::{chunk_columns, max_column_height, ChunkGenerator, TerrainGenerator},
+    Block, BlockType,
+};
+use ge_util::{coords::CHUNK_SIZE, ChunkOffset, ChunkPos};
+
+/// A `Heightmap` is a rectangular grid of surface heights in world space.
+///
+/// The grid starts at `origin` (in world `x` and `y`) and has one sample per block column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heightmap {
+    origin: (i32, i32),
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Heightmap {
+    /// Create a new flat heightmap with every sample set to `0.0`.
+    #[must_use]
+    pub fn new(origin: (i32, i32), width: usize, height: usize) -> Self {
+        return Self {
+            origin,
+            width,
+            height,
+            data: vec![0.0; width * height],
+        };
+    }
+
+    /// Create a new heightmap by sampling `f` at every world column in the grid.
+    #[must_use]
+    pub fn from_fn(
+        origin: (i32, i32),
+        width: usize,
+        height: usize,
+        f: impl Fn(i32, i32) -> f32,
+    ) -> Self {
+        let mut data = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (wx, wy) = Self::to_world(origin, x, y);
+                data.push(f(wx, wy));
+            }
+        }
+
+        return Self {
+            origin,
+            width,
+            height,
+            data,
+        };
+    }
+
+    /// Create a heightmap that covers a rectangular region of chunks.
+    ///
+    /// `lo` is the first chunk offset and `count` is the number of chunks along each axis.
+    #[must_use]
+    pub fn from_chunks(lo: (i32, i32), count: (i32, i32), f: impl Fn(i32, i32) -> f32) -> Self {
+        #[allow(clippy::cast_sign_loss, reason = "chunk counts are never negative")]
+        return Self::from_fn(
+            (lo.0 * CHUNK_SIZE, lo.1 * CHUNK_SIZE),
+            (count.0 * CHUNK_SIZE) as usize,
+            (count.1 * CHUNK_SIZE) as usize,
+            f,
+        );
+    }
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    fn to_world(origin: (i32, i32), x: usize, y: usize) -> (i32, i32) {
+        return (origin.0 + x as i32, origin.1 + y as i32);
+    }
+
+    /// The world position of the first sample.
+    #[must_use]
+    pub fn origin(&self) -> (i32, i32) {
+        return self.origin;
+    }
+
+    /// The number of samples along the `x` axis.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        return self.width;
+    }
+
+    /// The number of samples along the `y` axis.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        return self.height;
+    }
+
+    /// The raw samples in row-major order.
+    #[must_use]
+    pub fn data(&self) -> &[f32] {
+        return &self.data;
+    }
+
+    /// Get the sample at a grid position.
+    ///
+    /// # Panics
+    /// Panics if the position is outside of the grid.
+    #[must_use]
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        assert!(x < self.width && y < self.height, "out of range");
+        return self.data[y * self.width + x];
+    }
+
+    /// Set the sample at a grid position.
+    ///
+    /// # Panics
+    /// Panics if the position is outside of the grid.
+    pub fn set(&mut self, x: usize, y: usize, value: f32) {
+        assert!(x < self.width && y < self.height, "out of range");
+        self.data[y * self.width + x] = value;
+    }
+
+    /// Get a mutable reference to the sample at a grid position.
+    pub(crate) fn get_mut(&mut self, x: usize, y: usize) -> &mut f32 {
+        return &mut self.data[y * self.width + x];
+    }
+
+    /// Get the sample at a world column, or `None` if the column is outside of the grid.
+    #[must_use]
+    pub fn get_world(&self, x: i32, y: i32) -> Option<f32> {
+        let gx = usize::try_from(x - self.origin.0).ok()?;
+        let gy = usize::try_from(y - self.origin.1).ok()?;
+        if gx >= self.width || gy >= self.height {
+            return None;
+        }
+        return Some(self.get(gx, gy));
+    }
+
+    /// Get the sample at a world column, clamping the column to the edge of the grid.
+    ///
+    /// # Panics
+    /// Panics if the heightmap is empty.
+    #[must_use]
+    pub fn get_world_clamped(&self, x: i32, y: i32) -> f32 {
+        assert!(!self.data.is_empty(), "heightmap is empty");
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let gx = (x - self.origin.0).clamp(0, self.width as i32 - 1);
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        let gy = (y - self.origin.1).clamp(0, self.height as i32 - 1);
+        #[allow(clippy::cast_sign_loss, reason = "value is clamped to be positive")]
+        return self.get(gx as usize, gy as usize);
+    }
+
+    /// Copy a sub-region of this heightmap into a new heightmap.
+    ///
+    /// # Panics
+    /// Panics if the sub-region is not fully contained within this heightmap.
+    #[must_use]
+    pub fn crop(&self, origin: (i32, i32), width: usize, height: usize) -> Self {
+        let ox = usize::try_from(origin.0 - self.origin.0).expect("crop outside of heightmap");
+        let oy = usize::try_from(origin.1 - self.origin.1).expect("crop outside of heightmap");
+        assert!(
+            ox + width <= self.width && oy + height <= self.height,
+            "crop outside of heightmap"
+        );
+
+        let mut data = Vec::with_capacity(width * height);
+        for y in oy..oy + height {
+            data.extend_from_slice(&self.data[y * self.width + ox..y * self.width + ox + width]);
+        }
+
+        return Self {
+            origin,
+            width,
+            height,
+            data,
+        };
+    }
+}
+
+impl ChunkGenerator for Heightmap {
+    fn generate_at(
+        &self,
+        chunk_pos: impl Into<ChunkPos>,
+        chunk_offset: impl Into<ChunkOffset> + Copy,
+    ) -> Block {
+        let chunk_pos: ChunkPos = chunk_pos.into();
+        let world_pos = chunk_pos.to_world_pos(chunk_offset);
+
+        #[allow(clippy::cast_possible_truncation, reason = "truncation is expected")]
+        let surface_z = self.get_world_clamped(world_pos.x(), world_pos.y()) as i32;
+        let ty = match surface_z {
+            z if chunk_pos.z() > z => BlockType::Air,
+            _ => BlockType::Stone,
+        };
+
+        return Block::new(ty, chunk_pos, chunk_offset);
+    }
+
+    fn max_height(&self, chunk_offset: ChunkOffset) -> Option<i32> {
+        return max_column_height(chunk_offset, |x, y| return self.get_world_clamped(x, y));
+    }
+
+    fn column_heights(&self, chunk_offset: ChunkOffset) -> Option<Vec<i32>> {
+        return Some(chunk_columns(chunk_offset, |x, y| {
+            return self.get_world_clamped(x, y);
+        }));
+    }
+}
+
+impl TerrainGenerator for Heightmap {
+    fn surface_height(&self, x: i32, y: i32) -> f32 {
+        return self.get_world_clamped(x, y);
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_lookup() {
+        let hm = Heightmap::from_fn((-4, 10), 8, 4, |x, y| return (x + y) as f32);
+        assert_eq!(Some(6.0), hm.get_world(-4, 10));
+        assert_eq!(Some(16.0), hm.get_world(3, 13));
+        assert_eq!(None, hm.get_world(4, 13));
+        assert_eq!(None, hm.get_world(-5, 10));
+        assert_eq!(hm.get_world_clamped(100, 100), hm.get(7, 3));
+    }
+
+    #[test]
+    fn crop() {
+        let hm = Heightmap::from_fn((0, 0), 8, 8, |x, y| return (x * 10 + y) as f32);
+        let cropped = hm.crop((2, 3), 4, 2);
+        assert_eq!(Some(23.0), cropped.get_world(2, 3));
+        assert_eq!(Some(54.0), cropped.get_world(5, 4));
+        assert_eq!(8, cropped.data().len());
+    }
+}
@@ -0,0 +1,295 @@
+use crate::Chunk;
+use ge_util::ChunkOffset;
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    fmt,
+    sync::{
+        atomic::{self, AtomicBool},
+        mpsc, Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// How urgently a chunk is needed.
+///
+/// Chunks that are in view always come before chunks that are not, and closer chunks come before
+/// chunks that are further away.
+#[derive(Debug, Clone, Copy)]
+pub struct Priority {
+    pub in_view: bool,
+    pub distance: f32,
+}
+
+impl Priority {
+    #[must_use]
+    pub fn new(in_view: bool, distance: f32) -> Self {
+        return Self { in_view, distance };
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return self
+            .in_view
+            .cmp(&other.in_view)
+            .then_with(|| return other.distance.total_cmp(&self.distance));
+    }
+}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl PartialEq for Priority {
+    fn eq(&self, other: &Self) -> bool {
+        return self.cmp(other) == Ordering::Equal;
+    }
+}
+
+impl Eq for Priority {}
+
+#[derive(Debug)]
+struct Job {
+    offset: ChunkOffset,
+    priority: Priority,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return self.priority.cmp(&other.priority);
+    }
+}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        return self.priority == other.priority;
+    }
+}
+
+impl Eq for Job {}
+
+type GenerateFn = dyn Fn(ChunkOffset) -> Chunk + Send + Sync;
+
+/// Generates chunks in the background on a rayon thread pool.
+///
+/// Every submitted chunk spawns a task on the pool, and each task runs whichever queued job has
+/// the highest `Priority` when it starts, so priorities can change while jobs wait. Finished
+/// chunks are sent back through a channel and collected with `drain`.
+pub struct ChunkJobs {
+    pool: Arc<rayon::ThreadPool>,
+    generate: Arc<GenerateFn>,
+    queue: Arc<Mutex<BinaryHeap<Job>>>,
+    /// Cancellation flags of every job that has been submitted but not received yet.
+    jobs: HashMap<ChunkOffset, Arc<AtomicBool>>,
+    sender: mpsc::Sender<Chunk>,
+    receiver: mpsc::Receiver<Chunk>,
+}
+
+impl fmt::Debug for ChunkJobs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f
+            .debug_struct("ChunkJobs")
+            .field("pool", &self.pool)
+            .field("jobs", &self.jobs.len())
+            .finish_non_exhaustive();
+    }
+}
+
+impl ChunkJobs {
+    /// Create a job queue that runs `generate` on `pool`.
+    pub fn new(
+        pool: Arc<rayon::ThreadPool>,
+        generate: impl Fn(ChunkOffset) -> Chunk + Send + Sync + 'static,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        return Self {
+            pool,
+            generate: Arc::new(generate),
+            queue: Arc::default(),
+            jobs: HashMap::new(),
+            sender,
+            receiver,
+        };
+    }
+
+    /// Queue a chunk to be generated.
+    ///
+    /// Returns `false` if the chunk is already queued or being generated.
+    #[allow(clippy::missing_panics_doc, reason = "the lock is never poisoned")]
+    pub fn submit(&mut self, offset: ChunkOffset, priority: Priority) -> bool {
+        if self.jobs.contains_key(&offset) {
+            return false;
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.jobs.insert(offset, Arc::clone(&cancelled));
+        self.queue.lock().unwrap().push(Job {
+            offset,
+            priority,
+            cancelled,
+        });
+
+        let queue = Arc::clone(&self.queue);
+        let generate = Arc::clone(&self.generate);
+        let sender = self.sender.clone();
+        self.pool.spawn(move || {
+            let Some(job) = queue.lock().unwrap().pop() else {
+                return;
+            };
+            if job.cancelled.load(atomic::Ordering::Relaxed) {
+                return;
+            }
+
+            let chunk = generate(job.offset);
+            if !job.cancelled.load(atomic::Ordering::Relaxed) {
+                // the receiver is only gone if the queue was dropped
+                let _ = sender.send(chunk);
+            }
+        });
+        return true;
+    }
+
+    /// Update the priority of every queued job.
+    #[allow(clippy::missing_panics_doc, reason = "the lock is never poisoned")]
+    pub fn prioritise(&self, f: impl Fn(ChunkOffset) -> Priority) {
+        let mut queue = self.queue.lock().unwrap();
+        let mut jobs = std::mem::take(&mut *queue).into_vec();
+        for job in &mut jobs {
+            job.priority = f(job.offset);
+        }
+        *queue = BinaryHeap::from(jobs);
+    }
+
+    /// Cancel every job whose chunk does not match `f`.
+    ///
+    /// Cancelled jobs that have not started are skipped, and chunks from cancelled jobs that are
+    /// already running are thrown away.
+    #[allow(clippy::missing_panics_doc, reason = "the lock is never poisoned")]
+    pub fn retain(&mut self, f: impl Fn(ChunkOffset) -> bool) {
+        self.jobs.retain(|&offset, cancelled| {
+            let keep = f(offset);
+            if !keep {
+                cancelled.store(true, atomic::Ordering::Relaxed);
+            }
+            return keep;
+        });
+        self.queue
+            .lock()
+            .unwrap()
+            .retain(|job| return !job.cancelled.load(atomic::Ordering::Relaxed));
+    }
+
+    /// Receive finished chunks and pass them to `f` until `budget` has passed or there are no
+    /// more chunks ready. Returns the number of chunks received.
+    ///
+    /// The time spent in `f` counts towards the budget, so the caller's work is limited as well.
+    pub fn drain(&mut self, budget: Duration, mut f: impl FnMut(Chunk)) -> usize {
+        let start = Instant::now();
+        let mut count = 0;
+        while start.elapsed() < budget {
+            let Ok(chunk) = self.receiver.try_recv() else {
+                break;
+            };
+            // chunks from jobs that were cancelled and submitted again are still valid, but the
+            // newer job does not need to run anymore
+            if let Some(cancelled) = self.jobs.remove(&chunk.position) {
+                cancelled.store(true, atomic::Ordering::Relaxed);
+                f(chunk);
+                count += 1;
+            }
+        }
+        return count;
+    }
+
+    /// The number of jobs that have been submitted but not received yet.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        return self.jobs.len();
+    }
+
+    /// Returns `true` if the chunk is queued or being generated.
+    #[must_use]
+    pub fn contains(&self, offset: ChunkOffset) -> bool {
+        return self.jobs.contains_key(&offset);
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offset(x: i32) -> ChunkOffset {
+        return ChunkOffset::new(x, 0, 0).unwrap();
+    }
+
+    /// A single threaded pool that is blocked until the returned sender is used.
+    fn blocked_pool() -> (Arc<rayon::ThreadPool>, mpsc::Sender<()>) {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let (tx, rx) = mpsc::channel();
+        pool.spawn(move || return rx.recv().unwrap());
+        return (Arc::new(pool), tx);
+    }
+
+    fn receive_all(jobs: &mut ChunkJobs) -> Vec<i32> {
+        let mut received = vec![];
+        let start = Instant::now();
+        while jobs.pending() > 0 && start.elapsed() < Duration::from_secs(10) {
+            jobs.drain(Duration::from_millis(10), |c| {
+                return received.push(c.position.x());
+            });
+        }
+        return received;
+    }
+
+    #[test]
+    fn runs_in_priority_order() {
+        let (pool, unblock) = blocked_pool();
+        let mut jobs = ChunkJobs::new(pool, |o| return Chunk::new(o));
+
+        assert!(jobs.submit(offset(0), Priority::new(false, 1.0)));
+        assert!(jobs.submit(offset(1), Priority::new(true, 50.0)));
+        assert!(jobs.submit(offset(2), Priority::new(true, 10.0)));
+        assert!(jobs.submit(offset(3), Priority::new(false, 5.0)));
+        assert!(!jobs.submit(offset(3), Priority::new(true, 0.0)));
+
+        // moving the camera changes the order of jobs that are still queued
+        jobs.prioritise(|o| {
+            return Priority::new(o.x() != 2, o.x() as f32);
+        });
+        unblock.send(()).unwrap();
+
+        assert_eq!(vec![0, 1, 3, 2], receive_all(&mut jobs));
+    }
+
+    #[test]
+    fn cancelled_jobs_are_dropped() {
+        let (pool, unblock) = blocked_pool();
+        let mut jobs = ChunkJobs::new(pool, |o| return Chunk::new(o));
+
+        for x in 0..4 {
+            jobs.submit(offset(x), Priority::new(true, x as f32));
+        }
+        jobs.retain(|o| return o.x() % 2 == 0);
+        assert_eq!(2, jobs.pending());
+        assert!(!jobs.contains(offset(1)));
+        unblock.send(()).unwrap();
+
+        let mut received = receive_all(&mut jobs);
+        received.sort();
+        assert_eq!(vec![0, 2], received);
+    }
+}
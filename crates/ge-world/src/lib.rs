@@ -13,13 +13,20 @@
 #[macro_use]
 extern crate tracing;
 
+pub mod delta;
+pub mod edit;
+pub mod erosion;
 pub mod gen;
+pub mod heightmap;
+pub mod jobs;
+pub mod lod;
+pub mod map;
+pub mod mesh;
 pub mod noise;
+pub mod section;
 pub mod spline;
 pub mod trns;
 
+mod seed;
 mod types;
 pub use types::*;
-
-#[macro_use]
-extern crate ge_macros;
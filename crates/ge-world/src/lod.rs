@@ -0,0 +1,223 @@
+use crate::{BlockType, Chunk};
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE, SECTION_SIZE},
+    ChunkOffset, LodConfig,
+};
+
+/// How much detail a chunk is drawn with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LodLevel {
+    /// Every block is drawn.
+    #[default]
+    Full,
+    /// Cells of `2x2x2` blocks.
+    Half,
+    /// Cells of `4x4x4` blocks.
+    Quarter,
+    /// Cells of `8x8x8` blocks.
+    Eighth,
+}
+
+impl LodLevel {
+    pub const ALL: [LodLevel; 4] = [
+        LodLevel::Full,
+        LodLevel::Half,
+        LodLevel::Quarter,
+        LodLevel::Eighth,
+    ];
+
+    /// The number of blocks along each side of a cell.
+    #[must_use]
+    pub fn scale(self) -> i32 {
+        return 1 << self as i32;
+    }
+
+    /// The furthest distance in chunks at which this level is used.
+    fn max_distance(self, config: &LodConfig) -> Option<i32> {
+        if self == LodLevel::Eighth {
+            return None;
+        }
+        #[allow(clippy::cast_possible_wrap, reason = "distances are small")]
+        return Some(config.distance as i32 * self.scale());
+    }
+
+    /// Pick the level of a chunk at `distance` chunks from the camera.
+    ///
+    /// A chunk that is already drawn at `current` only switches to a coarser level once it is
+    /// `config.hysteresis` chunks past the threshold, so chunks do not flicker between levels when
+    /// the camera moves back and forth across it. Switching to a finer level happens straight away.
+    #[must_use]
+    pub fn select(distance: i32, current: Option<LodLevel>, config: &LodConfig) -> Self {
+        if !config.enabled {
+            return LodLevel::Full;
+        }
+
+        let target = Self::ALL
+            .into_iter()
+            .find(|l| {
+                return l
+                    .max_distance(config)
+                    .is_none_or(|max| return distance <= max);
+            })
+            .unwrap_or(LodLevel::Eighth);
+
+        #[allow(clippy::cast_possible_wrap, reason = "distances are small")]
+        return match current {
+            Some(current) if current < target => match current.max_distance(config) {
+                Some(max) if distance <= max + config.hysteresis as i32 => current,
+                _ => target,
+            },
+            _ => target,
+        };
+    }
+
+    /// The distance between two chunks in chunks, as used by `select`.
+    #[must_use]
+    pub fn distance(a: ChunkOffset, b: ChunkOffset) -> i32 {
+        return (a.x() - b.x()).abs().max((a.y() - b.y()).abs());
+    }
+}
+
+/// A chunk downsampled into cells of `scale x scale x scale` blocks.
+///
+/// A cell is solid if at least half of its blocks are, and it takes the type of its highest block
+/// so grass stays on top of the terrain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LodChunk {
+    pub position: ChunkOffset,
+    level: LodLevel,
+    cells: Vec<BlockType>,
+}
+
+impl LodChunk {
+    #[allow(
+        clippy::cast_sign_loss,
+        reason = "positions in a chunk are never negative"
+    )]
+    #[must_use]
+    pub fn from_chunk(chunk: &Chunk, level: LodLevel) -> Self {
+        let s = level.scale();
+        let (w, h) = (CHUNK_SIZE / s, CHUNK_HEIGHT / s);
+        let len = (w * w * h) as usize;
+        let mut counts = vec![0i32; len];
+        let mut tops = vec![(-1, BlockType::Air); len];
+
+        for (i, section) in chunk.sections() {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            let base_z = i as i32 * SECTION_SIZE;
+            for ((x, y, z), ty) in section.iter() {
+                let z = base_z + z;
+                let cell = (((z / s) * w + y / s) * w + x / s) as usize;
+                counts[cell] += 1;
+                if z > tops[cell].0 {
+                    tops[cell] = (z, ty);
+                }
+            }
+        }
+
+        let cells = counts
+            .into_iter()
+            .zip(tops)
+            .map(|(count, (_, ty))| {
+                return if count * 2 >= s * s * s {
+                    ty
+                } else {
+                    BlockType::Air
+                };
+            })
+            .collect();
+
+        return Self {
+            position: chunk.position,
+            level,
+            cells,
+        };
+    }
+
+    #[must_use]
+    pub fn level(&self) -> LodLevel {
+        return self.level;
+    }
+
+    /// The number of cells along the horizontal and vertical axes.
+    #[must_use]
+    pub fn size(&self) -> (i32, i32) {
+        let s = self.level.scale();
+        return (CHUNK_SIZE / s, CHUNK_HEIGHT / s);
+    }
+
+    /// Get the type of a cell, or `None` if the position is outside of the chunk.
+    #[allow(clippy::cast_sign_loss, reason = "positions are checked first")]
+    #[must_use]
+    pub fn get(&self, x: i32, y: i32, z: i32) -> Option<BlockType> {
+        let (width, height) = self.size();
+        if !(0..width).contains(&x) || !(0..width).contains(&y) || !(0..height).contains(&z) {
+            return None;
+        }
+        return Some(self.cells[((z * width + y) * width + x) as usize]);
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ge_util::ChunkPos;
+
+    fn config() -> LodConfig {
+        return LodConfig {
+            enabled: true,
+            distance: 2,
+            hysteresis: 1,
+            skirt: 1,
+        };
+    }
+
+    #[test]
+    fn select_levels() {
+        let config = config();
+        let levels = (0..=20)
+            .map(|d| return LodLevel::select(d, None, &config).scale())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![1, 1, 1, 2, 2, 4, 4, 4, 4, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8, 8],
+            levels
+        );
+
+        // coarser levels wait for the hysteresis, finer levels do not
+        let full = Some(LodLevel::Full);
+        assert_eq!(LodLevel::Full, LodLevel::select(3, full, &config));
+        assert_eq!(LodLevel::Half, LodLevel::select(4, full, &config));
+        assert_eq!(
+            LodLevel::Full,
+            LodLevel::select(2, Some(LodLevel::Half), &config)
+        );
+
+        let disabled = LodConfig {
+            enabled: false,
+            ..config
+        };
+        assert_eq!(LodLevel::Full, LodLevel::select(20, None, &disabled));
+    }
+
+    #[test]
+    fn downsample() {
+        // stone up to z = 9 with grass on top, and a single floating block
+        let mut chunk = Chunk::new(ChunkOffset::default());
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.fill_column(x, y, 0..10, BlockType::Stone);
+                chunk.set(ChunkPos::new(x, y, 9).unwrap(), BlockType::Grass);
+            }
+        }
+        chunk.set(ChunkPos::new(0, 0, 30).unwrap(), BlockType::Dirt);
+
+        let lod = LodChunk::from_chunk(&chunk, LodLevel::Quarter);
+        assert_eq!((4, 64), lod.size());
+        assert_eq!(Some(BlockType::Stone), lod.get(1, 1, 0));
+        // 2 of the 4 layers are solid, and the top one is grass
+        assert_eq!(Some(BlockType::Grass), lod.get(1, 1, 2));
+        assert_eq!(Some(BlockType::Air), lod.get(0, 0, 7));
+        assert_eq!(None, lod.get(4, 0, 0));
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{BlockType, World};
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE},
+    ChunkPos,
+};
+use image::{GrayImage, Luma, Rgb, RgbImage};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The colour of a block type when seen from above.
+#[must_use]
+pub fn block_colour(ty: BlockType) -> [u8; 3] {
+    return match ty {
+        BlockType::Dev => [255, 0, 255],
+        BlockType::Air => [0, 0, 0],
+        BlockType::Dirt => [121, 85, 58],
+        BlockType::Grass => [96, 148, 56],
+        BlockType::Stone => [125, 125, 125],
+        BlockType::Water => [48, 92, 186],
+        BlockType::Wood => [102, 81, 50],
+    };
+}
+
+/// A single column of the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapColumn {
+    /// The highest non-air block in the column.
+    pub top: BlockType,
+    /// The height of `top`.
+    pub top_z: i32,
+    /// The height of the highest opaque block, i.e. the terrain below any water.
+    pub terrain_z: i32,
+}
+
+/// A top-down view of a generated region of the world.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldMap {
+    origin: (i32, i32),
+    width: usize,
+    height: usize,
+    columns: Vec<Option<MapColumn>>,
+}
+
+impl WorldMap {
+    /// Create a map covering the bounding box of every chunk in `world`.
+    ///
+    /// Columns that are not covered by a chunk, or that only contain air, are left empty.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "the highest block is always inside the chunk"
+    )]
+    #[must_use]
+    pub fn from_world(world: &World) -> Self {
+        let Some(first) = world.chunks.first() else {
+            return Self {
+                origin: (0, 0),
+                width: 0,
+                height: 0,
+                columns: Vec::new(),
+            };
+        };
+
+        let mut lo = (first.position.x(), first.position.y());
+        let mut hi = lo;
+        for chunk in &world.chunks {
+            lo = (lo.0.min(chunk.position.x()), lo.1.min(chunk.position.y()));
+            hi = (hi.0.max(chunk.position.x()), hi.1.max(chunk.position.y()));
+        }
+
+        let origin = (lo.0 * CHUNK_SIZE, lo.1 * CHUNK_SIZE);
+        #[allow(clippy::cast_sign_loss, reason = "the region is never empty")]
+        let (width, height) = (
+            ((hi.0 - lo.0 + 1) * CHUNK_SIZE) as usize,
+            ((hi.1 - lo.1 + 1) * CHUNK_SIZE) as usize,
+        );
+        let mut columns = vec![None; width * height];
+
+        for chunk in &world.chunks {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let Some(top_z) = chunk.highest(x, y, |ty| return ty != BlockType::Air) else {
+                        continue;
+                    };
+                    let column = MapColumn {
+                        top: chunk.get(ChunkPos::new(x, y, top_z).unwrap()),
+                        top_z,
+                        terrain_z: chunk.highest(x, y, |ty| return ty.is_opaque()).unwrap_or(0),
+                    };
+
+                    #[allow(clippy::cast_sign_loss, reason = "chunks are inside the region")]
+                    let (gx, gy) = (
+                        (chunk.position.x() * CHUNK_SIZE + x - origin.0) as usize,
+                        (chunk.position.y() * CHUNK_SIZE + y - origin.1) as usize,
+                    );
+                    columns[gy * width + gx] = Some(column);
+                }
+            }
+        }
+
+        return Self {
+            origin,
+            width,
+            height,
+            columns,
+        };
+    }
+
+    /// The world position of the top left pixel.
+    #[must_use]
+    pub fn origin(&self) -> (i32, i32) {
+        return self.origin;
+    }
+
+    /// The number of columns along the `x` axis.
+    #[must_use]
+    pub fn width(&self) -> usize {
+        return self.width;
+    }
+
+    /// The number of columns along the `y` axis.
+    #[must_use]
+    pub fn height(&self) -> usize {
+        return self.height;
+    }
+
+    /// Get a column by its position in the map, or `None` if it is empty.
+    ///
+    /// # Panics
+    /// Panics if the position is outside of the map.
+    #[must_use]
+    pub fn get(&self, x: usize, y: usize) -> Option<MapColumn> {
+        assert!(x < self.width && y < self.height, "out of range");
+        return self.columns[y * self.width + x];
+    }
+
+    fn terrain_z(&self, x: usize, y: usize) -> i32 {
+        return self.get(x, y).map_or(0, |c| return c.terrain_z);
+    }
+
+    /// Render the terrain height as a grayscale image, where black is `z = 0` and white is the
+    /// top of the world.
+    #[must_use]
+    pub fn heightmap_image(&self) -> GrayImage {
+        #[allow(clippy::cast_possible_truncation, reason = "map sizes fit in a u32")]
+        return GrayImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let z = self
+                .terrain_z(x as usize, y as usize)
+                .clamp(0, CHUNK_HEIGHT - 1);
+            #[allow(clippy::cast_sign_loss, reason = "value is clamped to be positive")]
+            return Luma([(z * 255 / (CHUNK_HEIGHT - 1)) as u8]);
+        });
+    }
+
+    /// Render the top block of every column, shaded by the slope of the terrain and the depth of
+    /// any water.
+    #[must_use]
+    pub fn colour_image(&self) -> RgbImage {
+        #[allow(clippy::cast_possible_truncation, reason = "map sizes fit in a u32")]
+        return RgbImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let (x, y) = (x as usize, y as usize);
+            let Some(column) = self.get(x, y) else {
+                return Rgb([0, 0, 0]);
+            };
+
+            #[allow(clippy::cast_precision_loss, reason = "heights are small")]
+            let shade = if column.top == BlockType::Water {
+                // deeper water is darker
+                let depth = (column.top_z - column.terrain_z) as f32;
+                (1.0 - depth * 0.05).clamp(0.4, 1.0)
+            } else {
+                // light the terrain from the top left
+                let (px, py) = (x.saturating_sub(1), y.saturating_sub(1));
+                let slope = (column.terrain_z - self.terrain_z(px, py)) as f32;
+                (1.0 + slope * 0.15).clamp(0.6, 1.4)
+            };
+
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                reason = "value is clamped to the range of a u8"
+            )]
+            return Rgb(block_colour(column.top)
+                .map(|c| return (f32::from(c) * shade).clamp(0.0, 255.0) as u8));
+        });
+    }
+
+    /// Save `heightmap.png` and `colour.png` into `dir`, creating it if needed.
+    ///
+    /// Returns the paths of the written files.
+    ///
+    /// # Errors
+    /// Errors if the directory cannot be created or an image cannot be written.
+    pub fn save(&self, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>, MapError> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+
+        let heightmap = dir.join("heightmap.png");
+        self.heightmap_image().save(&heightmap)?;
+        let colour = dir.join("colour.png");
+        self.colour_image().save(&colour)?;
+
+        return Ok(vec![heightmap, colour]);
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum MapError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("image error: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chunk;
+    use ge_util::ChunkOffset;
+
+    #[test]
+    fn columns_and_images() {
+        let mut a = Chunk::new(ChunkOffset::new(-1, 0, 0).unwrap());
+        a.set(ChunkPos::new(0, 0, 10).unwrap(), BlockType::Stone);
+        a.set(ChunkPos::new(0, 0, 12).unwrap(), BlockType::Water);
+        let mut b = Chunk::new(ChunkOffset::new(0, 1, 0).unwrap());
+        b.set(ChunkPos::new(15, 15, 255).unwrap(), BlockType::Grass);
+        let map = WorldMap::from_world(&World { chunks: vec![a, b] });
+
+        assert_eq!((-16, 0), map.origin());
+        assert_eq!((32, 32), (map.width(), map.height()));
+        assert_eq!(
+            Some(MapColumn {
+                top: BlockType::Water,
+                top_z: 12,
+                terrain_z: 10,
+            }),
+            map.get(0, 0)
+        );
+        assert_eq!(None, map.get(1, 0));
+        assert_eq!(BlockType::Grass, map.get(31, 31).unwrap().top);
+
+        let heightmap = map.heightmap_image();
+        assert_eq!(10, heightmap.get_pixel(0, 0).0[0]);
+        assert_eq!(255, heightmap.get_pixel(31, 31).0[0]);
+        assert_eq!(0, heightmap.get_pixel(1, 0).0[0]);
+
+        let colour = map.colour_image();
+        assert_eq!(Rgb([0, 0, 0]), *colour.get_pixel(1, 0));
+        assert!(colour.get_pixel(0, 0).0[2] > colour.get_pixel(0, 0).0[0]);
+    }
+}
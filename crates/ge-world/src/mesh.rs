@@ -0,0 +1,503 @@
+use crate::{
+    lod::{LodChunk, LodLevel},
+    BlockType, Chunk,
+};
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE, SECTIONS_PER_CHUNK, SECTION_SIZE},
+    ChunkOffset, ChunkPos, EngineConfig,
+};
+
+/// A side of a block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    Front,
+    Back,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::Top,
+        Face::Bottom,
+        Face::Left,
+        Face::Right,
+        Face::Front,
+        Face::Back,
+    ];
+
+    /// The direction the face points in.
+    #[must_use]
+    pub fn normal(self) -> [i32; 3] {
+        return match self {
+            Face::Top => [0, 0, 1],
+            Face::Bottom => [0, 0, -1],
+            Face::Left => [-1, 0, 0],
+            Face::Right => [1, 0, 0],
+            Face::Front => [0, 1, 0],
+            Face::Back => [0, -1, 0],
+        };
+    }
+
+    /// The axis the face points along, followed by the two axes it spans.
+    fn axes(self) -> [usize; 3] {
+        return match self {
+            Face::Top | Face::Bottom => [2, 0, 1],
+            Face::Left | Face::Right => [0, 1, 2],
+            Face::Front | Face::Back => [1, 0, 2],
+        };
+    }
+}
+
+/// A rectangle of faces that point in the same direction and have the same type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quad {
+    pub ty: BlockType,
+    pub face: Face,
+    /// The lowest cell that the quad covers, relative to the chunk.
+    pub position: [i32; 3],
+    /// The number of cells the quad covers along each axis, which is always 1 along its normal.
+    pub size: [i32; 3],
+}
+
+/// The sides of a chunk that can touch another chunk.
+const SIDES: [Face; 4] = [Face::Left, Face::Right, Face::Front, Face::Back];
+
+/// The blocks of the neighbouring chunks that touch the sides of a chunk.
+///
+/// Faces at the sides of a chunk are culled against these blocks at full detail. Sides without
+/// a loaded neighbour fall back to `cull_border`.
+#[derive(Debug, Clone, Default)]
+pub struct Neighbours {
+    /// The wall of blocks facing every side in `SIDES`, one row of `CHUNK_SIZE` blocks per layer.
+    walls: [Option<Vec<BlockType>>; 4],
+}
+
+impl Neighbours {
+    /// Copy the blocks that face the chunk at `offset` out of the neighbours that `get` finds.
+    #[must_use]
+    pub fn new<'a>(offset: ChunkOffset, get: impl Fn(ChunkOffset) -> Option<&'a Chunk>) -> Self {
+        let walls = SIDES.map(|face| {
+            let [dx, dy, _] = face.normal();
+            let neighbour = get(ChunkOffset::new(offset.x() + dx, offset.y() + dy, 0).ok()?)?;
+            let wall = (0..CHUNK_HEIGHT)
+                .flat_map(|z| {
+                    return (0..CHUNK_SIZE).map(move |along| {
+                        // the wall is on the opposite side of the neighbour
+                        let (x, y) = match face {
+                            Face::Left => (CHUNK_SIZE - 1, along),
+                            Face::Right => (0, along),
+                            Face::Front => (along, 0),
+                            _ => (along, CHUNK_SIZE - 1),
+                        };
+                        return ChunkPos::new(x, y, z)
+                            .map_or(BlockType::Air, |pos| return neighbour.get(pos));
+                    });
+                })
+                .collect();
+            return Some(wall);
+        });
+        return Self { walls };
+    }
+
+    /// The block just outside the side `face` of the chunk, next to the cell `[x, y, z]`.
+    ///
+    /// Returns `None` if `face` is not a side or there is no neighbour on that side.
+    #[allow(clippy::cast_sign_loss, reason = "cells are inside the chunk")]
+    fn get(&self, face: Face, [x, y, z]: [i32; 3]) -> Option<BlockType> {
+        let side = SIDES.iter().position(|&side| return side == face)?;
+        let wall = self.walls[side].as_ref()?;
+        let along = match face {
+            Face::Left | Face::Right => y,
+            _ => x,
+        };
+        return wall.get((z * CHUNK_SIZE + along) as usize).copied();
+    }
+}
+
+/// Mesh every section of a chunk that has visible faces.
+///
+/// The quads are measured in cells of `level.scale()` blocks. `neighbours` is only used at full
+/// detail.
+#[must_use]
+pub fn sections(
+    chunk: &Chunk,
+    neighbours: &Neighbours,
+    level: LodLevel,
+    config: &EngineConfig,
+) -> Vec<(usize, Vec<Quad>)> {
+    if level == LodLevel::Full {
+        return chunk
+            .sections()
+            .map(|(i, _)| return (i, section(chunk, neighbours, i, config)))
+            .filter(|(_, quads)| return !quads.is_empty())
+            .collect();
+    }
+
+    let lod = LodChunk::from_chunk(chunk, level);
+    #[allow(clippy::cast_possible_wrap, reason = "skirts are shallow")]
+    let skirt = config.world_gen.lod.skirt as i32;
+    #[allow(clippy::cast_sign_loss, reason = "constants are positive")]
+    return (0..SECTIONS_PER_CHUNK as usize)
+        .map(|i| return (i, lod_section(&lod, i, skirt)))
+        .filter(|(_, quads)| return !quads.is_empty())
+        .collect();
+}
+
+/// Mesh a section of a chunk at full detail.
+///
+/// Faces are visible if the block they face is transparent and of another type. The top of the
+/// chunk is always open and the bottom never is, while the sides face the blocks of
+/// `neighbours`, or depend on `cull_border` where there is no neighbour. If culling is disabled,
+/// every face is visible except for those between translucent blocks of the same type.
+#[must_use]
+pub fn section(
+    chunk: &Chunk,
+    neighbours: &Neighbours,
+    index: usize,
+    config: &EngineConfig,
+) -> Vec<Quad> {
+    if chunk.section(index).is_none() {
+        return Vec::new();
+    }
+
+    let world_gen = &config.world_gen;
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let z = index as i32 * SECTION_SIZE;
+    let lo = [0, 0, z];
+    let hi = [CHUNK_SIZE, CHUNK_SIZE, z + SECTION_SIZE];
+    return greedy(lo, hi, |[x, y, z], face| {
+        let ty = chunk.get(ChunkPos::new(x, y, z).ok()?);
+        if ty == BlockType::Air {
+            return None;
+        }
+
+        let [dx, dy, dz] = face.normal();
+        let neighbour = match ChunkPos::new(x + dx, y + dy, z + dz) {
+            Ok(pos) => Some(chunk.get(pos)),
+            Err(_) => neighbours.get(face, [x, y, z]),
+        };
+        if !world_gen.culling {
+            // the inside of a body of water would be blended over itself
+            return (!ty.is_translucent() || neighbour != Some(ty)).then_some(ty);
+        }
+
+        let visible = match neighbour {
+            Some(neighbour) => neighbour.is_transparent() && neighbour != ty,
+            None => match face {
+                Face::Top => true,
+                Face::Bottom => false,
+                _ => !world_gen.cull_border,
+            },
+        };
+        return visible.then_some(ty);
+    });
+}
+
+/// Mesh the cells of a downsampled chunk that are in the section `index`.
+///
+/// Faces are visible if the cell they face is transparent and of another type, or if they are at
+/// the top of the chunk. The sides of the chunk are also visible down to `skirt` cells below the
+/// highest cell of their column, which covers the gaps to neighbours with a different level of
+/// detail.
+#[must_use]
+pub fn lod_section(chunk: &LodChunk, index: usize, skirt: i32) -> Vec<Quad> {
+    let (w, h) = chunk.size();
+    let cells = SECTION_SIZE / chunk.level().scale();
+    #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+    let z = index as i32 * cells;
+    if z >= h {
+        return Vec::new();
+    }
+
+    let is_solid = |x, y, z| {
+        return chunk
+            .get(x, y, z)
+            .is_some_and(|ty| return ty != BlockType::Air);
+    };
+    let tops = (0..w * w)
+        .map(|i| return (0..h).rev().find(|&z| return is_solid(i % w, i / w, z)))
+        .collect::<Vec<_>>();
+    #[allow(clippy::cast_sign_loss, reason = "positions are in the chunk")]
+    let top = |x: i32, y: i32| return tops[(y * w + x) as usize];
+
+    return greedy([0, 0, z], [w, w, z + cells], |[x, y, z], face| {
+        let ty = chunk.get(x, y, z)?;
+        if ty == BlockType::Air {
+            return None;
+        }
+
+        let [dx, dy, dz] = face.normal();
+        let visible = match chunk.get(x + dx, y + dy, z + dz) {
+            Some(neighbour) => neighbour.is_transparent() && neighbour != ty,
+            None => match face {
+                Face::Top => true,
+                Face::Bottom => false,
+                _ => top(x, y).is_some_and(|top| return z >= top - skirt),
+            },
+        };
+        return visible.then_some(ty);
+    });
+}
+
+/// Merge the visible faces of the cells in `lo..hi` into as few quads as possible.
+///
+/// `visible` returns the type of a face if it should be drawn. Each slice of faces is covered
+/// row by row, growing every quad as wide and then as high as faces of the same type allow.
+#[allow(
+    clippy::cast_sign_loss,
+    reason = "indices are relative to the lowest cell"
+)]
+fn greedy(
+    lo: [i32; 3],
+    hi: [i32; 3],
+    visible: impl Fn([i32; 3], Face) -> Option<BlockType>,
+) -> Vec<Quad> {
+    let mut quads = Vec::new();
+    for face in Face::ALL {
+        let [normal, u, v] = face.axes();
+        let (cols, rows) = (hi[u] - lo[u], hi[v] - lo[v]);
+        let index = |col: i32, row: i32| return (row * cols + col) as usize;
+        let cell = |depth: i32, col: i32, row: i32| {
+            let mut pos = [0; 3];
+            pos[normal] = depth;
+            pos[u] = lo[u] + col;
+            pos[v] = lo[v] + row;
+            return pos;
+        };
+
+        let mut mask = vec![None; (cols * rows) as usize];
+        for depth in lo[normal]..hi[normal] {
+            for row in 0..rows {
+                for col in 0..cols {
+                    mask[index(col, row)] = visible(cell(depth, col, row), face);
+                }
+            }
+
+            for row in 0..rows {
+                let mut col = 0;
+                while col < cols {
+                    let Some(ty) = mask[index(col, row)] else {
+                        col += 1;
+                        continue;
+                    };
+
+                    let mut width = 1;
+                    while col + width < cols && mask[index(col + width, row)] == Some(ty) {
+                        width += 1;
+                    }
+                    let mut height = 1;
+                    while row + height < rows
+                        && (col..col + width)
+                            .all(|c| return mask[index(c, row + height)] == Some(ty))
+                    {
+                        height += 1;
+                    }
+                    for covered in row..row + height {
+                        mask[index(col, covered)..index(col + width, covered)].fill(None);
+                    }
+
+                    let mut size = [1; 3];
+                    size[u] = width;
+                    size[v] = height;
+                    quads.push(Quad {
+                        ty,
+                        face,
+                        position: cell(depth, col, row),
+                        size,
+                    });
+                    col += width;
+                }
+            }
+        }
+    }
+    return quads;
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ge_util::ChunkOffset;
+
+    fn fill(chunk: &mut Chunk, lo: [i32; 3], hi: [i32; 3], ty: BlockType) {
+        for z in lo[2]..hi[2] {
+            for y in lo[1]..hi[1] {
+                for x in lo[0]..hi[0] {
+                    chunk.set(ChunkPos::new(x, y, z).unwrap(), ty);
+                }
+            }
+        }
+    }
+
+    fn count(quads: &[Quad], face: Face) -> usize {
+        return quads.iter().filter(|q| return q.face == face).count();
+    }
+
+    #[test]
+    fn single_block() {
+        let mut chunk = Chunk::new(ChunkOffset::default());
+        fill(&mut chunk, [3, 4, 20], [4, 5, 21], BlockType::Stone);
+        let quads = section(&chunk, &Neighbours::default(), 1, &EngineConfig::default());
+        assert_eq!(6, quads.len());
+        for face in Face::ALL {
+            assert_eq!(1, count(&quads, face));
+        }
+        assert!(quads
+            .iter()
+            .all(|q| return q.position == [3, 4, 20] && q.size == [1, 1, 1]));
+    }
+
+    #[test]
+    fn hidden_faces_are_merged_away() {
+        let mut chunk = Chunk::new(ChunkOffset::default());
+        fill(&mut chunk, [2, 2, 2], [5, 5, 5], BlockType::Stone);
+        let quads = section(&chunk, &Neighbours::default(), 0, &EngineConfig::default());
+        assert_eq!(6, quads.len());
+        let top = quads.iter().find(|q| return q.face == Face::Top).unwrap();
+        assert_eq!(([2, 2, 4], [3, 3, 1]), (top.position, top.size));
+        let right = quads.iter().find(|q| return q.face == Face::Right).unwrap();
+        assert_eq!(([4, 2, 2], [1, 3, 3]), (right.position, right.size));
+    }
+
+    #[test]
+    fn types_are_not_merged() {
+        let mut chunk = Chunk::new(ChunkOffset::default());
+        fill(&mut chunk, [0, 0, 10], [16, 16, 11], BlockType::Stone);
+        fill(&mut chunk, [7, 7, 10], [8, 8, 11], BlockType::Dirt);
+
+        let mut config = EngineConfig::default();
+        config.world_gen.cull_border = false;
+        let quads = section(&chunk, &Neighbours::default(), 0, &config);
+        // the stone around the dirt takes four quads, one on each side of it
+        assert_eq!(5, count(&quads, Face::Top));
+        assert_eq!(5, count(&quads, Face::Bottom));
+        for face in [Face::Left, Face::Right, Face::Front, Face::Back] {
+            assert_eq!(1, count(&quads, face));
+        }
+        let area = quads
+            .iter()
+            .filter(|q| return q.face == Face::Top)
+            .map(|q| return q.size[0] * q.size[1])
+            .sum::<i32>();
+        assert_eq!(256, area);
+
+        config.world_gen.cull_border = true;
+        assert_eq!(
+            10,
+            section(&chunk, &Neighbours::default(), 0, &config).len()
+        );
+        // without culling, every slice of the sides is drawn and the dirt splits one of them
+        config.world_gen.culling = false;
+        assert_eq!(
+            10 + 4 * (15 + 3),
+            section(&chunk, &Neighbours::default(), 0, &config).len()
+        );
+    }
+
+    #[test]
+    fn transparent_blocks() {
+        let mut chunk = Chunk::new(ChunkOffset::default());
+        fill(&mut chunk, [5, 5, 5], [7, 6, 6], BlockType::Water);
+        fill(&mut chunk, [7, 5, 5], [8, 6, 6], BlockType::Stone);
+        let quads = section(&chunk, &Neighbours::default(), 0, &EngineConfig::default());
+
+        // no faces between the water blocks, but the stone can be seen through the water
+        let water = quads
+            .iter()
+            .filter(|q| return q.ty == BlockType::Water)
+            .collect::<Vec<_>>();
+        assert_eq!(5, water.len());
+        assert!(water.iter().all(|q| return q.face != Face::Right));
+        assert_eq!(6, quads.len() - water.len());
+
+        // translucent faces inside the water are never drawn, even without culling
+        let mut config = EngineConfig::default();
+        config.world_gen.culling = false;
+        let quads = section(&chunk, &Neighbours::default(), 0, &config);
+        let water = quads
+            .iter()
+            .filter(|q| return q.ty == BlockType::Water)
+            .collect::<Vec<_>>();
+        assert_eq!(6, water.len());
+        assert!(water
+            .iter()
+            .all(|q| return q.face != Face::Right || q.position[0] == 6));
+    }
+
+    #[test]
+    fn sections_share_their_border() {
+        let mut chunk = Chunk::new(ChunkOffset::default());
+        fill(&mut chunk, [3, 3, 14], [4, 4, 18], BlockType::Stone);
+        let config = EngineConfig::default();
+        let meshed = sections(&chunk, &Neighbours::default(), LodLevel::Full, &config);
+        assert_eq!(
+            vec![0, 1],
+            meshed.iter().map(|(i, _)| return *i).collect::<Vec<_>>()
+        );
+        assert_eq!(0, count(&meshed[0].1, Face::Top));
+        assert_eq!(0, count(&meshed[1].1, Face::Bottom));
+        assert_eq!(10, meshed[0].1.len() + meshed[1].1.len());
+    }
+
+    #[test]
+    fn sides_face_the_neighbours() {
+        let mut left = Chunk::new(ChunkOffset::default());
+        let mut right = Chunk::new(ChunkOffset::new(1, 0, 0).unwrap());
+        fill(&mut left, [0, 0, 0], [16, 16, 1], BlockType::Stone);
+        fill(&mut right, [0, 0, 0], [16, 16, 1], BlockType::Stone);
+        let mut config = EngineConfig::default();
+        config.world_gen.cull_border = true;
+        let mesh = |left: &Chunk| {
+            let neighbours = Neighbours::new(right.position, |offset| {
+                return (offset == left.position).then_some(left);
+            });
+            return section(&right, &neighbours, 0, &config);
+        };
+        assert_eq!(0, count(&mesh(&left), Face::Left));
+
+        // digging at the edge of the neighbour uncovers a face of this chunk
+        left.set(ChunkPos::new(15, 3, 0).unwrap(), BlockType::Air);
+        let quads = mesh(&left);
+        assert_eq!(1, count(&quads, Face::Left));
+        let face = quads.iter().find(|q| return q.face == Face::Left).unwrap();
+        assert_eq!(([0, 3, 0], [1, 1, 1]), (face.position, face.size));
+
+        // sides without a neighbour still follow `cull_border`
+        config.world_gen.cull_border = false;
+        let quads = section(&right, &Neighbours::default(), 0, &config);
+        assert_eq!(1, count(&quads, Face::Left));
+        assert_eq!(1, count(&quads, Face::Right));
+    }
+
+    #[test]
+    fn lod() {
+        let mut chunk = Chunk::new(ChunkOffset::default());
+        fill(&mut chunk, [0, 0, 0], [16, 16, 16], BlockType::Stone);
+        let lod = LodChunk::from_chunk(&chunk, LodLevel::Half);
+
+        // the bottom is never visible and the top is a single quad of 8x8 cells
+        let quads = lod_section(&lod, 0, 0);
+        assert_eq!(5, quads.len());
+        let top = quads.iter().find(|q| return q.face == Face::Top).unwrap();
+        assert_eq!(([0, 0, 7], [8, 8, 1]), (top.position, top.size));
+        // only the highest cell of the sides is visible without a skirt
+        assert!(quads
+            .iter()
+            .filter(|q| return q.face != Face::Top)
+            .all(|q| return q.size[2] == 1));
+        assert!(lod_section(&lod, 1, 0).is_empty());
+
+        let mut config = EngineConfig::default();
+        config.world_gen.lod.skirt = 2;
+        let meshed = sections(&chunk, &Neighbours::default(), LodLevel::Half, &config);
+        assert_eq!(1, meshed.len());
+        assert!(meshed[0]
+            .1
+            .iter()
+            .filter(|q| return q.face == Face::Left)
+            .all(|q| return q.size[2] == 3));
+    }
+}
@@ -2,7 +2,6 @@
 use ge_util::EngineConfig;
 use rand::{seq::SliceRandom, SeedableRng};
 
-pub const DEFAULT_SEED: u64 = 0;
 pub const MAX_OCTAVES: usize = 32;
 pub const SIZE: usize = 256;
 pub const MASK: usize = SIZE - 1;
@@ -159,6 +158,33 @@ impl Noise {
         return sum;
     }
 
+    /// Evaluate `fbm` at a batch of points that share the same `z`, writing the results to `out`.
+    ///
+    /// Each octave is evaluated for the whole batch before moving on to the next one, which keeps
+    /// the inner loop free of branches and easy for the compiler to vectorise. The results are
+    /// identical to calling `fbm` for every point.
+    ///
+    /// # Panics
+    /// Panics if `xs`, `ys` and `out` do not have the same length.
+    pub fn fbm_batch(&self, xs: &[f32], ys: &[f32], z: f32, out: &mut [f32]) {
+        assert!(
+            xs.len() == ys.len() && xs.len() == out.len(),
+            "batch lengths differ"
+        );
+
+        let mut freq = 1.0 / self.frequency;
+        let mut amp = self.amplitude;
+        out.fill(0.0);
+
+        for _ in 0..self.octaves {
+            for ((sum, &x), &y) in out.iter_mut().zip(xs).zip(ys) {
+                *sum += self.eval(x * freq, y * freq, z * freq) * amp;
+            }
+            freq *= self.lacunarity;
+            amp *= self.persistence;
+        }
+    }
+
     #[must_use]
     pub fn seed(&self) -> u64 {
         return self.seed;
@@ -168,7 +194,7 @@ impl Noise {
 impl From<&EngineConfig> for Noise {
     fn from(value: &EngineConfig) -> Self {
         return Self::new(
-            DEFAULT_SEED,
+            value.world_gen.seed.derive("terrain").value(),
             value.world_gen.noise.octaves,
             value.world_gen.noise.frequency,
             value.world_gen.noise.amplitude,
@@ -177,3 +203,26 @@ impl From<&EngineConfig> for Noise {
         );
     }
 }
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batch_matches_fbm() {
+        let noise = Noise::new(42, 6, 16.0, 12.0, 2.0, 0.5);
+        let xs = (0..100)
+            .map(|i| return i as f32 * 1.7 - 80.0)
+            .collect::<Vec<_>>();
+        let ys = (0..100)
+            .map(|i| return (i * i) as f32 * 0.3)
+            .collect::<Vec<_>>();
+        let mut out = vec![f32::NAN; xs.len()];
+        noise.fbm_batch(&xs, &ys, 0.5, &mut out);
+
+        for ((&x, &y), &v) in xs.iter().zip(&ys).zip(&out) {
+            assert_eq!(noise.fbm(x, y, 0.5).to_bits(), v.to_bits());
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::BlockType;
+use ge_util::coords::{CHUNK_SIZE, SECTION_SIZE};
+
+#[allow(clippy::cast_sign_loss, reason = "constants are positive")]
+const VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * SECTION_SIZE) as usize;
+
+/// A `Section` is a `16x16x16` slice of a `Chunk`.
+///
+/// Chunks only store sections that contain at least one non-air block, so large parts of the
+/// world (e.g. the sky) take up no memory and are skipped entirely by generation and culling.
+#[allow(
+    missing_copy_implementations,
+    reason = "sections are large and should not be copied implicitly"
+)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    blocks: [BlockType; VOLUME],
+    non_air: usize,
+}
+
+impl Default for Section {
+    fn default() -> Self {
+        return Self {
+            blocks: [BlockType::Air; VOLUME],
+            non_air: 0,
+        };
+    }
+}
+
+impl Section {
+    #[allow(clippy::cast_sign_loss, reason = "positions are checked by the caller")]
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        debug_assert!(
+            (0..CHUNK_SIZE).contains(&x)
+                && (0..CHUNK_SIZE).contains(&y)
+                && (0..SECTION_SIZE).contains(&z),
+            "section position out of range"
+        );
+        return ((z * CHUNK_SIZE + y) * CHUNK_SIZE + x) as usize;
+    }
+
+    /// Get the block type at a position relative to the bottom of the section.
+    #[must_use]
+    pub fn get(&self, x: i32, y: i32, z: i32) -> BlockType {
+        return self.blocks[Self::index(x, y, z)];
+    }
+
+    /// Set the block type at a position relative to the bottom of the section.
+    pub fn set(&mut self, x: i32, y: i32, z: i32, ty: BlockType) {
+        let blk = &mut self.blocks[Self::index(x, y, z)];
+        match (*blk == BlockType::Air, ty == BlockType::Air) {
+            (true, false) => self.non_air += 1,
+            (false, true) => self.non_air -= 1,
+            _ => {}
+        }
+        *blk = ty;
+    }
+
+    /// The number of blocks in this section that are not air.
+    #[must_use]
+    pub fn non_air(&self) -> usize {
+        return self.non_air;
+    }
+
+    /// Returns `true` if every block in this section is air.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        return self.non_air == 0;
+    }
+
+    /// Iterate over the positions (relative to the section) and types of all non-air blocks.
+    pub fn iter(&self) -> impl Iterator<Item = ((i32, i32, i32), BlockType)> + '_ {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+        return self
+            .blocks
+            .iter()
+            .enumerate()
+            .filter(|(_, &ty)| return ty != BlockType::Air)
+            .map(|(i, &ty)| {
+                let i = i as i32;
+                let pos = (
+                    i % CHUNK_SIZE,
+                    (i / CHUNK_SIZE) % CHUNK_SIZE,
+                    i / (CHUNK_SIZE * CHUNK_SIZE),
+                );
+                return (pos, ty);
+            });
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use crate::{BlockType, Chunk};
+    use ge_util::{ChunkOffset, ChunkPos};
+
+    #[test]
+    fn sections_are_sparse() {
+        let mut chunk = Chunk::new(ChunkOffset::default());
+        assert_eq!(0, chunk.sections().count());
+
+        let pos = ChunkPos::new(3, 4, 37).unwrap();
+        chunk.set(pos, BlockType::Air);
+        assert_eq!(0, chunk.sections().count());
+
+        chunk.set(pos, BlockType::Stone);
+        chunk.set(ChunkPos::new(0, 0, 32).unwrap(), BlockType::Dirt);
+        assert_eq!(BlockType::Stone, chunk.get(pos));
+        assert_eq!(BlockType::Air, chunk.get(ChunkPos::new(3, 4, 36).unwrap()));
+        assert_eq!(
+            vec![2],
+            chunk.sections().map(|(i, _)| return i).collect::<Vec<_>>()
+        );
+        assert_eq!(2, chunk.section(2).unwrap().non_air());
+        assert_eq!(Some(37), chunk.highest(3, 4, |ty| return ty.is_opaque()));
+
+        let mut blocks = chunk
+            .blocks()
+            .map(|b| return (b.chunk_pos(), b.ty()))
+            .collect::<Vec<_>>();
+        blocks.sort_by_key(|(p, _)| return p.z());
+        assert_eq!(
+            vec![
+                (ChunkPos::new(0, 0, 32).unwrap(), BlockType::Dirt),
+                (pos, BlockType::Stone)
+            ],
+            blocks
+        );
+
+        // the section is freed once it only contains air again
+        chunk.set(pos, BlockType::Air);
+        chunk.set(ChunkPos::new(0, 0, 32).unwrap(), BlockType::Air);
+        assert_eq!(0, chunk.sections().count());
+    }
+}
@@ -0,0 +1,14 @@
+use ge_util::seed::splitmix;
+
+/// Hash a world seed together with a pair of world-space coordinates.
+///
+/// This is used to give every grid cell (e.g. an erosion tile or river source cell) its own
+/// deterministic seed, independent of the order in which cells are generated.
+#[allow(clippy::cast_sign_loss, reason = "only the bits are used")]
+pub(crate) fn hash_coords(seed: u64, x: i32, y: i32) -> u64 {
+    let mut h = seed;
+    for v in [u64::from(x as u32), u64::from(y as u32)] {
+        h = splitmix(h ^ v);
+    }
+    return h;
+}
@@ -0,0 +1,287 @@
+use super::{Stage, StageContext, StageError};
+use crate::{gen::Terrain, seed::hash_coords, BlockType, Chunk, ChunkTransformation};
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE},
+    ChunkPos, EngineConfig,
+};
+use rand::{Rng, SeedableRng};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashMap},
+};
+
+const NEIGHBOURS: [(i32, i32); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+/// A lake filling a local basin up to the height where it would spill over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lake {
+    /// The height of the water surface.
+    pub level: i32,
+    /// The world columns covered by the lake.
+    pub columns: BTreeSet<(i32, i32)>,
+}
+
+/// Fills local basins in the terrain with water, each at its own water level.
+///
+/// Like [`Rivers`](super::Rivers), lakes are found in world space from points placed on a grid.
+/// Each point flows downhill to the bottom of its basin, and the basin is flooded until the water
+/// would spill over its lowest rim. Every chunk computes the lakes that could reach it, so lakes
+/// are identical on both sides of a chunk border.
+#[derive(Debug, Clone)]
+pub struct Lakes {
+    terrain: Terrain,
+    sea_level: i32,
+    seed: u64,
+    spacing: i32,
+    max_radius: i32,
+    max_depth: i32,
+    min_size: usize,
+}
+
+impl Lakes {
+    #[must_use]
+    pub fn new(terrain: Terrain, config: &EngineConfig) -> Self {
+        let lakes = config.world_gen.lakes;
+        return Self {
+            terrain,
+            sea_level: config.world_gen.sea_level,
+            seed: config.world_gen.seed.derive("lakes").value(),
+            spacing: lakes.spacing.max(1),
+            max_radius: lakes.max_radius.max(1),
+            max_depth: lakes.max_depth.max(1),
+            min_size: lakes.min_size,
+        };
+    }
+
+    #[allow(clippy::cast_possible_truncation, reason = "truncation is expected")]
+    fn height(&self, x: i32, y: i32) -> i32 {
+        return self.terrain.surface_height(x, y) as i32;
+    }
+
+    /// Follow the terrain downhill from a point until reaching the bottom of a basin.
+    fn basin_bottom(&self, mut x: i32, mut y: i32) -> (i32, i32) {
+        for _ in 0..self.max_radius {
+            let here = self.terrain.surface_height(x, y);
+            let lowest = NEIGHBOURS
+                .iter()
+                .map(|(dx, dy)| return (x + dx, y + dy))
+                .map(|(nx, ny)| return (nx, ny, self.terrain.surface_height(nx, ny)))
+                .min_by(|a, b| return a.2.total_cmp(&b.2))
+                .expect("there are always neighbours");
+            if lowest.2 >= here {
+                break;
+            }
+            (x, y) = (lowest.0, lowest.1);
+        }
+        return (x, y);
+    }
+
+    /// Flood the basin around `bottom` and return the lake, if the basin holds one.
+    ///
+    /// Columns are visited lowest first. The first time a column is lower than the highest column
+    /// visited so far, the water has found a way out, so the lake is everything below that rim.
+    #[must_use]
+    pub fn flood(&self, bottom: (i32, i32)) -> Option<Lake> {
+        let floor = self.height(bottom.0, bottom.1);
+        let mut heights = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        heights.insert(bottom, floor);
+        queue.push(Reverse((floor, bottom)));
+
+        let mut rim = floor;
+        let mut visited = Vec::new();
+        while let Some(Reverse((height, (x, y)))) = queue.pop() {
+            if height < rim {
+                // the water spills over the rim here
+                break;
+            }
+            if height - floor > self.max_depth {
+                // the basin is too deep, so cap the lake at the deepest allowed level
+                break;
+            }
+            rim = height;
+            visited.push(((x, y), height));
+
+            for (nx, ny) in NEIGHBOURS.iter().map(|(dx, dy)| return (x + dx, y + dy)) {
+                if (nx - bottom.0).abs() > self.max_radius
+                    || (ny - bottom.1).abs() > self.max_radius
+                {
+                    // the basin is too wide to be flooded
+                    return None;
+                }
+                if heights.contains_key(&(nx, ny)) {
+                    continue;
+                }
+                let h = self.height(nx, ny);
+                heights.insert((nx, ny), h);
+                queue.push(Reverse((h, (nx, ny))));
+            }
+        }
+
+        let columns = visited
+            .into_iter()
+            .filter(|&(_, h)| return h < rim)
+            .map(|(p, _)| return p)
+            .collect::<BTreeSet<_>>();
+        if rim <= self.sea_level || columns.len() < self.min_size {
+            return None;
+        }
+
+        return Some(Lake {
+            level: rim,
+            columns,
+        });
+    }
+
+    /// Get the lake of a grid cell, if the cell has one.
+    #[must_use]
+    pub fn lake(&self, cx: i32, cy: i32) -> Option<Lake> {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(hash_coords(self.seed, cx, cy));
+        let x = cx * self.spacing + rng.gen_range(0..self.spacing);
+        let y = cy * self.spacing + rng.gen_range(0..self.spacing);
+        return self.flood(self.basin_bottom(x, y));
+    }
+
+    /// Get every lake that could cover part of the world-space rectangle `lo..hi`.
+    #[must_use]
+    pub fn lakes_near(&self, lo: (i32, i32), hi: (i32, i32)) -> Vec<Lake> {
+        // a lake is at most `max_radius` from its bottom, which is at most `max_radius` away
+        let reach = 2 * self.max_radius;
+        let cells = |lo: i32, hi: i32| {
+            return (lo - reach).div_euclid(self.spacing)..=(hi + reach).div_euclid(self.spacing);
+        };
+
+        let mut lakes = cells(lo.1, hi.1)
+            .flat_map(|cy| return cells(lo.0, hi.0).map(move |cx| return (cx, cy)))
+            .filter_map(|(cx, cy)| return self.lake(cx, cy))
+            .collect::<Vec<_>>();
+
+        // several cells can drain into the same basin
+        lakes.sort_by(|a, b| return a.columns.first().cmp(&b.columns.first()));
+        lakes.dedup();
+        return lakes;
+    }
+}
+
+impl Stage for Lakes {
+    const NAME: &'static str = "lakes";
+
+    /// The parameters replace the `world_gen.lakes` settings of the config.
+    fn build(cx: &StageContext<'_>, params: &toml::Table) -> Result<Self, StageError> {
+        let mut config = cx.config.clone();
+        config.world_gen.lakes = super::params(Self::NAME, &config.world_gen.lakes, params)?;
+        return Ok(Self::new(cx.terrain.clone(), &config));
+    }
+}
+
+impl ChunkTransformation for Lakes {
+    fn name(&self) -> &'static str {
+        return "lakes";
+    }
+
+    fn transform(&self, chunk: &mut Chunk) {
+        let lo = (
+            chunk.position.x() * CHUNK_SIZE,
+            chunk.position.y() * CHUNK_SIZE,
+        );
+        let hi = (lo.0 + CHUNK_SIZE - 1, lo.1 + CHUNK_SIZE - 1);
+
+        for lake in self.lakes_near(lo, hi) {
+            for &(wx, wy) in lake.columns.range((lo.0, lo.1)..=(hi.0, hi.1)) {
+                if wy < lo.1 || wy > hi.1 {
+                    continue;
+                }
+
+                let surface = self.height(wx, wy);
+                for z in (surface + 1).max(0)..=lake.level.min(CHUNK_HEIGHT - 1) {
+                    let pos = ChunkPos::new(wx - lo.0, wy - lo.1, z).unwrap();
+                    if chunk.get(pos) == BlockType::Air {
+                        chunk.set(pos, BlockType::Water);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gen::{ChunkGenerator, NoiseChunkGenerator},
+        noise::Noise,
+    };
+    use ge_util::ChunkOffset;
+
+    fn terrain() -> NoiseChunkGenerator {
+        let noise = Noise::new(5, 5, 16.0, 12.0, 2.0, 0.5);
+        return NoiseChunkGenerator::with_noise(noise, 100);
+    }
+
+    fn lakes() -> Lakes {
+        let mut config = EngineConfig::default();
+        config.world_gen.sea_level = 80;
+        config.world_gen.lakes.enabled = true;
+        config.world_gen.lakes.spacing = 16;
+        return Lakes::new(terrain().into(), &config);
+    }
+
+    #[test]
+    fn lakes_are_connected_and_enclosed() {
+        let lakes = lakes();
+        let found = lakes.lakes_near((0, 0), (63, 63));
+        assert!(!found.is_empty());
+
+        for lake in found {
+            // every column is under the water level and reachable from every other column
+            let mut stack = vec![*lake.columns.first().unwrap()];
+            let mut seen = BTreeSet::new();
+            while let Some((x, y)) = stack.pop() {
+                if !lake.columns.contains(&(x, y)) || !seen.insert((x, y)) {
+                    continue;
+                }
+                assert!(lakes.height(x, y) < lake.level);
+                for (dx, dy) in NEIGHBOURS {
+                    stack.push((x + dx, y + dy));
+                }
+            }
+            assert_eq!(seen, lake.columns);
+
+            // every column next to the lake is at or above the water level, so it cannot leak
+            for &(x, y) in &lake.columns {
+                for (dx, dy) in NEIGHBOURS {
+                    let n = (x + dx, y + dy);
+                    if !lake.columns.contains(&n) {
+                        assert!(lakes.height(n.0, n.1) >= lake.level);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn water_matches_across_chunks() {
+        let lakes = lakes();
+        let gen = terrain();
+        let mut chunks = HashMap::new();
+
+        for lake in lakes.lakes_near((0, 0), (63, 63)) {
+            for &(x, y) in &lake.columns {
+                let offset =
+                    ChunkOffset::new(x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE), 0)
+                        .unwrap();
+                let chunk = chunks.entry(offset).or_insert_with(|| {
+                    return gen.generate(offset).apply_transformation(&lakes);
+                });
+                let pos = ChunkPos::new(
+                    x.rem_euclid(CHUNK_SIZE),
+                    y.rem_euclid(CHUNK_SIZE),
+                    lake.level,
+                )
+                .unwrap();
+                assert_eq!(BlockType::Water, chunk.get(pos));
+            }
+        }
+    }
+}
@@ -1,32 +1,52 @@
+mod lakes;
+mod registry;
+mod rivers;
+mod script;
 mod sea_level;
 pub mod surface;
 
+pub use lakes::{Lake, Lakes};
+pub use registry::{params, stages, Stage, StageContext, StageError, TransformationRegistry};
+pub use rivers::{RiverPoint, Rivers};
+pub use script::{ScriptError, ScriptTransformation};
 pub use sea_level::SeaLevel;
 pub use surface::SimpleSurfacePainter;
 
-use crate::ChunkTransformation;
+use crate::{gen::Terrain, ChunkTransformation};
+use ge_util::EngineConfig;
+use std::{fmt, ops::Deref, sync::Arc};
 
-#[derive(Debug, Clone, Copy)]
-pub enum Transformation {
-    SeaLevel(SeaLevel),
-    SurfacePainter(SimpleSurfacePainter),
+/// Build the pipeline in `config` with the built-in stages, in the order they should be applied.
+///
+/// # Errors
+/// Errors with the first stage that cannot be built.
+pub fn pipeline(
+    terrain: &Terrain,
+    config: &EngineConfig,
+) -> Result<Vec<Transformation>, StageError> {
+    return TransformationRegistry::default().pipeline(terrain, config);
 }
 
-impl ChunkTransformation for Transformation {
-    fn name(&self) -> &'static str {
-        match self {
-            Self::SeaLevel(t) => return t.name(),
-            Self::SurfacePainter(t) => return t.name(),
-        }
+/// A stage of the world-gen pipeline, which can be shared between generator threads.
+#[derive(Clone)]
+pub struct Transformation(Arc<dyn ChunkTransformation + Send + Sync>);
+
+impl fmt::Debug for Transformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.debug_tuple("Transformation").field(&self.name()).finish();
     }
+}
+
+impl Deref for Transformation {
+    type Target = dyn ChunkTransformation + Send + Sync;
 
-    fn transform(&self, chunk: &mut crate::Chunk) {
-        match self {
-            Self::SeaLevel(t) => return t.transform(chunk),
-            Self::SurfacePainter(t) => return t.transform(chunk),
-        }
+    fn deref(&self) -> &Self::Target {
+        return &*self.0;
     }
 }
 
-impl_from_trns!(SeaLevel for SeaLevel);
-impl_from_trns!(SimpleSurfacePainter for SurfacePainter);
+impl<T: ChunkTransformation + Send + Sync + 'static> From<T> for Transformation {
+    fn from(trns: T) -> Self {
+        return Self(Arc::new(trns));
+    }
+}
@@ -0,0 +1,342 @@
+use super::{Lakes, Rivers, ScriptError, ScriptTransformation, SeaLevel, SimpleSurfacePainter};
+use crate::{gen::Terrain, trns::Transformation, ChunkTransformation};
+use ge_util::{EngineConfig, StageConfig};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::BTreeMap, fmt, sync::Arc};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StageError {
+    #[error("unknown stage '{name}', the registered stages are: {registered}")]
+    Unknown { name: String, registered: String },
+    #[error("invalid parameters for stage '{stage}': {error}")]
+    Params {
+        stage: String,
+        error: toml::de::Error,
+    },
+    #[error(transparent)]
+    Script(#[from] ScriptError),
+}
+
+/// Everything a stage can be built from, besides its own parameters.
+#[derive(Debug, Clone)]
+pub struct StageContext<'a> {
+    pub config: &'a EngineConfig,
+    /// The terrain of the world, for stages that look at the surface beyond the chunk.
+    pub terrain: Terrain,
+}
+
+/// A transformation that can be built by name from serialized parameters.
+pub trait Stage: ChunkTransformation + Send + Sync + Sized + 'static {
+    /// The name the stage is registered under.
+    const NAME: &'static str;
+
+    /// Build the stage from its parameters, which are empty if none were given.
+    ///
+    /// # Errors
+    /// Errors if the parameters are invalid.
+    fn build(cx: &StageContext<'_>, params: &toml::Table) -> Result<Self, StageError>;
+}
+
+type Factory = Arc<
+    dyn Fn(&StageContext<'_>, &toml::Table) -> Result<Transformation, StageError> + Send + Sync,
+>;
+
+/// Maps the names of stages to the factories that build them.
+///
+/// `TransformationRegistry::default()` contains every built-in stage. Other crates can add their
+/// own stages with `register_stage` or `register`, or replace the built-in ones by registering a
+/// stage under the same name.
+#[derive(Clone)]
+pub struct TransformationRegistry {
+    factories: BTreeMap<String, Factory>,
+}
+
+impl fmt::Debug for TransformationRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f
+            .debug_struct("TransformationRegistry")
+            .field("stages", &self.factories.keys())
+            .finish();
+    }
+}
+
+impl Default for TransformationRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry
+            .register_stage::<SeaLevel>()
+            .register_stage::<Rivers>()
+            .register_stage::<Lakes>()
+            .register_stage::<SimpleSurfacePainter>()
+            .register_stage::<ScriptTransformation>();
+        return registry;
+    }
+}
+
+impl TransformationRegistry {
+    /// Create a registry without any stages.
+    #[must_use]
+    pub fn empty() -> Self {
+        return Self {
+            factories: BTreeMap::new(),
+        };
+    }
+
+    /// Register a factory under `name`, replacing any stage that was registered under it before.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        factory: impl Fn(&StageContext<'_>, &toml::Table) -> Result<Transformation, StageError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self {
+        self.factories.insert(name.into(), Arc::new(factory));
+        return self;
+    }
+
+    /// Register a stage under its name.
+    pub fn register_stage<S: Stage>(&mut self) -> &mut Self {
+        return self.register(
+            S::NAME,
+            |cx, params| return Ok(S::build(cx, params)?.into()),
+        );
+    }
+
+    #[must_use]
+    pub fn contains(&self, name: &str) -> bool {
+        return self.factories.contains_key(name);
+    }
+
+    /// The names of the registered stages, in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> + '_ {
+        return self.factories.keys().map(String::as_str);
+    }
+
+    /// Build a registered stage.
+    ///
+    /// # Errors
+    /// Errors if no stage is registered under `name` or the stage cannot be built.
+    pub fn build(
+        &self,
+        name: &str,
+        cx: &StageContext<'_>,
+        params: &toml::Table,
+    ) -> Result<Transformation, StageError> {
+        let Some(factory) = self.factories.get(name) else {
+            return Err(StageError::Unknown {
+                name: name.to_owned(),
+                registered: self.names().collect::<Vec<_>>().join(", "),
+            });
+        };
+        return factory(cx, params);
+    }
+
+    /// Build the stages of the world-gen pipeline in `config`, in the order they are applied.
+    ///
+    /// # Errors
+    /// Errors with the first stage that cannot be built.
+    pub fn pipeline(
+        &self,
+        terrain: &Terrain,
+        config: &EngineConfig,
+    ) -> Result<Vec<Transformation>, StageError> {
+        let cx = StageContext {
+            config,
+            terrain: terrain.clone(),
+        };
+        return stages(config)
+            .iter()
+            .map(|stage| return self.build(&stage.name, &cx, &stage.params))
+            .collect();
+    }
+}
+
+/// Get the stages of the pipeline in `config`.
+///
+/// If the config does not list any stages, these are the built-in stages that are enabled,
+/// followed by the scripts.
+#[must_use]
+pub fn stages(config: &EngineConfig) -> Vec<StageConfig> {
+    let world_gen = &config.world_gen;
+    if !world_gen.stages.is_empty() {
+        return world_gen.stages.clone();
+    }
+
+    let mut stages = vec![StageConfig::new(SeaLevel::NAME)];
+    if world_gen.rivers.enabled {
+        stages.push(StageConfig::new(Rivers::NAME));
+    }
+    if world_gen.lakes.enabled {
+        stages.push(StageConfig::new(Lakes::NAME));
+    }
+    stages.push(StageConfig::new(SimpleSurfacePainter::NAME));
+    for name in &world_gen.scripts.stages {
+        let mut stage = StageConfig::new(ScriptTransformation::NAME);
+        stage
+            .params
+            .insert("name".to_owned(), toml::Value::String(name.clone()));
+        stages.push(stage);
+    }
+    return stages;
+}
+
+/// Apply the parameters of a stage on top of its defaults.
+///
+/// Only the parameters that are given are replaced, so stages can be configured in the config
+/// and have single values changed in the pipeline.
+///
+/// # Errors
+/// Errors if the parameters do not fit `T`.
+#[allow(
+    clippy::missing_panics_doc,
+    reason = "the defaults can always be serialized"
+)]
+pub fn params<T: Serialize + DeserializeOwned>(
+    stage: &str,
+    defaults: &T,
+    params: &toml::Table,
+) -> Result<T, StageError> {
+    let mut table = toml::Table::try_from(defaults).expect("defaults can be serialized");
+    table.extend(params.clone());
+    return table.try_into().map_err(|error| {
+        return StageError::Params {
+            stage: stage.to_owned(),
+            error,
+        };
+    });
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gen::{AsyncWorldGenerator, NoiseChunkGenerator},
+        BlockType, Chunk,
+    };
+    use ge_util::{ChunkOffset, ChunkPos};
+
+    /// A stage that a crate outside of ge-world could add.
+    #[derive(Debug)]
+    struct Ceiling {
+        height: i32,
+    }
+
+    impl ChunkTransformation for Ceiling {
+        fn name(&self) -> &str {
+            return "ceiling";
+        }
+
+        fn transform(&self, chunk: &mut Chunk) {
+            for y in 0..16 {
+                for x in 0..16 {
+                    chunk.set(ChunkPos::new(x, y, self.height).unwrap(), BlockType::Wood);
+                }
+            }
+        }
+    }
+
+    fn stage(name: &str, params: &str) -> StageConfig {
+        return StageConfig {
+            name: name.to_owned(),
+            params: toml::from_str(params).unwrap(),
+        };
+    }
+
+    #[test]
+    fn builtin() {
+        let registry = TransformationRegistry::default();
+        assert_eq!(
+            vec!["lakes", "rivers", "script", "sea_level", "surface"],
+            registry.names().collect::<Vec<_>>()
+        );
+
+        let mut config = EngineConfig::default();
+        let terrain = NoiseChunkGenerator::with_noise((&config).into(), 100).into();
+        let names = |config: &EngineConfig| {
+            return registry
+                .pipeline(&terrain, config)
+                .unwrap()
+                .iter()
+                .map(|t| return t.name().to_owned())
+                .collect::<Vec<_>>();
+        };
+        assert_eq!(vec!["sea level", "surface painting"], names(&config));
+
+        config.world_gen.lakes.enabled = true;
+        assert_eq!(
+            vec!["sea level", "lakes", "surface painting"],
+            names(&config)
+        );
+
+        // an explicit list of stages replaces the built-in pipeline
+        config.world_gen.stages = vec![stage("surface", ""), stage("sea_level", "")];
+        assert_eq!(vec!["surface painting", "sea level"], names(&config));
+
+        // a single broken stage fails the whole pipeline
+        for broken in [
+            stage("missing", ""),
+            stage("sea_level", "sea_level = 'high'"),
+        ] {
+            config.world_gen.stages = vec![stage("surface", ""), broken];
+            assert!(registry.pipeline(&terrain, &config).is_err());
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let registry = TransformationRegistry::default();
+        let config = EngineConfig::default();
+        let cx = StageContext {
+            config: &config,
+            terrain: NoiseChunkGenerator::with_noise((&config).into(), 100).into(),
+        };
+
+        let unknown = registry
+            .build("caves", &cx, &toml::Table::new())
+            .unwrap_err();
+        assert!(
+            unknown.to_string().contains("sea_level, surface"),
+            "{unknown}"
+        );
+        assert!(matches!(
+            registry.build("sea_level", &cx, &stage("", "fill_water = 3").params),
+            Err(StageError::Params { .. })
+        ));
+        assert!(matches!(
+            registry.build("script", &cx, &toml::Table::new()),
+            Err(StageError::Params { .. })
+        ));
+    }
+
+    #[test]
+    fn custom_stage() {
+        let mut registry = TransformationRegistry::default();
+        registry.register("ceiling", |_, params| {
+            let height = params.get("height").and_then(toml::Value::as_integer);
+            return Ok(Ceiling {
+                height: height.unwrap_or(200) as i32,
+            }
+            .into());
+        });
+
+        let mut config = EngineConfig::default();
+        config.world_gen.sea_level = 150;
+        config.world_gen.stages = vec![
+            stage("sea_level", "fill_water = true"),
+            stage("ceiling", "height = 180"),
+        ];
+        let gen = AsyncWorldGenerator::with_registry((1, 1), &config, &registry).unwrap();
+        let chunk = gen.generate_chunk(ChunkOffset::default());
+        assert_eq!(
+            BlockType::Wood,
+            chunk.get(ChunkPos::new(3, 4, 180).unwrap())
+        );
+        assert_eq!(BlockType::Air, chunk.get(ChunkPos::new(3, 4, 200).unwrap()));
+        // `fill_water` was changed, but `sea_level` still comes from the config
+        let water = chunk.highest(3, 4, |ty| return ty == BlockType::Water);
+        assert_eq!(Some(150), water);
+    }
+}
@@ -0,0 +1,470 @@
+use super::{Stage, StageContext, StageError};
+use crate::{
+    erosion::TileCache, gen::Terrain, seed::hash_coords, BlockType, Chunk, ChunkTransformation,
+};
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE},
+    ChunkPos, EngineConfig,
+};
+use rand::{Rng, SeedableRng};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    sync::Arc,
+};
+
+/// The maximum number of columns explored when breaching a pit.
+const BREACH_LIMIT: usize = 4096;
+
+const NEIGHBOURS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// A single column along the path of a river.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RiverPoint {
+    pub x: i32,
+    pub y: i32,
+    /// The height of the water surface, this never increases along a river.
+    pub level: i32,
+}
+
+/// Carves rivers that flow from high terrain down towards sea level.
+///
+/// Rivers are traced in world space from sources placed on a grid, so each chunk traces every
+/// river that could reach it and carves the parts that fall within its bounds. This keeps rivers
+/// continuous across chunk borders without needing access to the neighbouring chunks. Traced
+/// paths are cached by source cell, so each river is only traced once.
+#[derive(Debug, Clone)]
+pub struct Rivers {
+    terrain: Terrain,
+    paths: Arc<TileCache<Vec<RiverPoint>>>,
+    sea_level: i32,
+    seed: u64,
+    spacing: i32,
+    frequency: f32,
+    source_height: i32,
+    max_length: u32,
+    width: i32,
+    depth: i32,
+}
+
+impl Rivers {
+    #[must_use]
+    pub fn new(terrain: Terrain, config: &EngineConfig) -> Self {
+        let rivers = config.world_gen.rivers;
+        return Self {
+            terrain,
+            paths: Arc::default(),
+            sea_level: config.world_gen.sea_level,
+            seed: config.world_gen.seed.derive("rivers").value(),
+            spacing: rivers.spacing.max(1),
+            frequency: rivers.frequency,
+            source_height: rivers.source_height,
+            max_length: rivers.max_length,
+            width: rivers.width.max(0),
+            depth: rivers.depth.max(1),
+        };
+    }
+
+    #[allow(clippy::cast_possible_truncation, reason = "truncation is expected")]
+    fn height(&self, x: i32, y: i32) -> i32 {
+        return self.terrain.surface_height(x, y) as i32;
+    }
+
+    /// Get the river source of a grid cell, if the cell has one.
+    #[must_use]
+    pub fn source(&self, cx: i32, cy: i32) -> Option<(i32, i32)> {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(hash_coords(self.seed, cx, cy));
+        if rng.gen::<f32>() >= self.frequency {
+            return None;
+        }
+
+        let x = cx * self.spacing + rng.gen_range(0..self.spacing);
+        let y = cy * self.spacing + rng.gen_range(0..self.spacing);
+        if self.height(x, y) < self.source_height {
+            return None;
+        }
+        return Some((x, y));
+    }
+
+    /// Get every grid cell whose river could flow into the world-space rectangle `lo..hi`.
+    fn cells_near(&self, lo: (i32, i32), hi: (i32, i32)) -> impl Iterator<Item = (i32, i32)> {
+        #[allow(clippy::cast_possible_wrap, reason = "value should be small")]
+        let reach = self.max_length as i32 + self.width;
+        let spacing = self.spacing;
+        let cells = move |lo: i32, hi: i32| {
+            return (lo - reach).div_euclid(spacing)..=(hi + reach).div_euclid(spacing);
+        };
+
+        return cells(lo.1, hi.1)
+            .flat_map(move |cy| return cells(lo.0, hi.0).map(move |cx| return (cx, cy)));
+    }
+
+    /// Get every river source that could flow into the world-space rectangle `lo..hi`.
+    #[must_use]
+    pub fn sources_near(&self, lo: (i32, i32), hi: (i32, i32)) -> Vec<(i32, i32)> {
+        return self
+            .cells_near(lo, hi)
+            .filter_map(|(cx, cy)| return self.source(cx, cy))
+            .collect();
+    }
+
+    /// Get the path of the river of a grid cell, which is empty if the cell has no source.
+    ///
+    /// The path is traced the first time it is needed and shared by every chunk it flows
+    /// through.
+    #[must_use]
+    pub fn path(&self, cx: i32, cy: i32) -> Arc<Vec<RiverPoint>> {
+        return self.paths.get((cx, cy), || {
+            return self
+                .source(cx, cy)
+                .map(|source| return self.trace(source))
+                .unwrap_or_default();
+        });
+    }
+
+    /// Trace the path of a river from its source.
+    ///
+    /// The river always moves to the lowest neighbouring column it has not visited yet. When it
+    /// ends up in a pit it breaches the lowest point of the surrounding rim and cuts through the
+    /// terrain, which is why the water level is tracked separately from the terrain height. The
+    /// river stops when it reaches sea level, its maximum length, or a pit it cannot escape.
+    #[must_use]
+    pub fn trace(&self, source: (i32, i32)) -> Vec<RiverPoint> {
+        let (mut x, mut y) = source;
+        let mut level = self.height(x, y);
+        let mut path = Vec::new();
+        let mut visited = HashSet::new();
+        visited.insert((x, y));
+
+        while path.len() < self.max_length as usize {
+            path.push(RiverPoint { x, y, level });
+            if level <= self.sea_level {
+                break;
+            }
+
+            let next = NEIGHBOURS
+                .iter()
+                .map(|(dx, dy)| return (x + dx, y + dy))
+                .filter(|p| return !visited.contains(p))
+                .map(|(nx, ny)| return (nx, ny, self.terrain.surface_height(nx, ny)))
+                .min_by(|a, b| return a.2.total_cmp(&b.2));
+
+            match next {
+                Some((nx, ny, _)) if self.height(nx, ny) <= level => {
+                    visited.insert((nx, ny));
+                    (x, y) = (nx, ny);
+                }
+                _ => {
+                    let Some(route) = self.breach((x, y), level, &visited) else {
+                        break;
+                    };
+                    // the river keeps its level while it cuts through the rim
+                    for &p in &route[..route.len() - 1] {
+                        visited.insert(p);
+                        path.push(RiverPoint {
+                            x: p.0,
+                            y: p.1,
+                            level,
+                        });
+                    }
+                    (x, y) = route[route.len() - 1];
+                    visited.insert((x, y));
+                }
+            }
+            level = level.min(self.height(x, y));
+        }
+
+        path.truncate(self.max_length as usize);
+        return path;
+    }
+
+    /// Find the cheapest route out of a pit to a column below the water level.
+    ///
+    /// Columns are explored lowest first, so the route crosses the rim at its lowest point. The
+    /// returned route excludes `from` and ends at the outlet.
+    fn breach(
+        &self,
+        from: (i32, i32),
+        level: i32,
+        visited: &HashSet<(i32, i32)>,
+    ) -> Option<Vec<(i32, i32)>> {
+        let mut parents = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        parents.insert(from, from);
+        queue.push(Reverse((level, from)));
+
+        let mut explored = 0;
+        while let Some(Reverse((height, p))) = queue.pop() {
+            if height < level {
+                let mut route = vec![p];
+                while let Some(&parent) = parents.get(route.last().unwrap()) {
+                    if parent == from {
+                        break;
+                    }
+                    route.push(parent);
+                }
+                route.reverse();
+                return Some(route);
+            }
+
+            explored += 1;
+            if explored > BREACH_LIMIT {
+                return None;
+            }
+
+            for n in NEIGHBOURS
+                .iter()
+                .map(|(dx, dy)| return (p.0 + dx, p.1 + dy))
+            {
+                if visited.contains(&n) || parents.contains_key(&n) {
+                    continue;
+                }
+                parents.insert(n, p);
+                queue.push(Reverse((self.height(n.0, n.1), n)));
+            }
+        }
+
+        return None;
+    }
+}
+
+impl Stage for Rivers {
+    const NAME: &'static str = "rivers";
+
+    /// The parameters replace the `world_gen.rivers` settings of the config.
+    fn build(cx: &StageContext<'_>, params: &toml::Table) -> Result<Self, StageError> {
+        let mut config = cx.config.clone();
+        config.world_gen.rivers = super::params(Self::NAME, &config.world_gen.rivers, params)?;
+        return Ok(Self::new(cx.terrain.clone(), &config));
+    }
+}
+
+impl ChunkTransformation for Rivers {
+    fn name(&self) -> &'static str {
+        return "rivers";
+    }
+
+    fn transform(&self, chunk: &mut Chunk) {
+        let lo = (
+            chunk.position.x() * CHUNK_SIZE,
+            chunk.position.y() * CHUNK_SIZE,
+        );
+        let hi = (lo.0 + CHUNK_SIZE - 1, lo.1 + CHUNK_SIZE - 1);
+
+        // where rivers overlap the lowest water level wins
+        let mut columns: HashMap<(i32, i32), i32> = HashMap::new();
+        for (cx, cy) in self.cells_near(lo, hi) {
+            for point in self.path(cx, cy).iter() {
+                for (dx, dy) in (-self.width..=self.width)
+                    .flat_map(|dy| return (-self.width..=self.width).map(move |dx| return (dx, dy)))
+                    .filter(|(dx, dy)| return dx * dx + dy * dy <= self.width * self.width)
+                {
+                    let (wx, wy) = (point.x + dx, point.y + dy);
+                    if wx < lo.0 || wx > hi.0 || wy < lo.1 || wy > hi.1 {
+                        continue;
+                    }
+                    columns
+                        .entry((wx, wy))
+                        .and_modify(|level| *level = (*level).min(point.level))
+                        .or_insert(point.level);
+                }
+            }
+        }
+
+        for ((wx, wy), level) in columns {
+            // cut the terrain down to the water level and fill the bed with water
+            let surface = self.height(wx, wy);
+            let bed = surface.min(level - self.depth);
+            for z in (bed + 1).max(0)..=surface.max(level).min(CHUNK_HEIGHT - 1) {
+                let ty = if z <= level {
+                    BlockType::Water
+                } else {
+                    BlockType::Air
+                };
+                let pos = ChunkPos::new(wx - lo.0, wy - lo.1, z).unwrap();
+                chunk.set(pos, ty);
+            }
+        }
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        erosion::{ErodedTerrain, Erosion},
+        gen::{AsyncWorldGenerator, ChunkGenerator, NoiseChunkGenerator, TerrainGenerator},
+        noise::Noise,
+        trns::TransformationRegistry,
+    };
+    use ge_util::{ChunkOffset, StageConfig};
+    use std::sync::Arc;
+
+    fn terrain() -> NoiseChunkGenerator {
+        let noise = Noise::new(3, 5, 16.0, 12.0, 2.0, 0.5);
+        return NoiseChunkGenerator::with_noise(noise, 100);
+    }
+
+    fn config() -> EngineConfig {
+        let mut config = EngineConfig::default();
+        config.world_gen.sea_level = 90;
+        config.world_gen.rivers.enabled = true;
+        config.world_gen.rivers.frequency = 1.0;
+        config.world_gen.rivers.spacing = 32;
+        config.world_gen.rivers.max_length = 128;
+        return config;
+    }
+
+    fn rivers() -> Rivers {
+        return Rivers::new(terrain().into(), &config());
+    }
+
+    #[test]
+    fn paths_are_connected_and_flow_downhill() {
+        let rivers = rivers();
+        let sources = rivers.sources_near((0, 0), (31, 31));
+        assert!(!sources.is_empty());
+
+        let mut reached_sea = 0;
+        for source in sources {
+            let path = rivers.trace(source);
+            assert_eq!((path[0].x, path[0].y), source);
+            for pair in path.windows(2) {
+                let (a, b) = (pair[0], pair[1]);
+                assert!((a.x - b.x).abs() <= 1 && (a.y - b.y).abs() <= 1);
+                assert_ne!((a.x, a.y), (b.x, b.y));
+                assert!(b.level <= a.level);
+            }
+
+            assert!(path.len() <= rivers.max_length as usize);
+            if path.last().unwrap().level <= rivers.sea_level {
+                reached_sea += 1;
+            }
+        }
+        assert!(reached_sea > 0, "no rivers reach the sea");
+    }
+
+    #[test]
+    fn paths_are_traced_once() {
+        let rivers = rivers();
+        let (cx, cy) = rivers
+            .cells_near((0, 0), (31, 31))
+            .find(|&(cx, cy)| return rivers.source(cx, cy).is_some())
+            .unwrap();
+        let path = rivers.path(cx, cy);
+        assert_eq!(*path, rivers.trace(rivers.source(cx, cy).unwrap()));
+        assert!(Arc::ptr_eq(&path, &rivers.path(cx, cy)));
+        // clones of the stage share the traced paths
+        assert!(Arc::ptr_eq(&path, &rivers.clone().path(cx, cy)));
+    }
+
+    #[test]
+    fn water_is_continuous_across_chunks() {
+        let rivers = rivers();
+        let gen = terrain();
+        let chunk_of = |x: i32, y: i32| {
+            return ChunkOffset::new(x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE), 0)
+                .unwrap();
+        };
+
+        // find a river that flows from one chunk into another
+        let path = rivers
+            .sources_near((0, 0), (31, 31))
+            .into_iter()
+            .map(|source| return rivers.trace(source))
+            .find(|path| return path.len() > 1)
+            .unwrap();
+        let crossing = path
+            .windows(2)
+            .find(|pair| return chunk_of(pair[0].x, pair[0].y) != chunk_of(pair[1].x, pair[1].y))
+            .expect("river should cross a chunk border");
+
+        // the chunks on both sides of the border have water where the river flows
+        for point in crossing {
+            let offset = chunk_of(point.x, point.y);
+            let chunk = gen.generate(offset).apply_transformation(&rivers);
+            let has_water = (0..CHUNK_HEIGHT).any(|z| {
+                let pos = ChunkPos::new(
+                    point.x.rem_euclid(CHUNK_SIZE),
+                    point.y.rem_euclid(CHUNK_SIZE),
+                    z,
+                )
+                .unwrap();
+                return chunk.get(pos) == BlockType::Water;
+            });
+            assert!(has_water, "no water at {point:?}");
+        }
+    }
+
+    #[test]
+    fn rivers_follow_eroded_terrain() {
+        let mut config = config();
+        config.world_gen.erosion.enabled = true;
+        config.world_gen.erosion.iterations = 16;
+        config.world_gen.rivers.max_length = 48;
+        config.world_gen.stages = vec![StageConfig::new(Rivers::NAME)];
+        let registry = TransformationRegistry::default();
+        let gen = AsyncWorldGenerator::with_stages(terrain(), (1, 1), &config, &registry).unwrap();
+
+        // trace the rivers over the same eroded surface as the generator
+        let seed = config.world_gen.seed.derive("erosion").value();
+        let erosion = Erosion::new(config.world_gen.erosion, seed);
+        let eroded = ErodedTerrain::new(terrain(), erosion, Arc::default());
+        let rivers = Rivers::new(eroded.clone().into(), &config);
+        let path = rivers
+            .sources_near((0, 0), (31, 31))
+            .into_iter()
+            .map(|source| return rivers.trace(source))
+            .find(|path| return path.len() > 1)
+            .expect("a river flows near the origin");
+        assert!(path.iter().any(|p| {
+            return eroded.surface_height(p.x, p.y) != terrain().surface_height(p.x, p.y);
+        }));
+
+        let offset = ChunkOffset::new(
+            path[1].x.div_euclid(CHUNK_SIZE),
+            path[1].y.div_euclid(CHUNK_SIZE),
+            0,
+        )
+        .unwrap();
+        let chunk = gen.generate_chunk(offset);
+        let expected = eroded.generate(offset).apply_transformation(&rivers);
+        assert_eq!(expected, chunk);
+        // rivers traced over the surface from before the erosion do not fit the terrain
+        let raw = Rivers::new(terrain().into(), &config);
+        assert_ne!(eroded.generate(offset).apply_transformation(&raw), chunk);
+
+        for point in path.iter().filter(|p| {
+            return ChunkOffset::new(p.x.div_euclid(CHUNK_SIZE), p.y.div_euclid(CHUNK_SIZE), 0)
+                == Ok(offset);
+        }) {
+            let pos = |z| {
+                return ChunkPos::new(
+                    point.x.rem_euclid(CHUNK_SIZE),
+                    point.y.rem_euclid(CHUNK_SIZE),
+                    z,
+                )
+                .unwrap();
+            };
+            // the river is cut into the eroded surface, without any terrain left above its water
+            assert!(
+                (0..=point.level).any(|z| return chunk.get(pos(z)) == BlockType::Water),
+                "{point:?}"
+            );
+            assert!(
+                (point.level + 1..CHUNK_HEIGHT).all(|z| return chunk.get(pos(z)) == BlockType::Air),
+                "{point:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use super::{Stage, StageContext, StageError};
+use crate::{noise::Noise, seed::hash_coords, BlockType, Chunk, ChunkTransformation};
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE},
+    ChunkPos, EngineConfig, Seed,
+};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use rhai::{Engine, EvalAltResult, Scope, AST};
+use std::{fmt, path::PathBuf, sync::Arc};
+use thiserror::Error;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error("invalid script name '{0}', names may only contain letters, digits, '-' and '_'")]
+    InvalidName(String),
+    #[error("failed to read script '{}': {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("script '{name}' does not compile: {error}")]
+    Compile {
+        name: String,
+        error: rhai::ParseError,
+    },
+}
+
+/// A pipeline stage written in [Rhai](https://rhai.rs), loaded from `<dir>/<name>.rhai`.
+///
+/// The script runs once for every chunk, with these variables in scope:
+///
+/// - `chunk`, with `chunk.get(x, y, z)` and `chunk.set(x, y, z, block)` to read and write blocks
+///   by their name (`"stone"`, `"water"`, ...), `chunk.highest(x, y)` for the height of the
+///   highest solid block in a column (or -1), and `chunk.x` and `chunk.y` for the world position
+///   of the chunk's corner. Positions are relative to the chunk.
+/// - `rng`, a random number generator seeded by the world seed, the script name and the chunk, with
+///   `rng.float()` for a number in `0..1` and `rng.int(lo, hi)` for an integer in `lo..hi`.
+/// - `CHUNK_SIZE` and `CHUNK_HEIGHT`.
+///
+/// `noise(x, y, z)` samples gradient noise that is seeded by the world seed and the script name.
+///
+/// Scripts cannot reach anything outside of the chunk, and are stopped when they exceed the limits
+/// of the config. A script that fails leaves the chunk unchanged and logs where it failed.
+#[derive(Clone)]
+pub struct ScriptTransformation {
+    name: String,
+    engine: Arc<Engine>,
+    ast: Arc<AST>,
+    seed: u64,
+}
+
+impl fmt::Debug for ScriptTransformation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f
+            .debug_struct("ScriptTransformation")
+            .field("name", &self.name)
+            .finish_non_exhaustive();
+    }
+}
+
+impl ScriptTransformation {
+    /// Load the script `name` from the script directory of the config.
+    ///
+    /// # Errors
+    /// Errors if the name is not a plain file name, or the script cannot be read or compiled.
+    pub fn load(name: &str, config: &EngineConfig) -> Result<Self, ScriptError> {
+        let valid = |c: char| return c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if name.is_empty() || !name.chars().all(valid) {
+            return Err(ScriptError::InvalidName(name.to_owned()));
+        }
+        let path = config
+            .world_gen
+            .scripts
+            .dir
+            .join(name)
+            .with_extension("rhai");
+        let source = std::fs::read_to_string(&path)
+            .map_err(|source| return ScriptError::Io { path, source })?;
+        return Self::compile(name, &source, config);
+    }
+
+    /// Compile a script from its source.
+    ///
+    /// # Errors
+    /// Errors if the script does not compile.
+    pub fn compile(name: &str, source: &str, config: &EngineConfig) -> Result<Self, ScriptError> {
+        let seed = config.world_gen.seed.derive(&format!("script:{name}"));
+        let engine = Self::engine(name, seed, config.world_gen.scripts.max_operations);
+        let ast = engine.compile(source).map_err(|error| {
+            return ScriptError::Compile {
+                name: name.to_owned(),
+                error,
+            };
+        })?;
+        return Ok(Self {
+            name: name.to_owned(),
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+            seed: seed.value(),
+        });
+    }
+
+    /// Create a sandboxed engine with the chunk API.
+    fn engine(name: &str, seed: Seed, max_operations: u64) -> Engine {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(max_operations)
+            .set_max_call_levels(32)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(4096)
+            .set_max_array_size(1 << 16)
+            .set_max_map_size(1024)
+            .disable_symbol("eval");
+
+        let (print_name, debug_name) = (name.to_owned(), name.to_owned());
+        engine.on_print(move |text| info!("[{}] {}", print_name, text));
+        engine.on_debug(move |text, _, pos| debug!("[{}] {:?}: {}", debug_name, pos, text));
+
+        engine
+            .register_type_with_name::<ScriptChunk>("Chunk")
+            .register_get("x", |c: &mut ScriptChunk| return i64::from(c.origin.0))
+            .register_get("y", |c: &mut ScriptChunk| return i64::from(c.origin.1))
+            .register_fn("get", ScriptChunk::get)
+            .register_fn("set", ScriptChunk::set)
+            .register_fn("highest", ScriptChunk::highest);
+        engine
+            .register_type_with_name::<ScriptRng>("Rng")
+            .register_fn("float", |r: &mut ScriptRng| return r.0.gen::<f64>())
+            .register_fn("int", ScriptRng::int);
+
+        let noise = Noise::new(seed.derive("noise").value(), 1, 1.0, 1.0, 2.0, 0.5);
+        engine.register_fn("noise", move |x: f64, y: f64, z: f64| {
+            #[allow(clippy::cast_possible_truncation, reason = "noise is sampled as f32")]
+            return f64::from(noise.fbm(x as f32, y as f32, z as f32));
+        });
+        return engine;
+    }
+
+    /// Run the script on a copy of the chunk, so a script that fails halfway changes nothing.
+    fn run(&self, chunk: &Chunk) -> ScriptResult<Chunk> {
+        let offset = chunk.position;
+        let mut scope = Scope::new();
+        scope.push_constant("CHUNK_SIZE", i64::from(CHUNK_SIZE));
+        scope.push_constant("CHUNK_HEIGHT", i64::from(CHUNK_HEIGHT));
+        scope.push(
+            "rng",
+            ScriptRng(ChaCha8Rng::seed_from_u64(hash_coords(
+                self.seed,
+                offset.x(),
+                offset.y(),
+            ))),
+        );
+        scope.push(
+            "chunk",
+            ScriptChunk {
+                chunk: chunk.clone(),
+                origin: (offset.x() * CHUNK_SIZE, offset.y() * CHUNK_SIZE),
+            },
+        );
+
+        self.engine.run_ast_with_scope(&mut scope, &self.ast)?;
+        return scope
+            .get_value::<ScriptChunk>("chunk")
+            .map(|c| return c.chunk)
+            .ok_or_else(|| return "the `chunk` variable was replaced".into());
+    }
+}
+
+/// The parameters of a script stage.
+#[derive(Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScriptParams {
+    /// The name of the script to load.
+    name: String,
+}
+
+impl Stage for ScriptTransformation {
+    const NAME: &'static str = "script";
+
+    fn build(cx: &StageContext<'_>, params: &toml::Table) -> Result<Self, StageError> {
+        let params: ScriptParams =
+            toml::Value::Table(params.clone())
+                .try_into()
+                .map_err(|error| {
+                    return StageError::Params {
+                        stage: Self::NAME.to_owned(),
+                        error,
+                    };
+                })?;
+        return Ok(Self::load(&params.name, cx.config)?);
+    }
+}
+
+impl ChunkTransformation for ScriptTransformation {
+    fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    fn transform(&self, chunk: &mut Chunk) {
+        match self.run(chunk) {
+            Ok(transformed) => *chunk = transformed,
+            Err(e) => error!(
+                "script '{}' failed on chunk {:?}: {}",
+                self.name, chunk.position, e
+            ),
+        }
+    }
+}
+
+/// The chunk as it is seen by a script.
+#[derive(Debug, Clone)]
+struct ScriptChunk {
+    chunk: Chunk,
+    /// The world position of the corner of the chunk.
+    origin: (i32, i32),
+}
+
+impl ScriptChunk {
+    fn pos(x: i64, y: i64, z: i64) -> ScriptResult<ChunkPos> {
+        let pos = (i32::try_from(x), i32::try_from(y), i32::try_from(z));
+        return match pos {
+            (Ok(x), Ok(y), Ok(z)) => ChunkPos::new(x, y, z).ok(),
+            _ => None,
+        }
+        .ok_or_else(|| return format!("({x}, {y}, {z}) is outside of the chunk").into());
+    }
+
+    fn get(&mut self, x: i64, y: i64, z: i64) -> ScriptResult<String> {
+        return Ok(self.chunk.get(Self::pos(x, y, z)?).name().to_owned());
+    }
+
+    fn set(&mut self, x: i64, y: i64, z: i64, block: &str) -> ScriptResult<()> {
+        let Some(ty) = BlockType::from_name(block) else {
+            let names = BlockType::ALL.map(BlockType::name).join(", ");
+            return Err(format!("unknown block '{block}', expected one of {names}").into());
+        };
+        self.chunk.set(Self::pos(x, y, z)?, ty);
+        return Ok(());
+    }
+
+    fn highest(&mut self, x: i64, y: i64) -> ScriptResult<i64> {
+        let pos = Self::pos(x, y, 0)?;
+        return Ok(self
+            .chunk
+            .highest(pos.x(), pos.y(), |ty| return ty != BlockType::Air)
+            .map_or(-1, i64::from));
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ScriptRng(ChaCha8Rng);
+
+impl ScriptRng {
+    fn int(&mut self, lo: i64, hi: i64) -> ScriptResult<i64> {
+        if lo >= hi {
+            return Err(format!("rng.int({lo}, {hi}) needs lo < hi").into());
+        }
+        return Ok(self.0.gen_range(lo..hi));
+    }
+}
+
+#[allow(clippy::pedantic)]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ge_util::ChunkOffset;
+
+    fn chunk() -> Chunk {
+        let mut chunk = Chunk::new(ChunkOffset::new(1, -2, 0).unwrap());
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.fill_column(x, y, 0..100, BlockType::Stone);
+            }
+        }
+        return chunk;
+    }
+
+    fn compile(source: &str) -> ScriptTransformation {
+        return ScriptTransformation::compile("test", source, &EngineConfig::default()).unwrap();
+    }
+
+    #[test]
+    fn edits_the_chunk() {
+        let script = compile(
+            r#"
+            for x in 0..CHUNK_SIZE {
+                let z = chunk.highest(x, 0);
+                if chunk.get(x, 0, z) == "stone" {
+                    chunk.set(x, 0, z + 1, "wood");
+                }
+            }
+            if chunk.x == 16 && chunk.y == -32 {
+                chunk.set(0, 1, 0, "air");
+            }
+            "#,
+        );
+        let mut chunk = chunk();
+        script.transform(&mut chunk);
+
+        assert_eq!("test", script.name());
+        for x in 0..CHUNK_SIZE {
+            assert_eq!(
+                BlockType::Wood,
+                chunk.get(ChunkPos::new(x, 0, 100).unwrap())
+            );
+        }
+        assert_eq!(BlockType::Air, chunk.get(ChunkPos::new(0, 1, 0).unwrap()));
+        assert_eq!(BlockType::Air, chunk.get(ChunkPos::new(0, 1, 100).unwrap()));
+    }
+
+    #[test]
+    fn deterministic() {
+        let script = compile(
+            r#"
+            let n = noise(chunk.x / 7.0, chunk.y / 7.0, 0.5);
+            for i in 0..20 {
+                chunk.set(rng.int(0, 16), rng.int(0, 16), 120 + rng.int(0, 10), "dirt");
+            }
+            if rng.float() < 2.0 && n > -10.0 {
+                chunk.set(0, 0, 200, "grass");
+            }
+            "#,
+        );
+        let (mut a, mut b) = (chunk(), chunk());
+        script.transform(&mut a);
+        script.transform(&mut b);
+        assert_eq!(a, b);
+        assert_ne!(chunk(), a);
+        assert_eq!(BlockType::Grass, a.get(ChunkPos::new(0, 0, 200).unwrap()));
+    }
+
+    #[test]
+    fn errors() {
+        let Err(ScriptError::Compile { name, error }) =
+            ScriptTransformation::compile("broken", "let x = ;", &EngineConfig::default())
+        else {
+            panic!("script should not compile");
+        };
+        assert_eq!("broken", name);
+        assert_eq!(Some(1), error.1.line());
+
+        // runtime errors leave the chunk unchanged
+        for source in [
+            r#"chunk.set(0, 0, 150, "wood"); chunk.set(0, 0, 300, "wood");"#,
+            r#"chunk.set(0, 0, 150, "wood"); chunk.set(0, 0, 150, "gold");"#,
+            r#"chunk.set(0, 0, 150, "wood"); loop {}"#,
+        ] {
+            let script = compile(source);
+            assert!(script.run(&chunk()).is_err(), "{source}");
+            let mut unchanged = chunk();
+            script.transform(&mut unchanged);
+            assert_eq!(chunk(), unchanged);
+        }
+
+        let config = EngineConfig::default();
+        assert!(matches!(
+            ScriptTransformation::load("../secret", &config),
+            Err(ScriptError::InvalidName(_))
+        ));
+        assert!(matches!(
+            ScriptTransformation::load("missing", &config),
+            Err(ScriptError::Io { .. })
+        ));
+    }
+}
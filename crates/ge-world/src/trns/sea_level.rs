@@ -1,8 +1,12 @@
-use ge_util::EngineConfig;
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE},
+    ChunkPos, EngineConfig,
+};
 
-use crate::ChunkTransformation;
+use super::{Stage, StageContext, StageError};
+use crate::{BlockType, ChunkTransformation};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
 pub struct SeaLevel {
     sea_level: i32,
     fill_water: bool,
@@ -20,23 +24,30 @@ impl SeaLevel {
     }
 }
 
+impl Stage for SeaLevel {
+    const NAME: &'static str = "sea_level";
+
+    fn build(cx: &StageContext<'_>, params: &toml::Table) -> Result<Self, StageError> {
+        return super::params(Self::NAME, &Self::new(cx.config), params);
+    }
+}
+
 impl ChunkTransformation for SeaLevel {
     fn name(&self) -> &'static str {
         return "sea level";
     }
 
     fn transform(&self, chunk: &mut crate::Chunk) {
-        chunk
-            .blocks
-            .iter_mut()
-            .filter(|(_, blk)| {
-                return if self.fill_water {
-                    blk.chunk_pos().z() <= self.sea_level
-                } else {
-                    blk.chunk_pos().z() == self.sea_level
-                };
-            })
-            .filter(|(_, blk)| return blk.ty() == crate::BlockType::Air)
-            .for_each(|(_, blk)| blk.update(crate::BlockType::Water));
+        let lo = if self.fill_water { 0 } else { self.sea_level };
+        for z in lo.max(0)..=self.sea_level.min(CHUNK_HEIGHT - 1) {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    let pos = ChunkPos::new(x, y, z).unwrap();
+                    if chunk.get(pos) == BlockType::Air {
+                        chunk.set(pos, BlockType::Water);
+                    }
+                }
+            }
+        }
     }
 }
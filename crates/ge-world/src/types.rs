@@ -1,5 +1,9 @@
-use ge_util::{ChunkOffset, ChunkPos, EngineConfig, WorldPos};
-use std::collections::HashMap;
+use crate::section::Section;
+use ge_util::{
+    coords::{CHUNK_HEIGHT, SECTIONS_PER_CHUNK, SECTION_SIZE},
+    ChunkOffset, ChunkPos, EngineConfig, WorldPos,
+};
+use std::ops::Range;
 
 /// A `World` is a collection of `Block`s.
 #[derive(Debug, Clone)]
@@ -8,30 +12,173 @@ pub struct World {
 }
 
 impl World {
-    /// Flatten all the chunks into a list of blocks using `WorldPos`.
+    /// Flatten all the chunks into a list of non-air blocks using `WorldPos`.
     #[must_use]
     pub fn into_world_blocks(&self) -> Vec<Block> {
-        return self
-            .chunks
-            .iter()
-            .flat_map(|c| {
-                // dbg!(c.position);
-                return c.blocks.values().map(|b| return *b);
-            })
-            .collect();
+        return self.chunks.iter().flat_map(Chunk::blocks).collect();
+    }
+
+    /// Get a loaded chunk by its offset.
+    #[must_use]
+    pub fn chunk(&self, offset: ChunkOffset) -> Option<&Chunk> {
+        return self.chunks.iter().find(|c| return c.position == offset);
+    }
+
+    /// Get a mutable reference to a loaded chunk by its offset.
+    pub fn chunk_mut(&mut self, offset: ChunkOffset) -> Option<&mut Chunk> {
+        return self.chunks.iter_mut().find(|c| return c.position == offset);
+    }
+
+    /// Get the type of the block at a position, or `None` if its chunk is not loaded.
+    #[must_use]
+    pub fn get(&self, pos: WorldPos) -> Option<BlockType> {
+        return Some(self.chunk(pos.to_chunk_offset())?.get(pos.to_chunk_pos()));
+    }
+
+    /// Set the type of the block at a position and return the type it had before.
+    ///
+    /// Returns `None` and leaves the world unchanged if the chunk is not loaded.
+    pub fn set(&mut self, pos: WorldPos, ty: BlockType) -> Option<BlockType> {
+        let chunk = self.chunk_mut(pos.to_chunk_offset())?;
+        let before = chunk.get(pos.to_chunk_pos());
+        chunk.set(pos.to_chunk_pos(), ty);
+        return Some(before);
     }
 }
 
-/// A `Chunk` is a collection of `Block`s with a fixed size.
-#[derive(Debug, Clone)]
+/// A `Chunk` is a column of `Section`s with a fixed size.
+///
+/// Sections that only contain air are not stored.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Chunk {
-    pub blocks: HashMap<ChunkPos, Block>,
+    sections: Vec<Option<Box<Section>>>,
     pub position: ChunkOffset,
 }
 
 impl Chunk {
+    /// Create a new chunk filled with air.
+    #[must_use]
+    pub fn new(position: impl Into<ChunkOffset>) -> Self {
+        #[allow(clippy::cast_sign_loss, reason = "constant is positive")]
+        return Self {
+            sections: vec![None; SECTIONS_PER_CHUNK as usize],
+            position: position.into(),
+        };
+    }
+
+    #[allow(clippy::cast_sign_loss, reason = "chunk positions are never negative")]
+    fn section_index(pos: ChunkPos) -> usize {
+        return (pos.z() / SECTION_SIZE) as usize;
+    }
+
+    /// Get the type of the block at a position.
+    #[must_use]
+    pub fn get(&self, pos: ChunkPos) -> BlockType {
+        return match &self.sections[Self::section_index(pos)] {
+            Some(section) => section.get(pos.x(), pos.y(), pos.z() % SECTION_SIZE),
+            None => BlockType::Air,
+        };
+    }
+
+    /// Get the block at a position.
+    #[must_use]
+    pub fn block(&self, pos: ChunkPos) -> Block {
+        return Block::new(self.get(pos), pos, self.position);
+    }
+
+    /// Set the type of the block at a position.
+    ///
+    /// Sections are allocated when the first non-air block is placed in them and freed again once
+    /// they only contain air.
+    pub fn set(&mut self, pos: ChunkPos, ty: BlockType) {
+        let slot = &mut self.sections[Self::section_index(pos)];
+        if slot.is_none() && ty == BlockType::Air {
+            return;
+        }
+
+        let section = slot.get_or_insert_with(Default::default);
+        section.set(pos.x(), pos.y(), pos.z() % SECTION_SIZE, ty);
+        if section.is_empty() {
+            *slot = None;
+        }
+    }
+
+    /// Set every block in a column from `zs.start` up to (but not including) `zs.end`.
+    ///
+    /// This is much faster than calling `set` for every block, and the range is clamped to the
+    /// height of the chunk.
+    #[allow(clippy::cast_sign_loss, reason = "the range is clamped first")]
+    pub fn fill_column(&mut self, x: i32, y: i32, zs: Range<i32>, ty: BlockType) {
+        let (start, end) = (zs.start.max(0), zs.end.min(CHUNK_HEIGHT));
+        let mut z = start;
+        while z < end {
+            let base = z - z % SECTION_SIZE;
+            let top = (base + SECTION_SIZE).min(end);
+            let slot = &mut self.sections[(base / SECTION_SIZE) as usize];
+            if slot.is_some() || ty != BlockType::Air {
+                let section = slot.get_or_insert_with(Default::default);
+                for local in z - base..top - base {
+                    section.set(x, y, local, ty);
+                }
+                if section.is_empty() {
+                    *slot = None;
+                }
+            }
+            z = top;
+        }
+    }
+
+    /// Get a section by its index, counting up from the bottom of the chunk.
+    ///
+    /// Returns `None` if the section only contains air.
+    #[must_use]
+    pub fn section(&self, index: usize) -> Option<&Section> {
+        return self.sections.get(index)?.as_deref();
+    }
+
+    /// Iterate over all stored (non-empty) sections and their indices.
+    #[must_use]
+    pub fn sections(&self) -> impl DoubleEndedIterator<Item = (usize, &Section)> + '_ {
+        return self
+            .sections
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| return Some((i, s.as_deref()?)));
+    }
+
+    /// Iterate over all non-air blocks in the chunk.
+    #[allow(
+        clippy::missing_panics_doc,
+        reason = "sections are always inside the chunk"
+    )]
+    pub fn blocks(&self) -> impl Iterator<Item = Block> + '_ {
+        return self.sections().flat_map(move |(i, section)| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+            let base_z = i as i32 * SECTION_SIZE;
+            return section.iter().map(move |((x, y, z), ty)| {
+                let pos = ChunkPos::new(x, y, base_z + z).expect("section is inside the chunk");
+                return Block::new(ty, pos, self.position);
+            });
+        });
+    }
+
+    /// Get the height of the highest block in a column that matches `f`.
     #[must_use]
-    pub fn visible_blocks(&self, config: &EngineConfig) -> Vec<&Block> {
+    pub fn highest(&self, x: i32, y: i32, f: impl Fn(BlockType) -> bool) -> Option<i32> {
+        for (i, section) in self.sections().rev() {
+            if let Some(z) = (0..SECTION_SIZE)
+                .rev()
+                .find(|&z| return f(section.get(x, y, z)))
+            {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+                return Some(i as i32 * SECTION_SIZE + z);
+            }
+        }
+        return None;
+    }
+
+    #[must_use]
+    pub fn visible_blocks(&self, config: &EngineConfig) -> Vec<Block> {
         let neighbour_offsets: [(i32, i32, i32); 6] = [
             (0, 0, 1),
             (0, 0, -1),
@@ -43,61 +190,35 @@ impl Chunk {
 
         if !config.world_gen.culling {
             // if culling is disabled then return all blocks
-            return self.blocks.values().collect();
+            return self.blocks().collect();
         }
 
-        let mut visible_blocks = Vec::with_capacity(self.blocks.len());
-        for blk in self.blocks.values().filter(|blk| {
-            if !config.world_gen.cull_border {
-                return blk.chunk_pos.x() != 0
-                    || blk.chunk_pos.x() != 15
-                    || blk.chunk_pos.y() != 0
-                    || blk.chunk_pos.y() != 15;
-            }
+        // empty sections are never visited, so only blocks near the terrain are checked
+        let mut visible_blocks = Vec::new();
+        for blk in self.blocks() {
+            let pos = blk.chunk_pos;
 
-            // if we are culling the border then do nothing
-            return true;
-        }) {
-            // if the block is air then it is not visible
-            if blk.ty == BlockType::Air {
+            // the block is visible if it is at the edge of the chunk and the border is not culled
+            if !config.world_gen.cull_border
+                && (pos.x() == 0 || pos.x() == 15 || pos.y() == 0 || pos.y() == 15)
+            {
+                visible_blocks.push(blk);
                 continue;
             }
-            let neighbours = neighbour_offsets
+
+            // or if the block neighbours a transparent block
+            let exposed = neighbour_offsets
                 .iter()
                 .filter_map(|&o| {
-                    return ChunkPos::new(
-                        blk.chunk_pos.x() + o.0,
-                        blk.chunk_pos.y() + o.1,
-                        blk.chunk_pos.z() + o.2,
-                    )
-                    .ok();
+                    return ChunkPos::new(pos.x() + o.0, pos.y() + o.1, pos.z() + o.2).ok();
                 })
-                .filter_map(|o| return self.blocks.get(&o))
-                .collect::<Vec<_>>();
-
-            // if the block is at the edge of the chunk then it is visible
-            // or if the block neighbours an air block
-            let num_visible = neighbours
-                .iter()
-                .filter(|b| return b.ty.is_opaque())
-                .count();
+                .any(|o| return self.get(o).is_transparent());
 
-            if num_visible < neighbours.len() {
+            if exposed {
                 visible_blocks.push(blk);
             }
         }
 
-        if !config.world_gen.cull_border {
-            visible_blocks.extend(
-                self.blocks
-                    .iter()
-                    .filter(|(pos, _)| {
-                        return pos.x() == 0 || pos.x() == 15 || pos.y() == 0 || pos.y() == 15;
-                    })
-                    .map(|(_, b)| return b),
-            );
-        }
-
         return visible_blocks;
     }
 
@@ -109,7 +230,7 @@ impl Chunk {
 }
 
 pub trait ChunkTransformation {
-    fn name(&self) -> &'static str;
+    fn name(&self) -> &str;
 
     fn transform(&self, chunk: &mut Chunk);
 
@@ -136,12 +257,61 @@ pub enum BlockType {
 }
 
 impl BlockType {
+    /// Every block type, ordered by their ids.
+    pub const ALL: [BlockType; 7] = [
+        BlockType::Dev,
+        BlockType::Air,
+        BlockType::Dirt,
+        BlockType::Grass,
+        BlockType::Stone,
+        BlockType::Water,
+        BlockType::Wood,
+    ];
+
+    /// The id of the block type that is used when saving blocks to disk.
+    #[must_use]
+    pub fn id(self) -> u8 {
+        return self as u8;
+    }
+
+    /// Get a block type by its id.
+    #[must_use]
+    pub fn from_id(id: u8) -> Option<Self> {
+        return Self::ALL.get(usize::from(id)).copied();
+    }
+
+    /// The name of the block type, as used in scripts.
+    #[must_use]
+    pub fn name(self) -> &'static str {
+        return match self {
+            BlockType::Dev => "dev",
+            BlockType::Air => "air",
+            BlockType::Dirt => "dirt",
+            BlockType::Grass => "grass",
+            BlockType::Stone => "stone",
+            BlockType::Water => "water",
+            BlockType::Wood => "wood",
+        };
+    }
+
+    /// Get a block type by its name.
+    #[must_use]
+    pub fn from_name(name: &str) -> Option<Self> {
+        return Self::ALL.into_iter().find(|ty| return ty.name() == name);
+    }
+
     /// Returns `true` if the block is (at least partially) transparent.
     #[must_use]
     pub fn is_transparent(&self) -> bool {
         return matches!(self, BlockType::Air | BlockType::Water);
     }
 
+    /// Returns `true` if the block is drawn see-through, after every opaque block.
+    #[must_use]
+    pub fn is_translucent(&self) -> bool {
+        return matches!(self, BlockType::Water);
+    }
+
     /// Returns `true` if the block is fully opaque.
     #[must_use]
     pub fn is_opaque(&self) -> bool {
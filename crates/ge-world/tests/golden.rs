@@ -0,0 +1,148 @@
+//! Golden tests that make sure world generation stays reproducible.
+//!
+//! A fixed set of chunks is generated for fixed seeds, and the block contents of every chunk are
+//! hashed and compared with the hashes in `tests/goldens.txt`. When a change to the generator is
+//! intended to change the world, regenerate the goldens with:
+//!
+//! ```text
+//! GE_UPDATE_GOLDENS=1 cargo test -p ge-world --test golden
+//! ```
+#![deny(clippy::implicit_return)]
+#![allow(clippy::needless_return)]
+#![allow(clippy::pedantic)]
+
+use ge_util::{
+    coords::{CHUNK_HEIGHT, CHUNK_SIZE},
+    ChunkOffset, ChunkPos, EngineConfig, Seed,
+};
+use ge_world::{
+    gen::{
+        AsyncWorldGenerator, ChunkGenerator, FixedWorldGenerator, NoiseChunkGenerator,
+        WorldGenerator,
+    },
+    noise::Noise,
+    trns::{SeaLevel, SimpleSurfacePainter},
+    Chunk, ChunkTransformation,
+};
+use std::{collections::BTreeMap, path::PathBuf};
+
+const SEEDS: [&str; 3] = ["0", "1234", "golden"];
+const OFFSETS: [(i32, i32); 3] = [(0, 0), (-1, 2), (5, -3)];
+
+fn goldens_path() -> PathBuf {
+    return PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/goldens.txt");
+}
+
+fn config(seed: &str) -> EngineConfig {
+    let mut config = EngineConfig::default();
+    config.world_gen.seed = Seed::from_text(seed);
+    config.world_gen.base_height = 100;
+    config.world_gen.sea_level = 100;
+    config.world_gen.noise.octaves = 5;
+    config.world_gen.noise.frequency = 16.0;
+    config.world_gen.noise.amplitude = 12.0;
+    return config;
+}
+
+/// Hash every block in a chunk, in a fixed order, with FNV-1a.
+fn hash_chunk(chunk: &Chunk) -> u64 {
+    let mut bytes = Vec::with_capacity((CHUNK_SIZE * CHUNK_SIZE * CHUNK_HEIGHT) as usize);
+    for z in 0..CHUNK_HEIGHT {
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                bytes.push(chunk.get(ChunkPos::new(x, y, z).unwrap()) as u8);
+            }
+        }
+    }
+    return ge_util::seed::fnv1a(&bytes);
+}
+
+/// Generate every case and return its hash, keyed by `<seed> <x>,<y> <stage>`.
+fn generate() -> BTreeMap<String, u64> {
+    let mut hashes = BTreeMap::new();
+    for seed in SEEDS {
+        let config = config(seed);
+        let gen =
+            NoiseChunkGenerator::with_noise(Noise::from(&config), config.world_gen.base_height);
+        let sea_level = SeaLevel::new(&config);
+
+        for (x, y) in OFFSETS {
+            let offset = ChunkOffset::new(x, y, 0).unwrap();
+            let stages: [(&str, &[&dyn ChunkTransformation]); 3] = [
+                ("terrain", &[]),
+                ("sea_level", &[&sea_level]),
+                ("surface", &[&sea_level, &SimpleSurfacePainter]),
+            ];
+            for (stage, trns) in stages {
+                let chunk = trns.iter().fold(gen.generate(offset), |chunk, t| {
+                    return chunk.apply_transformation(*t);
+                });
+                hashes.insert(format!("{seed} {x},{y} {stage}"), hash_chunk(&chunk));
+            }
+        }
+    }
+    return hashes;
+}
+
+fn parse(goldens: &str) -> BTreeMap<String, u64> {
+    return goldens
+        .lines()
+        .filter(|l| return !l.trim().is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            let (key, hash) = l.rsplit_once(' ').expect("invalid golden line");
+            return (
+                key.to_owned(),
+                u64::from_str_radix(hash, 16).expect("invalid golden hash"),
+            );
+        })
+        .collect();
+}
+
+#[test]
+fn chunks_match_goldens() {
+    let hashes = generate();
+
+    if std::env::var_os("GE_UPDATE_GOLDENS").is_some() {
+        let mut out = String::from(
+            "# generated by `GE_UPDATE_GOLDENS=1 cargo test -p ge-world --test golden`\n",
+        );
+        for (key, hash) in &hashes {
+            out.push_str(&format!("{key} {hash:016x}\n"));
+        }
+        std::fs::write(goldens_path(), out).unwrap();
+        return;
+    }
+
+    let goldens = parse(&std::fs::read_to_string(goldens_path()).expect("goldens are missing"));
+    let mismatches = hashes
+        .iter()
+        .filter(|(key, hash)| return goldens.get(*key) != Some(hash))
+        .map(|(key, _)| return key.as_str())
+        .collect::<Vec<_>>();
+    assert!(
+        mismatches.is_empty() && goldens.len() == hashes.len(),
+        "world generation changed for {mismatches:?}, regenerate the goldens with \
+         `GE_UPDATE_GOLDENS=1` if this is intended"
+    );
+}
+
+#[test]
+fn async_and_fixed_generators_match() {
+    for seed in SEEDS {
+        let config = config(seed);
+        let trns = || return vec![SeaLevel::new(&config).into(), SimpleSurfacePainter.into()];
+        let noise = Noise::from(&config);
+
+        let mut a = AsyncWorldGenerator::new(noise, (2, 2), trns(), &config)
+            .generate()
+            .chunks;
+        let mut b = FixedWorldGenerator::new(noise, (2, 2), trns(), &config)
+            .generate()
+            .chunks;
+        a.sort_by_key(|c| return (c.position.x(), c.position.y()));
+        b.sort_by_key(|c| return (c.position.x(), c.position.y()));
+
+        assert_eq!(9, a.len());
+        assert!(a == b, "generators differ for seed {seed}");
+    }
+}